@@ -0,0 +1,221 @@
+// Reading audit logs.
+//
+// AuditLogChange values are whatever JSON Discord sent for the key, so old_parsed() and
+// new_parsed() parse them by key into an AuditLogValue: a role list for $add/$remove,
+// overwrites for permission_overwrites, a permission bit set for allow/deny/permissions, and so
+// on. AuditLog resolves an entry's user_id and target_id against the users, webhooks, threads
+// and other lists sent with it, which is what it takes to show an entry as "nelly kicked Wumpus".
+//
+// See https://discord.com/developers/docs/resources/audit-log#audit-log-change-object
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::types::*;
+
+// A role in a member's $add or $remove change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditLogRole {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum AuditLogValue {
+    Text(String),
+    Id(String),
+    Ids(Vec<String>),
+    Integer(i64),
+    Bool(bool),
+    Permissions(u64),
+    Roles(Vec<AuditLogRole>),
+    PermissionOverwrites(Vec<PermissionOverwrite>),
+    // Keys not listed here, or a value that doesn't have its key's usual shape
+    Other(Value),
+}
+
+const ID_KEYS: &[&str] = &[
+    "id",
+    "afk_channel_id",
+    "application_id",
+    "channel_id",
+    "creator_id",
+    "guild_id",
+    "inviter_id",
+    "owner_id",
+    "public_updates_channel_id",
+    "rules_channel_id",
+    "safety_alerts_channel_id",
+    "system_channel_id",
+    "widget_channel_id",
+];
+
+const ID_LIST_KEYS: &[&str] = &["applied_tags", "exempt_channels", "exempt_roles"];
+
+const PERMISSION_KEYS: &[&str] = &["allow", "deny", "permissions"];
+
+impl AuditLogValue {
+    pub fn parse(key: &str, value: &Value) -> Self {
+        let parsed = match key {
+            "$add" | "$remove" => serde_json::from_value(value.clone())
+                .ok()
+                .map(AuditLogValue::Roles),
+            "permission_overwrites" => serde_json::from_value(value.clone())
+                .ok()
+                .map(AuditLogValue::PermissionOverwrites),
+            key if PERMISSION_KEYS.contains(&key) => value
+                .as_str()
+                .and_then(|bits| bits.parse().ok())
+                .map(AuditLogValue::Permissions),
+            key if ID_KEYS.contains(&key) => {
+                value.as_str().map(|id| AuditLogValue::Id(id.to_string()))
+            }
+            key if ID_LIST_KEYS.contains(&key) => serde_json::from_value(value.clone())
+                .ok()
+                .map(AuditLogValue::Ids),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| match value {
+            Value::String(text) => AuditLogValue::Text(text.clone()),
+            Value::Bool(value) => AuditLogValue::Bool(*value),
+            Value::Number(number) if number.is_i64() => {
+                AuditLogValue::Integer(number.as_i64().unwrap_or_default())
+            }
+            value => AuditLogValue::Other(value.clone()),
+        })
+    }
+}
+
+impl AuditLogChange {
+    pub fn old_parsed(&self) -> Option<AuditLogValue> {
+        self.old_value
+            .as_ref()
+            .map(|value| AuditLogValue::parse(&self.key, value))
+    }
+
+    pub fn new_parsed(&self) -> Option<AuditLogValue> {
+        self.new_value
+            .as_ref()
+            .map(|value| AuditLogValue::parse(&self.key, value))
+    }
+}
+
+impl AuditLogEntry {
+    // The change to a key, e.g. "nick" or "$add"
+    pub fn change(&self, key: &str) -> Option<&AuditLogChange> {
+        self.changes
+            .as_ref()?
+            .iter()
+            .find(|change| change.key == key)
+    }
+}
+
+// What an entry's target_id refers to, when it came with the audit log
+#[derive(Debug, Clone, Copy)]
+pub enum AuditLogTarget<'a> {
+    User(&'a User),
+    Webhook(&'a Webhook),
+    Integration(&'a PartialIntegration),
+    Thread(&'a Channel),
+    ScheduledEvent(&'a GuildScheduledEvent),
+    AutoModerationRule(&'a AutoModerationRule),
+    ApplicationCommand(&'a ApplicationCommand),
+    // Channels, roles, emojis and the like, which aren't sent with the log
+    Id(&'a str),
+}
+
+impl AuditLogTarget<'_> {
+    // How to show it, falling back to the id
+    pub fn name(&self) -> String {
+        match self {
+            AuditLogTarget::User(user) => user_display_name(user).to_string(),
+            AuditLogTarget::Webhook(webhook) => webhook.name.clone().unwrap_or(webhook.id.clone()),
+            AuditLogTarget::Integration(integration) => integration.name.clone(),
+            AuditLogTarget::Thread(thread) => thread.name.clone().unwrap_or(thread.id.clone()),
+            AuditLogTarget::ScheduledEvent(event) => event.name.clone(),
+            AuditLogTarget::AutoModerationRule(rule) => rule.name.clone(),
+            AuditLogTarget::ApplicationCommand(command) => format!("/{}", command.name),
+            AuditLogTarget::Id(id) => id.to_string(),
+        }
+    }
+}
+
+// Global name if set, else username
+pub fn user_display_name(user: &User) -> &str {
+    user.global_name.as_deref().unwrap_or(&user.username)
+}
+
+impl AuditLog {
+    pub fn user(&self, user_id: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.id == user_id)
+    }
+
+    // Who did it, None for entries Discord made itself
+    pub fn entry_user(&self, entry: &AuditLogEntry) -> Option<&User> {
+        self.user(entry.user_id.as_deref()?)
+    }
+
+    // What it was done to, looked up in the list that goes with the action type
+    pub fn entry_target<'a>(&'a self, entry: &'a AuditLogEntry) -> Option<AuditLogTarget<'a>> {
+        let target_id = entry.target_id.as_deref()?;
+        let found = match entry.action_type {
+            // Members, bots, message authors and users automod acted on
+            AuditLogEvent::MemberKick
+            | AuditLogEvent::MemberBanAdd
+            | AuditLogEvent::MemberBanRemove
+            | AuditLogEvent::MemberUpdate
+            | AuditLogEvent::MemberRoleUpdate
+            | AuditLogEvent::BotAdd
+            | AuditLogEvent::MessageDelete
+            | AuditLogEvent::MessagePin
+            | AuditLogEvent::MessageUnpin
+            | AuditLogEvent::AutoModerationBlockMessage
+            | AuditLogEvent::AutoModerationFlagToChannel
+            | AuditLogEvent::AutoModerationUserCommunicationDisabled => {
+                self.user(target_id).map(AuditLogTarget::User)
+            }
+            AuditLogEvent::WebhookCreate
+            | AuditLogEvent::WebhookUpdate
+            | AuditLogEvent::WebhookDelete => self
+                .webhooks
+                .iter()
+                .find(|webhook| webhook.id == target_id)
+                .map(AuditLogTarget::Webhook),
+            AuditLogEvent::IntegrationCreate
+            | AuditLogEvent::IntegrationUpdate
+            | AuditLogEvent::IntegrationDelete => self
+                .integrations
+                .iter()
+                .find(|integration| integration.id == target_id)
+                .map(AuditLogTarget::Integration),
+            AuditLogEvent::GuildScheduledEventCreate
+            | AuditLogEvent::GuildScheduledEventUpdate
+            | AuditLogEvent::GuildScheduledEventDelete => self
+                .guild_scheduled_events
+                .iter()
+                .find(|event| event.id == target_id)
+                .map(AuditLogTarget::ScheduledEvent),
+            AuditLogEvent::ThreadCreate
+            | AuditLogEvent::ThreadUpdate
+            | AuditLogEvent::ThreadDelete => self
+                .threads
+                .iter()
+                .find(|thread| thread.id == target_id)
+                .map(AuditLogTarget::Thread),
+            AuditLogEvent::ApplicationCommandPermissionUpdate => self
+                .application_commands
+                .iter()
+                .find(|command| command.id == target_id)
+                .map(AuditLogTarget::ApplicationCommand),
+            AuditLogEvent::AutoModerationRuleCreate
+            | AuditLogEvent::AutoModerationRuleUpdate
+            | AuditLogEvent::AutoModerationRuleDelete => self
+                .auto_moderation_rules
+                .iter()
+                .find(|rule| rule.id == target_id)
+                .map(AuditLogTarget::AutoModerationRule),
+            _ => None,
+        };
+        Some(found.unwrap_or(AuditLogTarget::Id(target_id)))
+    }
+}
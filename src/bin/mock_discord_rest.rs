@@ -0,0 +1,43 @@
+// Runs the mock Discord REST API until killed, printing every request it answers.
+//
+//   cargo run --features mock_server --bin mock_discord_rest -- [address] [gateway url]
+//
+// Starts with a guild holding a #general channel and one other member, whose ids are printed.
+
+use std::time::Duration;
+
+use discord_api::{MockDiscord, MockRestServer};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or("127.0.0.1:8080".to_string());
+
+    let mut discord = MockDiscord::default();
+    if let Some(gateway_url) = args.next() {
+        discord.gateway_url = gateway_url;
+    }
+    let guild_id = discord.add_guild("Mock Guild");
+    let channel_id = discord.add_channel(&guild_id, "general", 0);
+    let user_id = discord.add_user("nelly");
+    discord.add_member(&guild_id, &user_id);
+
+    let server = MockRestServer::bind(&address, discord)?;
+    println!("mock Discord REST API at {}", server.base_url());
+    println!(
+        "bot {}, guild {}, channel {}, user {}",
+        server.discord().bot_user_id(),
+        guild_id,
+        channel_id,
+        user_id
+    );
+
+    let mut printed = 0;
+    loop {
+        std::thread::sleep(Duration::from_millis(100));
+        let requests = server.requests();
+        for request in &requests[printed..] {
+            println!("{} {} {}", request.status, request.method, request.path);
+        }
+        printed = requests.len();
+    }
+}
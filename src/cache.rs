@@ -0,0 +1,723 @@
+// In-memory cache of gateway state.
+//
+// Fed every GatewayReceiveEvent, it keeps guilds (with their roles and emojis), channels,
+// threads, members, voice states, presences and the most recent messages per channel, so
+// callers can look them up instead of asking the HTTP API.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::gateway_api::*;
+use super::types::*;
+
+// Which resources to keep. Disabled resources are never stored, which saves memory on
+// large bots that only need part of the state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    pub guilds: bool,
+    pub channels: bool,
+    pub threads: bool,
+    pub roles: bool,
+    pub members: bool,
+    pub emojis: bool,
+    pub voice_states: bool,
+    pub presences: bool,
+    pub messages_per_channel: usize, // 0 disables the message cache
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            guilds: true,
+            channels: true,
+            threads: true,
+            roles: true,
+            members: true,
+            emojis: true,
+            voice_states: true,
+            presences: true,
+            messages_per_channel: 100,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Cache {
+    config: CacheConfig,
+    current_user: Option<User>,
+    // Roles and emojis live on the guild, the lists below are stripped from it
+    guilds: HashMap<String, Guild>,
+    // Guild channels and threads by channel id
+    channels: HashMap<String, Channel>,
+    // guild_id -> user_id -> value
+    members: HashMap<String, HashMap<String, GuildMember>>,
+    voice_states: HashMap<String, HashMap<String, VoiceState>>,
+    presences: HashMap<String, HashMap<String, PresenceUpdate>>,
+    // channel_id -> messages, oldest first
+    messages: HashMap<String, VecDeque<Message>>,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    // Changes what is kept from now on and drops anything no longer enabled
+    pub fn set_config(&mut self, config: CacheConfig) {
+        if !config.guilds {
+            self.guilds.clear();
+        }
+        for guild in self.guilds.values_mut() {
+            if !config.roles {
+                guild.roles.clear();
+            }
+            if !config.emojis {
+                guild.emojis.clear();
+            }
+        }
+        if !config.channels {
+            self.channels.retain(|_, channel| is_thread(channel));
+        }
+        if !config.threads {
+            self.channels.retain(|_, channel| !is_thread(channel));
+        }
+        if !config.members {
+            self.members.clear();
+        }
+        if !config.voice_states {
+            self.voice_states.clear();
+        }
+        if !config.presences {
+            self.presences.clear();
+        }
+        for messages in self.messages.values_mut() {
+            drop_oldest(messages, config.messages_per_channel);
+        }
+        self.messages.retain(|_, messages| !messages.is_empty());
+        self.config = config;
+    }
+
+    pub fn update(&mut self, event: GatewayReceiveEvent) {
+        match event {
+            GatewayReceiveEvent::Ready(ready) => self.current_user = Some(ready.user),
+            GatewayReceiveEvent::UserUpdate(user) => self.current_user = Some(user),
+            GatewayReceiveEvent::GuildCreate(GuildCreatePayload::Available(guild)) => {
+                self.insert_guild(*guild)
+            }
+            GatewayReceiveEvent::GuildCreate(GuildCreatePayload::Unavailable(unavailable)) => {
+                if let Some(guild) = self.guilds.get_mut(&unavailable.id) {
+                    guild.unavailable = Some(true);
+                }
+            }
+            GatewayReceiveEvent::GuildUpdate(guild) => self.insert_guild(guild),
+            GatewayReceiveEvent::GuildDelete(unavailable) => match unavailable.removed() {
+                true => self.remove_guild(&unavailable.id),
+                // An outage, the guild comes back with a GuildCreate
+                false => {
+                    if let Some(guild) = self.guilds.get_mut(&unavailable.id) {
+                        guild.unavailable = Some(true);
+                    }
+                }
+            },
+            GatewayReceiveEvent::ChannelCreate(channel)
+            | GatewayReceiveEvent::ChannelUpdate(channel)
+            | GatewayReceiveEvent::ThreadCreate(channel)
+            | GatewayReceiveEvent::ThreadUpdate(channel) => self.insert_channel(channel),
+            GatewayReceiveEvent::ChannelDelete(channel) => self.remove_channel(&channel.id),
+            GatewayReceiveEvent::ThreadDelete(thread) => self.remove_channel(&thread.id),
+            GatewayReceiveEvent::ThreadListSync(sync) => {
+                for thread in sync.threads {
+                    self.insert_channel(thread);
+                }
+            }
+            GatewayReceiveEvent::GuildRoleCreate(GuildRoleCreate { guild_id, role })
+            | GatewayReceiveEvent::GuildRoleUpdate(GuildRoleUpdate { guild_id, role }) => {
+                if !self.config.roles {
+                    return;
+                }
+                if let Some(guild) = self.guilds.get_mut(&guild_id) {
+                    guild.roles.retain(|r| r.id != role.id);
+                    guild.roles.push(role);
+                }
+            }
+            GatewayReceiveEvent::GuildRoleDelete(delete) => {
+                if let Some(guild) = self.guilds.get_mut(&delete.guild_id) {
+                    guild.roles.retain(|role| role.id != delete.role_id);
+                }
+                if let Some(members) = self.members.get_mut(&delete.guild_id) {
+                    for member in members.values_mut() {
+                        member.roles.retain(|role_id| *role_id != delete.role_id);
+                    }
+                }
+            }
+            GatewayReceiveEvent::GuildEmojisUpdate(update) => {
+                if !self.config.emojis {
+                    return;
+                }
+                if let Some(guild) = self.guilds.get_mut(&update.guild_id) {
+                    guild.emojis = update.emojis;
+                }
+            }
+            GatewayReceiveEvent::GuildMemberAdd(member) => {
+                if let Some(guild_id) = member.guild_id.clone() {
+                    self.insert_member(&guild_id, member);
+                }
+            }
+            GatewayReceiveEvent::GuildMemberUpdate(update) => self.update_member(update),
+            GatewayReceiveEvent::GuildMemberRemove(remove) => {
+                if let Some(members) = self.members.get_mut(&remove.guild_id) {
+                    members.remove(&remove.user.id);
+                }
+                if let Some(presences) = self.presences.get_mut(&remove.guild_id) {
+                    presences.remove(&remove.user.id);
+                }
+            }
+            GatewayReceiveEvent::GuildMembersChunk(chunk) => {
+                for member in chunk.members {
+                    self.insert_member(&chunk.guild_id, member);
+                }
+                for presence in chunk.presences.unwrap_or_default() {
+                    self.insert_presence(&chunk.guild_id, presence);
+                }
+            }
+            GatewayReceiveEvent::VoiceStateUpdate(voice_state) => {
+                if let Some(guild_id) = voice_state.guild_id.clone() {
+                    self.insert_voice_state(&guild_id, voice_state);
+                }
+            }
+            GatewayReceiveEvent::PresenceUpdate(presence) => {
+                if let Some(guild_id) = presence.guild_id.clone() {
+                    self.insert_presence(&guild_id, presence);
+                }
+            }
+            GatewayReceiveEvent::MessageCreate(message) => self.insert_message(message),
+            GatewayReceiveEvent::MessageUpdate(message) => {
+                if let Some(cached) = self
+                    .messages
+                    .get_mut(&message.channel_id)
+                    .and_then(|messages| messages.iter_mut().find(|m| m.id == message.id))
+                {
+                    merge_message(cached, message);
+                }
+            }
+            GatewayReceiveEvent::MessageDelete(delete) => {
+                if let Some(messages) = self.messages.get_mut(&delete.channel_id) {
+                    messages.retain(|m| m.id != delete.id);
+                }
+            }
+            GatewayReceiveEvent::MessageDeleteBulk(delete) => {
+                if let Some(messages) = self.messages.get_mut(&delete.channel_id) {
+                    messages.retain(|m| !delete.ids.contains(&m.id));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Queries
+
+    pub fn current_user(&self) -> Option<&User> {
+        self.current_user.as_ref()
+    }
+
+    pub fn guild(&self, guild_id: &str) -> Option<&Guild> {
+        self.guilds.get(guild_id)
+    }
+
+    pub fn guilds(&self) -> impl Iterator<Item = &Guild> {
+        self.guilds.values()
+    }
+
+    // A guild channel or thread
+    pub fn channel(&self, channel_id: &str) -> Option<&Channel> {
+        self.channels.get(channel_id)
+    }
+
+    // Channels of a guild sorted by position, not including threads
+    pub fn guild_channels(&self, guild_id: &str) -> Vec<&Channel> {
+        let mut channels = self
+            .channels
+            .values()
+            .filter(|channel| channel.guild_id.as_deref() == Some(guild_id))
+            .filter(|channel| !is_thread(channel))
+            .collect::<Vec<_>>();
+        channels.sort_by_key(|channel| (channel.position, channel.id.clone()));
+        channels
+    }
+
+    // Active threads of a guild
+    pub fn threads(&self, guild_id: &str) -> Vec<&Channel> {
+        self.channels
+            .values()
+            .filter(|channel| channel.guild_id.as_deref() == Some(guild_id))
+            .filter(|channel| is_thread(channel))
+            .collect()
+    }
+
+    pub fn role(&self, guild_id: &str, role_id: &str) -> Option<&Role> {
+        self.guild(guild_id)?
+            .roles
+            .iter()
+            .find(|role| role.id == role_id)
+    }
+
+    pub fn roles(&self, guild_id: &str) -> &[Role] {
+        self.guild(guild_id)
+            .map(|guild| guild.roles.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn emoji(&self, guild_id: &str, emoji_id: &str) -> Option<&Emoji> {
+        self.guild(guild_id)?
+            .emojis
+            .iter()
+            .find(|emoji| emoji.id.as_deref() == Some(emoji_id))
+    }
+
+    pub fn emojis(&self, guild_id: &str) -> &[Emoji] {
+        self.guild(guild_id)
+            .map(|guild| guild.emojis.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn member(&self, guild_id: &str, user_id: &str) -> Option<&GuildMember> {
+        self.members.get(guild_id)?.get(user_id)
+    }
+
+    // Only the members seen so far: those in GuildCreate, chunks and member events
+    pub fn members(&self, guild_id: &str) -> Vec<&GuildMember> {
+        self.members
+            .get(guild_id)
+            .map(|members| members.values().collect())
+            .unwrap_or_default()
+    }
+
+    // Cached members with the role. Every member has the @everyone role, whose id is the guild id.
+    pub fn role_members(&self, guild_id: &str, role_id: &str) -> Vec<&GuildMember> {
+        self.members(guild_id)
+            .into_iter()
+            .filter(|member| role_id == guild_id || member.roles.iter().any(|id| id == role_id))
+            .collect()
+    }
+
+    // Channels the member can see
+    pub fn member_channels(&self, guild_id: &str, user_id: &str) -> Vec<&Channel> {
+        self.guild_channels(guild_id)
+            .into_iter()
+            .filter(|channel| {
+                self.permissions(&channel.id, user_id)
+                    .is_some_and(|permissions| permissions & PERMISSION_VIEW_CHANNEL != 0)
+            })
+            .collect()
+    }
+
+    // The member's permissions in a channel, from their roles and the channel's overwrites.
+    // Threads use their parent channel. None if the channel, guild or member isn't cached.
+    // https://discord.com/developers/docs/topics/permissions#permission-overwrites
+    pub fn permissions(&self, channel_id: &str, user_id: &str) -> Option<u64> {
+        let mut channel = self.channel(channel_id)?;
+        if is_thread(channel) {
+            channel = self.channel(channel.parent_id.as_deref()?)?;
+        }
+        let guild_id = channel.guild_id.as_deref()?;
+        let guild = self.guild(guild_id)?;
+        let member = self.member(guild_id, user_id)?;
+
+        if guild.owner_id == user_id {
+            return Some(u64::MAX);
+        }
+        let role_permissions = |role_id: &str| {
+            self.role(guild_id, role_id)
+                .and_then(|role| role.permissions.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        let mut permissions = member
+            .roles
+            .iter()
+            .fold(role_permissions(guild_id), |permissions, role_id| {
+                permissions | role_permissions(role_id)
+            });
+        if permissions & PERMISSION_ADMINISTRATOR != 0 {
+            return Some(u64::MAX);
+        }
+
+        let overwrites = channel.permission_overwrites.as_deref().unwrap_or_default();
+        let mut apply = |allow: u64, deny: u64| {
+            permissions &= !deny;
+            permissions |= allow;
+        };
+        let parse =
+            |bits: &Option<String>| bits.as_deref().and_then(|b| b.parse().ok()).unwrap_or(0);
+        let bits = |o: &PermissionOverwrite| (parse(&o.allow), parse(&o.deny));
+        // @everyone, then all of the member's roles at once, then the member
+        if let Some(everyone) = overwrites.iter().find(|o| o.id == guild_id) {
+            let (allow, deny) = bits(everyone);
+            apply(allow, deny);
+        }
+        let (allow, deny) = overwrites
+            .iter()
+            .filter(|o| o.overwrite_type == OverwriteType::Role && member.roles.contains(&o.id))
+            .map(bits)
+            .fold((0, 0), |(allow, deny), o| (allow | o.0, deny | o.1));
+        apply(allow, deny);
+        if let Some(own) = overwrites
+            .iter()
+            .find(|o| o.overwrite_type == OverwriteType::Member && o.id == user_id)
+        {
+            let (allow, deny) = bits(own);
+            apply(allow, deny);
+        }
+        Some(permissions)
+    }
+
+    pub fn voice_state(&self, guild_id: &str, user_id: &str) -> Option<&VoiceState> {
+        self.voice_states.get(guild_id)?.get(user_id)
+    }
+
+    // Everyone connected to a voice or stage channel
+    pub fn voice_states(&self, guild_id: &str) -> Vec<&VoiceState> {
+        self.voice_states
+            .get(guild_id)
+            .map(|voice_states| voice_states.values().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn presence(&self, guild_id: &str, user_id: &str) -> Option<&PresenceUpdate> {
+        self.presences.get(guild_id)?.get(user_id)
+    }
+
+    pub fn message(&self, channel_id: &str, message_id: &str) -> Option<&Message> {
+        self.messages
+            .get(channel_id)?
+            .iter()
+            .find(|message| message.id == message_id)
+    }
+
+    // The most recent messages seen in the channel, oldest first
+    pub fn messages(&self, channel_id: &str) -> Vec<&Message> {
+        self.messages
+            .get(channel_id)
+            .map(|messages| messages.iter().collect())
+            .unwrap_or_default()
+    }
+
+    // Answers a CacheQuery as JSON, null when nothing is cached
+    pub fn query(&self, query: &CacheQuery) -> serde_json::Value {
+        match query {
+            CacheQuery::CurrentUser => serde_json::json!(self.current_user()),
+            CacheQuery::Guild { guild_id } => serde_json::json!(self.guild(guild_id)),
+            CacheQuery::Guilds => serde_json::json!(self.guilds().collect::<Vec<_>>()),
+            CacheQuery::Channel { channel_id } => serde_json::json!(self.channel(channel_id)),
+            CacheQuery::GuildChannels { guild_id } => {
+                serde_json::json!(self.guild_channels(guild_id))
+            }
+            CacheQuery::Threads { guild_id } => serde_json::json!(self.threads(guild_id)),
+            CacheQuery::Role { guild_id, role_id } => {
+                serde_json::json!(self.role(guild_id, role_id))
+            }
+            CacheQuery::Roles { guild_id } => serde_json::json!(self.roles(guild_id)),
+            CacheQuery::Emojis { guild_id } => serde_json::json!(self.emojis(guild_id)),
+            CacheQuery::Member { guild_id, user_id } => {
+                serde_json::json!(self.member(guild_id, user_id))
+            }
+            CacheQuery::Members { guild_id } => serde_json::json!(self.members(guild_id)),
+            CacheQuery::RoleMembers { guild_id, role_id } => {
+                serde_json::json!(self.role_members(guild_id, role_id))
+            }
+            CacheQuery::MemberChannels { guild_id, user_id } => {
+                serde_json::json!(self.member_channels(guild_id, user_id))
+            }
+            CacheQuery::Permissions {
+                channel_id,
+                user_id,
+            } => serde_json::json!(self
+                .permissions(channel_id, user_id)
+                .map(|permissions| permissions.to_string())),
+            CacheQuery::VoiceStates { guild_id } => {
+                serde_json::json!(self.voice_states(guild_id))
+            }
+            CacheQuery::Presence { guild_id, user_id } => {
+                serde_json::json!(self.presence(guild_id, user_id))
+            }
+            CacheQuery::Messages { channel_id } => serde_json::json!(self.messages(channel_id)),
+        }
+    }
+
+    fn insert_guild(&mut self, mut guild: Guild) {
+        let guild_id = guild.id.clone();
+        for mut channel in guild.channels.take().unwrap_or_default() {
+            // Channels in GuildCreate come without a guild_id
+            channel.guild_id = Some(guild_id.clone());
+            self.insert_channel(channel);
+        }
+        for mut thread in guild.threads.take().unwrap_or_default() {
+            thread.guild_id = Some(guild_id.clone());
+            self.insert_channel(thread);
+        }
+        for member in guild.members.take().unwrap_or_default() {
+            self.insert_member(&guild_id, member);
+        }
+        for voice_state in guild.voice_states.take().unwrap_or_default() {
+            self.insert_voice_state(&guild_id, voice_state);
+        }
+        for presence in guild.presences.take().unwrap_or_default() {
+            self.insert_presence(&guild_id, presence);
+        }
+        if !self.config.guilds {
+            return;
+        }
+        if !self.config.roles {
+            guild.roles.clear();
+        }
+        if !self.config.emojis {
+            guild.emojis.clear();
+        }
+        // GuildUpdate doesn't carry member_count or joined_at, keep the ones from GuildCreate
+        if let Some(cached) = self.guilds.get(&guild_id) {
+            guild.member_count = guild.member_count.or(cached.member_count);
+            guild.joined_at = guild.joined_at.take().or(cached.joined_at.clone());
+            guild.large = guild.large.or(cached.large);
+        }
+        self.guilds.insert(guild_id, guild);
+    }
+
+    fn remove_guild(&mut self, guild_id: &str) {
+        self.guilds.remove(guild_id);
+        let channel_ids = self
+            .channels
+            .values()
+            .filter(|channel| channel.guild_id.as_deref() == Some(guild_id))
+            .map(|channel| channel.id.clone())
+            .collect::<Vec<_>>();
+        for channel_id in channel_ids {
+            self.remove_channel(&channel_id);
+        }
+        self.members.remove(guild_id);
+        self.voice_states.remove(guild_id);
+        self.presences.remove(guild_id);
+    }
+
+    fn insert_channel(&mut self, channel: Channel) {
+        // DMs are not cached, there is no guild to tie them to
+        if channel.guild_id.is_none() {
+            return;
+        }
+        let enabled = match is_thread(&channel) {
+            true => self.config.threads,
+            false => self.config.channels,
+        };
+        if enabled {
+            self.channels.insert(channel.id.clone(), channel);
+        }
+    }
+
+    fn remove_channel(&mut self, channel_id: &str) {
+        self.channels.remove(channel_id);
+        self.messages.remove(channel_id);
+        // Its threads go too, without a THREAD_DELETE. A category's channels stay, they get
+        // a CHANNEL_UPDATE.
+        let threads: Vec<String> = self
+            .channels
+            .values()
+            .filter(|channel| {
+                is_thread(channel) && channel.parent_id.as_deref() == Some(channel_id)
+            })
+            .map(|channel| channel.id.clone())
+            .collect();
+        for thread in threads {
+            self.channels.remove(&thread);
+            self.messages.remove(&thread);
+        }
+    }
+
+    fn insert_member(&mut self, guild_id: &str, member: GuildMember) {
+        if !self.config.members {
+            return;
+        }
+        let Some(user_id) = member.user.as_ref().map(|user| user.id.clone()) else {
+            return;
+        };
+        self.members
+            .entry(guild_id.to_string())
+            .or_default()
+            .insert(user_id, member);
+    }
+
+    fn update_member(&mut self, update: GuildMemberUpdate) {
+        if !self.config.members {
+            return;
+        }
+        let members = self.members.entry(update.guild_id.clone()).or_default();
+        match members.get_mut(&update.user.id) {
+            Some(member) => {
+                member.roles = update.roles;
+                member.nick = update.nick;
+                member.avatar = update.avatar;
+                member.premium_since = update.premium_since;
+                member.communication_disabled_until = update.communication_disabled_until;
+                member.deaf = update.deaf.unwrap_or(member.deaf);
+                member.mute = update.mute.unwrap_or(member.mute);
+                member.pending = update.pending.or(member.pending);
+                if let Some(joined_at) = update.joined_at {
+                    member.joined_at = joined_at;
+                }
+                member.user = Some(update.user);
+            }
+            None => {
+                members.insert(
+                    update.user.id.clone(),
+                    GuildMember {
+                        user: Some(update.user),
+                        nick: update.nick,
+                        avatar: update.avatar,
+                        roles: update.roles,
+                        joined_at: update.joined_at.unwrap_or_default(),
+                        premium_since: update.premium_since,
+                        deaf: update.deaf.unwrap_or(false),
+                        mute: update.mute.unwrap_or(false),
+                        flags: 0,
+                        pending: update.pending,
+                        permissions: None,
+                        guild_id: Some(update.guild_id),
+                        communication_disabled_until: update.communication_disabled_until,
+                    },
+                );
+            }
+        }
+    }
+
+    fn insert_voice_state(&mut self, guild_id: &str, voice_state: VoiceState) {
+        if !self.config.voice_states {
+            return;
+        }
+        let voice_states = self.voice_states.entry(guild_id.to_string()).or_default();
+        // A null channel means the user left voice
+        match voice_state.channel_id {
+            Some(_) => voice_states.insert(voice_state.user_id.clone(), voice_state),
+            None => voice_states.remove(&voice_state.user_id),
+        };
+    }
+
+    fn insert_presence(&mut self, guild_id: &str, presence: PresenceUpdate) {
+        if !self.config.presences {
+            return;
+        }
+        let presences = self.presences.entry(guild_id.to_string()).or_default();
+        match presence.status.as_str() {
+            "offline" => presences.remove(&presence.user.id),
+            _ => presences.insert(presence.user.id.clone(), presence),
+        };
+    }
+
+    fn insert_message(&mut self, message: Message) {
+        let max = self.config.messages_per_channel;
+        if max == 0 {
+            return;
+        }
+        let messages = self.messages.entry(message.channel_id.clone()).or_default();
+        messages.push_back(message);
+        drop_oldest(messages, max);
+    }
+}
+
+// A read from the cache, see DiscordApiRequest::Cache
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CacheQuery {
+    CurrentUser,
+    Guild { guild_id: String },
+    Guilds,
+    Channel { channel_id: String },
+    GuildChannels { guild_id: String },
+    Threads { guild_id: String },
+    Role { guild_id: String, role_id: String },
+    Roles { guild_id: String },
+    Emojis { guild_id: String },
+    Member { guild_id: String, user_id: String },
+    Members { guild_id: String },
+    RoleMembers { guild_id: String, role_id: String },
+    MemberChannels { guild_id: String, user_id: String },
+    // Permission bits as a string, like Discord sends them
+    Permissions { channel_id: String, user_id: String },
+    VoiceStates { guild_id: String },
+    Presence { guild_id: String, user_id: String },
+    Messages { channel_id: String },
+}
+
+fn is_thread(channel: &Channel) -> bool {
+    matches!(
+        channel.channel_type,
+        ChannelType::AnnouncementThread | ChannelType::PublicThread | ChannelType::PrivateThread
+    )
+}
+
+// Applies a MESSAGE_UPDATE to a cached message. Edits carry the whole message, but
+// updates like embed unfurls only carry what changed, so those keep everything else
+fn merge_message(cached: &mut Message, update: Message) {
+    if update.author.is_some() {
+        *cached = update;
+        return;
+    }
+    macro_rules! merge {
+        ($($field:ident),* $(,)?) => {
+            $(if update.$field.is_some() {
+                cached.$field = update.$field;
+            })*
+        };
+    }
+    merge!(
+        content,
+        timestamp,
+        edited_timestamp,
+        tts,
+        mentions,
+        mention_everyone,
+        mention_channels,
+        reactions,
+        nonce,
+        pinned,
+        webhook_id,
+        message_type,
+        activity,
+        application,
+        application_id,
+        message_reference,
+        flags,
+        referenced_message,
+        interaction,
+        interaction_metadata,
+        thread,
+        components,
+        sticker_items,
+        stickers,
+        position,
+        role_subscription_data,
+        resolved,
+        poll,
+        guild_id,
+        member,
+    );
+    if !update.mention_roles.is_empty() {
+        cached.mention_roles = update.mention_roles;
+    }
+    if !update.attachments.is_empty() {
+        cached.attachments = update.attachments;
+    }
+    if !update.embeds.is_empty() {
+        cached.embeds = update.embeds;
+    }
+}
+
+// Drops the oldest messages down to max
+fn drop_oldest(messages: &mut VecDeque<Message>, max: usize) {
+    while messages.len() > max {
+        messages.pop_front();
+    }
+}
@@ -0,0 +1,331 @@
+// Image URLs on the Discord CDN
+// https://discord.com/developers/docs/reference#image-formatting
+
+use super::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+    Lottie, // Stickers only
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Lottie => "json",
+        }
+    }
+}
+
+// Animated images have hashes starting with a_
+pub fn is_animated_hash(hash: &str) -> bool {
+    hash.starts_with("a_")
+}
+
+// Builds a URL for a hashed image such as an avatar, icon or banner.
+// `format` defaults to gif for animated hashes and png otherwise,
+// and `size` must be a power of two between 16 and 4096.
+pub fn image_url(
+    path: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    let animated = is_animated_hash(hash);
+    let format = match format {
+        Some(ImageFormat::Gif) if !animated => {
+            return Err(anyhow::anyhow!("gif requested for static image {}", hash));
+        }
+        Some(ImageFormat::Lottie) => {
+            return Err(anyhow::anyhow!("lottie is only available for stickers"));
+        }
+        Some(format) => format,
+        None if animated => ImageFormat::Gif,
+        None => ImageFormat::Png,
+    };
+
+    Ok(format!(
+        "{}/{}/{}.{}{}",
+        CDN_URL,
+        path,
+        hash,
+        format.extension(),
+        size_param(size)?
+    ))
+}
+
+pub fn user_avatar_url(
+    user_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("avatars/{}", user_id), hash, format, size)
+}
+
+pub fn user_banner_url(
+    user_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("banners/{}", user_id), hash, format, size)
+}
+
+pub fn guild_member_avatar_url(
+    guild_id: &str,
+    user_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(
+        &format!("guilds/{}/users/{}/avatars", guild_id, user_id),
+        hash,
+        format,
+        size,
+    )
+}
+
+pub fn guild_icon_url(
+    guild_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("icons/{}", guild_id), hash, format, size)
+}
+
+pub fn guild_banner_url(
+    guild_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("banners/{}", guild_id), hash, format, size)
+}
+
+pub fn guild_splash_url(
+    guild_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("splashes/{}", guild_id), hash, format, size)
+}
+
+pub fn guild_discovery_splash_url(
+    guild_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(
+        &format!("discovery-splashes/{}", guild_id),
+        hash,
+        format,
+        size,
+    )
+}
+
+pub fn role_icon_url(
+    role_id: &str,
+    hash: &str,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    image_url(&format!("role-icons/{}", role_id), hash, format, size)
+}
+
+// Emojis are addressed by id, animation comes from the emoji object rather than a hash prefix
+pub fn emoji_url(
+    emoji_id: &str,
+    animated: bool,
+    format: Option<ImageFormat>,
+    size: Option<u32>,
+) -> anyhow::Result<String> {
+    let format = match format {
+        Some(ImageFormat::Gif) if !animated => {
+            return Err(anyhow::anyhow!(
+                "gif requested for static emoji {}",
+                emoji_id
+            ));
+        }
+        Some(ImageFormat::Lottie) => {
+            return Err(anyhow::anyhow!("lottie is only available for stickers"));
+        }
+        Some(format) => format,
+        None if animated => ImageFormat::Gif,
+        None => ImageFormat::Png,
+    };
+
+    Ok(format!(
+        "{}/emojis/{}.{}{}",
+        CDN_URL,
+        emoji_id,
+        format.extension(),
+        size_param(size)?
+    ))
+}
+
+// The extension is fixed by the sticker's format: png for PNG and APNG, json for Lottie, gif for GIF
+pub fn sticker_url(sticker_id: &str, format_type: StickerFormatType) -> anyhow::Result<String> {
+    let format = match format_type {
+        StickerFormatType::Png | StickerFormatType::Apng => ImageFormat::Png,
+        StickerFormatType::Lottie => ImageFormat::Lottie,
+        StickerFormatType::Gif => ImageFormat::Gif,
+        StickerFormatType::Unknown(value) => {
+            return Err(anyhow::anyhow!("unknown sticker format type {}", value));
+        }
+    };
+
+    Ok(format!(
+        "{}/stickers/{}.{}",
+        CDN_URL,
+        sticker_id,
+        format.extension()
+    ))
+}
+
+// Users on the new username system (discriminator "0" or missing) get (id >> 22) % 6,
+// legacy users get discriminator % 5
+pub fn default_avatar_index(user_id: &str, discriminator: Option<&str>) -> u64 {
+    match discriminator.and_then(|d| d.parse::<u64>().ok()) {
+        Some(discriminator) if discriminator != 0 => discriminator % 5,
+        _ => (user_id.parse::<u64>().unwrap_or(0) >> 22) % 6,
+    }
+}
+
+pub fn default_avatar_url(user_id: &str, discriminator: Option<&str>) -> String {
+    format!(
+        "{}/embed/avatars/{}.png",
+        CDN_URL,
+        default_avatar_index(user_id, discriminator)
+    )
+}
+
+fn size_param(size: Option<u32>) -> anyhow::Result<String> {
+    match size {
+        None => Ok(String::new()),
+        Some(size) if size.is_power_of_two() && (16..=4096).contains(&size) => {
+            Ok(format!("?size={}", size))
+        }
+        Some(size) => Err(anyhow::anyhow!(
+            "image size must be a power of two between 16 and 4096, got {}",
+            size
+        )),
+    }
+}
+
+impl User {
+    // The user's avatar, or their default avatar when none is set
+    pub fn avatar_url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<String> {
+        match &self.avatar {
+            Some(hash) => user_avatar_url(&self.id, hash, format, size),
+            None => Ok(default_avatar_url(&self.id, self.discriminator.as_deref())),
+        }
+    }
+}
+
+impl GuildMember {
+    // The member's guild avatar, falling back to the user's avatar
+    pub fn avatar_url(
+        &self,
+        guild_id: &str,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(user) = &self.user else {
+            return Ok(None);
+        };
+        match &self.avatar {
+            Some(hash) => guild_member_avatar_url(guild_id, &user.id, hash, format, size).map(Some),
+            None => user.avatar_url(format, size).map(Some),
+        }
+    }
+}
+
+impl Guild {
+    pub fn icon_url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        self.icon
+            .as_deref()
+            .map(|hash| guild_icon_url(&self.id, hash, format, size))
+            .transpose()
+    }
+
+    pub fn banner_url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        self.banner
+            .as_deref()
+            .map(|hash| guild_banner_url(&self.id, hash, format, size))
+            .transpose()
+    }
+
+    pub fn splash_url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        self.splash
+            .as_deref()
+            .map(|hash| guild_splash_url(&self.id, hash, format, size))
+            .transpose()
+    }
+}
+
+impl Role {
+    pub fn icon_url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        self.icon
+            .as_deref()
+            .map(|hash| role_icon_url(&self.id, hash, format, size))
+            .transpose()
+    }
+}
+
+impl Emoji {
+    // None for unicode emojis, which have no id
+    pub fn url(
+        &self,
+        format: Option<ImageFormat>,
+        size: Option<u32>,
+    ) -> anyhow::Result<Option<String>> {
+        self.id
+            .as_deref()
+            .map(|id| emoji_url(id, self.animated.unwrap_or(false), format, size))
+            .transpose()
+    }
+}
+
+impl Sticker {
+    pub fn url(&self) -> anyhow::Result<String> {
+        sticker_url(&self.id, self.format_type)
+    }
+}
+
+impl StickerItem {
+    pub fn url(&self) -> anyhow::Result<String> {
+        sticker_url(&self.id, self.format_type)
+    }
+}
@@ -0,0 +1,272 @@
+// Declarative application command sync.
+//
+// Compares the commands registered with Discord against the ones we want and plans the
+// fewest creates, updates and deletes to get there. Unlike bulk overwriting, unchanged
+// commands keep their ids and permissions.
+
+use super::http_api::*;
+use super::types::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandScope {
+    Global,
+    Guild(String),
+}
+
+#[derive(Debug)]
+pub enum CommandSyncAction {
+    Create(NewApplicationCommand),
+    Update {
+        command_id: String,
+        command: NewApplicationCommand,
+    },
+    Delete {
+        command_id: String,
+        name: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct CommandSyncPlan {
+    pub application_id: String,
+    pub scope: CommandScope,
+    pub actions: Vec<CommandSyncAction>,
+}
+
+impl CommandSyncPlan {
+    // Commands are matched by name and type, which Discord treats as unique.
+    // Deletes come first so a freed name can be reused by a create.
+    pub fn new(
+        application_id: &str,
+        scope: CommandScope,
+        current: &[ApplicationCommand],
+        desired: Vec<NewApplicationCommand>,
+    ) -> Self {
+        let mut actions = Vec::new();
+
+        for existing in current {
+            let still_wanted = desired
+                .iter()
+                .any(|command| same_command(existing, command));
+            if !still_wanted {
+                actions.push(CommandSyncAction::Delete {
+                    command_id: existing.id.clone(),
+                    name: existing.name.clone(),
+                });
+            }
+        }
+
+        for command in desired {
+            match current
+                .iter()
+                .find(|existing| same_command(existing, &command))
+            {
+                None => actions.push(CommandSyncAction::Create(command)),
+                Some(existing) => {
+                    // Where a command is available is only compared when declared, Discord
+                    // fills it in otherwise
+                    let mut current = existing.to_new();
+                    if command.integration_types.is_none() {
+                        current.integration_types = None;
+                    }
+                    if command.contexts.is_none() {
+                        current.contexts = None;
+                    }
+                    if comparable(&current) != comparable(&command) {
+                        actions.push(CommandSyncAction::Update {
+                            command_id: existing.id.clone(),
+                            command,
+                        });
+                    }
+                }
+            }
+        }
+
+        Self {
+            application_id: application_id.to_string(),
+            scope,
+            actions,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    // The calls that carry out the plan, in order
+    pub fn calls(&self) -> Vec<HttpApiCall> {
+        let application_id = self.application_id.clone();
+        self.actions
+            .iter()
+            .map(|action| {
+                HttpApiCall::Commands(match (action, &self.scope) {
+                    (CommandSyncAction::Create(command), CommandScope::Global) => {
+                        CommandsCall::CreateApplicationCommand {
+                            application_id: application_id.clone(),
+                            command: command.clone(),
+                        }
+                    }
+                    (CommandSyncAction::Create(command), CommandScope::Guild(guild_id)) => {
+                        CommandsCall::CreateGuildApplicationCommand {
+                            application_id: application_id.clone(),
+                            guild_id: guild_id.clone(),
+                            command: command.clone(),
+                        }
+                    }
+                    (
+                        CommandSyncAction::Update {
+                            command_id,
+                            command,
+                        },
+                        CommandScope::Global,
+                    ) => CommandsCall::UpdateApplicationCommand {
+                        application_id: application_id.clone(),
+                        command_id: command_id.clone(),
+                        command: command.clone(),
+                    },
+                    (
+                        CommandSyncAction::Update {
+                            command_id,
+                            command,
+                        },
+                        CommandScope::Guild(guild_id),
+                    ) => CommandsCall::UpdateGuildApplicationCommand {
+                        application_id: application_id.clone(),
+                        guild_id: guild_id.clone(),
+                        command_id: command_id.clone(),
+                        command: command.clone(),
+                    },
+                    (CommandSyncAction::Delete { command_id, .. }, CommandScope::Global) => {
+                        CommandsCall::DeleteGlobalApplicationCommand {
+                            application_id: application_id.clone(),
+                            command_id: command_id.clone(),
+                        }
+                    }
+                    (
+                        CommandSyncAction::Delete { command_id, .. },
+                        CommandScope::Guild(guild_id),
+                    ) => CommandsCall::DeleteGuildApplicationCommand {
+                        application_id: application_id.clone(),
+                        guild_id: guild_id.clone(),
+                        command_id: command_id.clone(),
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+// One line per action, for dry runs
+impl std::fmt::Display for CommandSyncPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scope = match &self.scope {
+            CommandScope::Global => "global".to_string(),
+            CommandScope::Guild(guild_id) => format!("guild {}", guild_id),
+        };
+        if self.actions.is_empty() {
+            return write!(f, "{} commands are up to date", scope);
+        }
+        write!(f, "{} command changes:", scope)?;
+        for action in &self.actions {
+            match action {
+                CommandSyncAction::Create(command) => write!(f, "\n  create {}", command.name)?,
+                CommandSyncAction::Update {
+                    command_id,
+                    command,
+                } => write!(f, "\n  update {} ({})", command.name, command_id)?,
+                CommandSyncAction::Delete { command_id, name } => {
+                    write!(f, "\n  delete {} ({})", name, command_id)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Lists the commands currently registered in a scope
+pub fn list_commands_call(application_id: &str, scope: &CommandScope) -> HttpApiCall {
+    HttpApiCall::Commands(match scope {
+        CommandScope::Global => CommandsCall::ListApplicationCommands {
+            application_id: application_id.to_string(),
+            with_localizations: Some(true),
+        },
+        CommandScope::Guild(guild_id) => CommandsCall::ListGuildApplicationCommands {
+            application_id: application_id.to_string(),
+            guild_id: guild_id.clone(),
+            with_localizations: Some(true),
+        },
+    })
+}
+
+// Fetches the current commands with `send`, plans the changes, and unless `dry_run`
+// applies them. `send` makes one call and returns the response body. Returns the plan,
+// print it to see what changed or, for a dry run, what would.
+pub fn sync_commands<F>(
+    application_id: &str,
+    scope: CommandScope,
+    desired: Vec<NewApplicationCommand>,
+    dry_run: bool,
+    mut send: F,
+) -> anyhow::Result<CommandSyncPlan>
+where
+    F: FnMut(&HttpApiCall) -> anyhow::Result<Vec<u8>>,
+{
+    let body = send(&list_commands_call(application_id, &scope))?;
+    let current = serde_json::from_slice::<Vec<ApplicationCommand>>(&body)?;
+    let plan = CommandSyncPlan::new(application_id, scope, &current, desired);
+
+    if !dry_run {
+        for call in plan.calls() {
+            send(&call)?;
+        }
+    }
+
+    Ok(plan)
+}
+
+fn same_command(existing: &ApplicationCommand, command: &NewApplicationCommand) -> bool {
+    let command_type =
+        |t: Option<ApplicationCommandType>| t.unwrap_or(ApplicationCommandType::ChatInput);
+    existing.name == command.name
+        && command_type(existing.command_type) == command_type(command.command_type)
+}
+
+// The command as JSON, with the type it gets when none is given
+fn comparable(command: &NewApplicationCommand) -> serde_json::Value {
+    let mut value = normalize(serde_json::json!(command));
+    value["type"] = serde_json::json!(command
+        .command_type
+        .unwrap_or(ApplicationCommandType::ChatInput));
+    value
+}
+
+// Drops the differences that don't matter, so an unchanged command compares equal to
+// what Discord sends back: nulls and defaults, empty lists, and integer vs float numbers.
+// Values are normalized first, so an object left empty by that is dropped too.
+fn normalize(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, normalize(value)))
+                .filter(|(key, value)| match (key.as_str(), value) {
+                    (_, serde_json::Value::Null) => false,
+                    ("required" | "autocomplete" | "nsfw", serde_json::Value::Bool(false)) => false,
+                    ("dm_permission", serde_json::Value::Bool(true)) => false,
+                    ("description", serde_json::Value::String(s)) => !s.is_empty(),
+                    (_, serde_json::Value::Array(items)) => !items.is_empty(),
+                    (_, serde_json::Value::Object(map)) => !map.is_empty(),
+                    _ => true,
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(normalize).collect())
+        }
+        serde_json::Value::Number(n) => n
+            .as_f64()
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Number(n)),
+        value => value,
+    }
+}
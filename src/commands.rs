@@ -0,0 +1,581 @@
+// Slash command framework: declare a command once with slash_command!, then use the same
+// declaration to register it and to parse incoming interactions into a typed struct.
+//
+//     slash_command! {
+//         pub struct Ban("mod ban", "Ban a member") {
+//             user: UserArg => "Member to ban",
+//             reason: Option<String> => "Shown in the audit log",
+//         }
+//     }
+//
+//     let router = CommandRouter::new()
+//         .describe("mod", "Moderation tools")
+//         .command(|interaction, ban: Ban| Ok(InteractionResponse::ChannelMessageWithSource(...)));
+//
+//     router.application_commands()? // Register with sync_commands
+//     router.dispatch(&interaction) // For each InteractionCreate
+//
+// A name with spaces is a subcommand: "mod ban" is the ban subcommand of /mod, and
+// "mod user ban" the ban subcommand in the user group of /mod. Discord allows no deeper
+// nesting, and a command with subcommands can't be run itself.
+
+use std::collections::HashMap;
+
+use super::http_api::*;
+use super::types::*;
+
+#[derive(Debug)]
+pub enum CommandError {
+    // The interaction is not an application command
+    NotACommand,
+    // No handler for this command path, e.g. "mod ban"
+    UnknownCommand(String),
+    MissingOption(String),
+    InvalidOption {
+        name: String,
+        expected: ApplicationCommandOptionType,
+        value: serde_json::Value,
+    },
+    Handler(anyhow::Error),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::NotACommand => write!(f, "interaction is not an application command"),
+            CommandError::UnknownCommand(path) => write!(f, "unknown command /{}", path),
+            CommandError::MissingOption(name) => write!(f, "missing required option `{}`", name),
+            CommandError::InvalidOption {
+                name,
+                expected,
+                value,
+            } => write!(
+                f,
+                "option `{}` should be of type {:?}, got {}",
+                name, expected, value
+            ),
+            CommandError::Handler(e) => write!(f, "command failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+// The options of one invoked command, with the subcommand path resolved
+pub struct CommandArgs<'a> {
+    pub path: Vec<&'a str>,
+    pub options: Vec<&'a ApplicationCommandInteractionDataOption>,
+    pub resolved: Option<&'a ResolvedData>,
+}
+
+impl<'a> CommandArgs<'a> {
+    pub fn from_interaction(interaction: &'a Interaction) -> Result<Self, CommandError> {
+        let data = match (&interaction.interaction_type, &interaction.data) {
+            (InteractionKind::ApplicationCommand, Some(data)) => data,
+            _ => return Err(CommandError::NotACommand),
+        };
+
+        let mut path = vec![data.name.as_str()];
+        let mut options: Vec<&ApplicationCommandInteractionDataOption> =
+            data.options.iter().flatten().collect();
+
+        // Walk down through the subcommand group and subcommand, if any
+        while let [option] = options.as_slice() {
+            match option.option_type {
+                ApplicationCommandOptionType::SubCommandGroup
+                | ApplicationCommandOptionType::SubCommand => {
+                    path.push(option.name.as_str());
+                    options = option.options.iter().flatten().collect();
+                }
+                _ => break,
+            }
+        }
+
+        Ok(Self {
+            path,
+            options,
+            resolved: data.resolved.as_ref(),
+        })
+    }
+
+    // The command path joined with spaces, as used in slash_command! names
+    pub fn path_string(&self) -> String {
+        self.path.join(" ")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'a ApplicationCommandInteractionDataOption> {
+        self.options
+            .iter()
+            .find(|option| option.name == name)
+            .copied()
+    }
+}
+
+// A type that can be declared as, and extracted from, one command option
+pub trait CommandArgument: Sized {
+    const OPTION_TYPE: ApplicationCommandOptionType;
+    const REQUIRED: bool = true;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError>;
+
+    fn option(name: &str, description: &str) -> ApplicationCommandOption {
+        let mut option = ApplicationCommandOption::new(Self::OPTION_TYPE, name, description);
+        option.required = Some(Self::REQUIRED);
+        option
+    }
+}
+
+// Looks up a required option and checks its type
+fn required_option<'a>(
+    name: &str,
+    option_type: ApplicationCommandOptionType,
+    args: &CommandArgs<'a>,
+) -> Result<&'a ApplicationCommandInteractionDataOption, CommandError> {
+    let option = args
+        .get(name)
+        .ok_or_else(|| CommandError::MissingOption(name.to_string()))?;
+    if option.option_type != option_type {
+        return Err(invalid_option(name, option_type, &option.value));
+    }
+    Ok(option)
+}
+
+fn invalid_option(
+    name: &str,
+    expected: ApplicationCommandOptionType,
+    value: &serde_json::Value,
+) -> CommandError {
+    CommandError::InvalidOption {
+        name: name.to_string(),
+        expected,
+        value: value.clone(),
+    }
+}
+
+// Snowflake option values come through as strings
+fn id_option(
+    name: &str,
+    option_type: ApplicationCommandOptionType,
+    args: &CommandArgs,
+) -> Result<String, CommandError> {
+    let option = required_option(name, option_type, args)?;
+    option
+        .value
+        .as_str()
+        .map(|id| id.to_string())
+        .ok_or_else(|| invalid_option(name, option_type, &option.value))
+}
+
+impl CommandArgument for String {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::String;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let option = required_option(name, Self::OPTION_TYPE, args)?;
+        option
+            .value
+            .as_str()
+            .map(|value| value.to_string())
+            .ok_or_else(|| invalid_option(name, Self::OPTION_TYPE, &option.value))
+    }
+}
+
+impl CommandArgument for i64 {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Integer;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let option = required_option(name, Self::OPTION_TYPE, args)?;
+        option
+            .value
+            .as_i64()
+            .ok_or_else(|| invalid_option(name, Self::OPTION_TYPE, &option.value))
+    }
+}
+
+impl CommandArgument for f64 {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Number;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let option = required_option(name, Self::OPTION_TYPE, args)?;
+        option
+            .value
+            .as_f64()
+            .ok_or_else(|| invalid_option(name, Self::OPTION_TYPE, &option.value))
+    }
+}
+
+impl CommandArgument for bool {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Boolean;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let option = required_option(name, Self::OPTION_TYPE, args)?;
+        option
+            .value
+            .as_bool()
+            .ok_or_else(|| invalid_option(name, Self::OPTION_TYPE, &option.value))
+    }
+}
+
+// Missing optional options become None, present ones must still be valid
+impl<T: CommandArgument> CommandArgument for Option<T> {
+    const OPTION_TYPE: ApplicationCommandOptionType = T::OPTION_TYPE;
+    const REQUIRED: bool = false;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        match args.get(name) {
+            Some(_) => T::extract(name, args).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserArg {
+    pub id: String,
+    pub user: Option<User>,
+    pub member: Option<GuildMember>, // Only in guilds, and without its user field
+}
+
+impl CommandArgument for UserArg {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::User;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let id = id_option(name, Self::OPTION_TYPE, args)?;
+        Ok(resolve_user(id, args))
+    }
+}
+
+fn resolve_user(id: String, args: &CommandArgs) -> UserArg {
+    let resolved = args.resolved;
+    UserArg {
+        user: resolved
+            .and_then(|r| r.users.as_ref())
+            .and_then(|users| users.get(&id))
+            .cloned(),
+        member: resolved
+            .and_then(|r| r.members.as_ref())
+            .and_then(|members| members.get(&id))
+            .cloned(),
+        id,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelArg {
+    pub id: String,
+    pub channel: Option<Channel>, // Partial: id, name, type, permissions and thread fields
+}
+
+impl CommandArgument for ChannelArg {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Channel;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let id = id_option(name, Self::OPTION_TYPE, args)?;
+        Ok(Self {
+            channel: args
+                .resolved
+                .and_then(|r| r.channels.as_ref())
+                .and_then(|channels| channels.get(&id))
+                .cloned(),
+            id,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoleArg {
+    pub id: String,
+    pub role: Option<Role>,
+}
+
+impl CommandArgument for RoleArg {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Role;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let id = id_option(name, Self::OPTION_TYPE, args)?;
+        Ok(Self {
+            role: args
+                .resolved
+                .and_then(|r| r.roles.as_ref())
+                .and_then(|roles| roles.get(&id))
+                .cloned(),
+            id,
+        })
+    }
+}
+
+// Either a user or a role, whichever the id resolves to
+#[derive(Debug, Clone)]
+pub struct MentionableArg {
+    pub id: String,
+    pub user: Option<User>,
+    pub member: Option<GuildMember>,
+    pub role: Option<Role>,
+}
+
+impl CommandArgument for MentionableArg {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Mentionable;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let id = id_option(name, Self::OPTION_TYPE, args)?;
+        let role = args
+            .resolved
+            .and_then(|r| r.roles.as_ref())
+            .and_then(|roles| roles.get(&id))
+            .cloned();
+        let UserArg { id, user, member } = resolve_user(id, args);
+        Ok(Self {
+            id,
+            user,
+            member,
+            role,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachmentArg {
+    pub id: String,
+    pub attachment: Option<Attachment>,
+}
+
+impl CommandArgument for AttachmentArg {
+    const OPTION_TYPE: ApplicationCommandOptionType = ApplicationCommandOptionType::Attachment;
+
+    fn extract(name: &str, args: &CommandArgs) -> Result<Self, CommandError> {
+        let id = id_option(name, Self::OPTION_TYPE, args)?;
+        Ok(Self {
+            attachment: args
+                .resolved
+                .and_then(|r| r.attachments.as_ref())
+                .and_then(|attachments| attachments.get(&id))
+                .cloned(),
+            id,
+        })
+    }
+}
+
+// Implemented by slash_command!
+pub trait SlashCommand: Sized {
+    // Full path, e.g. "ping" or "mod ban"
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+
+    fn options() -> Vec<ApplicationCommandOption>;
+    fn from_args(args: &CommandArgs) -> Result<Self, CommandError>;
+}
+
+// Declares a command struct whose fields are its options, see the top of this file.
+// Field names are the option names, so keep them lowercase.
+#[macro_export]
+macro_rules! slash_command {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($command:literal, $description:literal) {
+            $($field:ident: $ty:ty => $field_description:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        $vis struct $name {
+            $(pub $field: $ty,)*
+        }
+
+        impl $crate::SlashCommand for $name {
+            const NAME: &'static str = $command;
+            const DESCRIPTION: &'static str = $description;
+
+            fn options() -> Vec<$crate::ApplicationCommandOption> {
+                vec![$(
+                    <$ty as $crate::CommandArgument>::option(stringify!($field), $field_description),
+                )*]
+            }
+
+            #[allow(unused_variables)]
+            fn from_args(args: &$crate::CommandArgs) -> Result<Self, $crate::CommandError> {
+                Ok(Self {
+                    $($field: <$ty as $crate::CommandArgument>::extract(stringify!($field), args)?,)*
+                })
+            }
+        }
+    };
+}
+
+type CommandHandler =
+    Box<dyn Fn(&Interaction, &CommandArgs) -> Result<InteractionResponse, CommandError>>;
+
+struct RoutedCommand {
+    path: Vec<String>,
+    description: String,
+    options: Vec<ApplicationCommandOption>,
+    handler: CommandHandler,
+}
+
+#[derive(Default)]
+pub struct CommandRouter {
+    commands: Vec<RoutedCommand>,
+    // Descriptions for parent commands and groups, which have no handler of their own
+    descriptions: HashMap<String, String>,
+}
+
+impl CommandRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn command<C, F>(mut self, handler: F) -> Self
+    where
+        C: SlashCommand,
+        F: Fn(&Interaction, C) -> anyhow::Result<InteractionResponse> + 'static,
+    {
+        self.commands.push(RoutedCommand {
+            path: C::NAME.split_whitespace().map(|x| x.to_string()).collect(),
+            description: C::DESCRIPTION.to_string(),
+            options: C::options(),
+            handler: Box::new(move |interaction, args| {
+                let command = C::from_args(args)?;
+                handler(interaction, command).map_err(CommandError::Handler)
+            }),
+        });
+        self
+    }
+
+    // Sets the description of a parent command or group, e.g. describe("mod", "Moderation tools")
+    pub fn describe(mut self, path: &str, description: &str) -> Self {
+        self.descriptions
+            .insert(normalize_path(path), description.to_string());
+        self
+    }
+
+    // One top level command per name, with subcommands and groups nested under it.
+    // Fails for paths Discord would reject, before anything is registered.
+    pub fn application_commands(&self) -> anyhow::Result<Vec<NewApplicationCommand>> {
+        self.check_commands()?;
+        let mut top_level: Vec<NewApplicationCommand> = Vec::new();
+
+        for command in &self.commands {
+            let root = command.path[0].as_str();
+            if command.path.len() == 1 {
+                top_level.push(NewApplicationCommand {
+                    name: root.to_string(),
+                    description: Some(command.description.clone()),
+                    command_type: Some(ApplicationCommandType::ChatInput),
+                    options: Some(command.options.clone()),
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            let position = match top_level.iter().position(|c| c.name == root) {
+                Some(position) => position,
+                None => {
+                    top_level.push(NewApplicationCommand {
+                        name: root.to_string(),
+                        description: Some(self.description_for(&command.path[..1])),
+                        command_type: Some(ApplicationCommandType::ChatInput),
+                        options: Some(Vec::new()),
+                        ..Default::default()
+                    });
+                    top_level.len() - 1
+                }
+            };
+            let mut options = top_level[position].options.get_or_insert_with(Vec::new);
+
+            // Descend into the group for three part paths
+            if command.path.len() == 3 {
+                let group_name = &command.path[1];
+                let group = match options.iter().position(|o| &o.name == group_name) {
+                    Some(position) => position,
+                    None => {
+                        let mut group = ApplicationCommandOption::new(
+                            ApplicationCommandOptionType::SubCommandGroup,
+                            group_name.clone(),
+                            self.description_for(&command.path[..2]),
+                        );
+                        group.options = Some(Vec::new());
+                        options.push(group);
+                        options.len() - 1
+                    }
+                };
+                options = options[group].options.get_or_insert_with(Vec::new);
+            }
+
+            let mut subcommand = ApplicationCommandOption::new(
+                ApplicationCommandOptionType::SubCommand,
+                command.path[command.path.len() - 1].clone(),
+                command.description.clone(),
+            );
+            subcommand.options = Some(command.options.clone());
+            options.push(subcommand);
+        }
+
+        Ok(top_level)
+    }
+
+    // Parses the interaction into the matching command and runs its handler
+    pub fn dispatch(&self, interaction: &Interaction) -> Result<InteractionResponse, CommandError> {
+        let args = CommandArgs::from_interaction(interaction)?;
+        let command = self
+            .commands
+            .iter()
+            .find(|command| {
+                command
+                    .path
+                    .iter()
+                    .map(|x| x.as_str())
+                    .eq(args.path.iter().copied())
+            })
+            .ok_or_else(|| CommandError::UnknownCommand(args.path_string()))?;
+        (command.handler)(interaction, &args)
+    }
+
+    // Every path has one to three words, and none is another's prefix, e.g. "mod" next to
+    // "mod ban" or "mod user" next to "mod user ban", which includes declaring one twice.
+    // Required options also come before optional ones, as Discord expects.
+    fn check_commands(&self) -> anyhow::Result<()> {
+        for (i, command) in self.commands.iter().enumerate() {
+            if command.path.is_empty() || command.path.len() > 3 {
+                return Err(anyhow::anyhow!(
+                    "command /{} must have one to three words",
+                    command.path.join(" ")
+                ));
+            }
+            let first_optional = command
+                .options
+                .iter()
+                .position(|option| option.required != Some(true));
+            if let Some(first_optional) = first_optional {
+                if let Some(required) = command.options[first_optional..]
+                    .iter()
+                    .find(|option| option.required == Some(true))
+                {
+                    return Err(anyhow::anyhow!(
+                        "command /{} has required option `{}` after optional option `{}`",
+                        command.path.join(" "),
+                        required.name,
+                        command.options[first_optional].name
+                    ));
+                }
+            }
+            for other in &self.commands[i + 1..] {
+                let shared = command.path.len().min(other.path.len());
+                if command.path[..shared] == other.path[..shared] {
+                    return Err(anyhow::anyhow!(
+                        "commands /{} and /{} clash, a command can't be declared twice or have subcommands",
+                        command.path.join(" "),
+                        other.path.join(" ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn description_for(&self, path: &[String]) -> String {
+        let path = path.join(" ");
+        self.descriptions.get(&path).cloned().unwrap_or(path)
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    path.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,256 @@
+// Fluent construction of embeds, checked against Discord's limits
+// https://discord.com/developers/docs/resources/message#embed-object-embed-limits
+
+use super::http_api::format_timestamp;
+use super::types::*;
+
+pub const EMBED_TITLE_MAX: usize = 256;
+pub const EMBED_DESCRIPTION_MAX: usize = 4096;
+pub const EMBED_FIELDS_MAX: usize = 25;
+pub const EMBED_FIELD_NAME_MAX: usize = 256;
+pub const EMBED_FIELD_VALUE_MAX: usize = 1024;
+pub const EMBED_FOOTER_TEXT_MAX: usize = 2048;
+pub const EMBED_AUTHOR_NAME_MAX: usize = 256;
+// Sum of title, description, field names and values, footer text and author name
+pub const EMBED_TOTAL_MAX: usize = 6000;
+
+// Returned by EmbedBuilder::build, `field` names the offending part, e.g. "fields[3].value"
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedValidationError {
+    pub field: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for EmbedValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "embed {}: {}", self.field, self.reason)
+    }
+}
+
+impl std::error::Error for EmbedValidationError {}
+
+#[derive(Debug, Default)]
+pub struct EmbedBuilder {
+    title: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+    timestamp: Option<String>,
+    color: Option<u32>,
+    footer: Option<EmbedFooter>,
+    image: Option<String>,
+    thumbnail: Option<String>,
+    author: Option<EmbedAuthor>,
+    fields: Vec<EmbedField>,
+    invalid_color: Option<String>,
+}
+
+impl EmbedBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn timestamp(mut self, time: std::time::SystemTime) -> Self {
+        self.timestamp = Some(format_timestamp(time));
+        self
+    }
+
+    // Raw 0xRRGGBB value
+    pub fn color(mut self, color: u32) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn color_rgb(self, r: u8, g: u8, b: u8) -> Self {
+        self.color(u32::from_be_bytes([0, r, g, b]))
+    }
+
+    // Accepts "#5865F2", "5865F2" or "0x5865F2", an invalid value fails build()
+    pub fn color_hex(mut self, hex: &str) -> Self {
+        let digits = ["#", "0x", "0X"]
+            .iter()
+            .find_map(|prefix| hex.strip_prefix(prefix))
+            .unwrap_or(hex);
+        // from_str_radix alone would take a sign, e.g. "+5865F"
+        let valid = digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit());
+        match u32::from_str_radix(digits, 16) {
+            Ok(color) if valid => {
+                self.color = Some(color);
+                self.invalid_color = None;
+            }
+            _ => self.invalid_color = Some(hex.to_string()),
+        }
+        self
+    }
+
+    pub fn footer(mut self, text: impl Into<String>, icon_url: Option<String>) -> Self {
+        self.footer = Some(EmbedFooter {
+            text: text.into(),
+            icon_url,
+            proxy_icon_url: None,
+        });
+        self
+    }
+
+    pub fn author(
+        mut self,
+        name: impl Into<String>,
+        url: Option<String>,
+        icon_url: Option<String>,
+    ) -> Self {
+        self.author = Some(EmbedAuthor {
+            name: name.into(),
+            url,
+            icon_url,
+            proxy_icon_url: None,
+        });
+        self
+    }
+
+    pub fn image(mut self, url: impl Into<String>) -> Self {
+        self.image = Some(url.into());
+        self
+    }
+
+    pub fn thumbnail(mut self, url: impl Into<String>) -> Self {
+        self.thumbnail = Some(url.into());
+        self
+    }
+
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push(EmbedField {
+            name: name.into(),
+            value: value.into(),
+            inline: None,
+        });
+        self
+    }
+
+    pub fn inline_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push(EmbedField {
+            name: name.into(),
+            value: value.into(),
+            inline: Some(true),
+        });
+        self
+    }
+
+    // Checks every limit and returns the embed, or the first part that breaks one
+    pub fn build(self) -> Result<Embed, EmbedValidationError> {
+        if let Some(hex) = &self.invalid_color {
+            return Err(invalid(
+                "color",
+                format!("{:?} is not a 6 digit hex color", hex),
+            ));
+        }
+        if self.fields.len() > EMBED_FIELDS_MAX {
+            return Err(invalid(
+                "fields",
+                format!("{} fields, max {}", self.fields.len(), EMBED_FIELDS_MAX),
+            ));
+        }
+
+        let mut total = 0;
+        if let Some(title) = &self.title {
+            total += check_length("title", title, EMBED_TITLE_MAX)?;
+        }
+        if let Some(description) = &self.description {
+            total += check_length("description", description, EMBED_DESCRIPTION_MAX)?;
+        }
+        for (i, field) in self.fields.iter().enumerate() {
+            check_not_empty(&format!("fields[{}].name", i), &field.name)?;
+            total += check_length(
+                &format!("fields[{}].name", i),
+                &field.name,
+                EMBED_FIELD_NAME_MAX,
+            )?;
+            check_not_empty(&format!("fields[{}].value", i), &field.value)?;
+            total += check_length(
+                &format!("fields[{}].value", i),
+                &field.value,
+                EMBED_FIELD_VALUE_MAX,
+            )?;
+        }
+        if let Some(footer) = &self.footer {
+            total += check_length("footer.text", &footer.text, EMBED_FOOTER_TEXT_MAX)?;
+        }
+        if let Some(author) = &self.author {
+            total += check_length("author.name", &author.name, EMBED_AUTHOR_NAME_MAX)?;
+        }
+        if total > EMBED_TOTAL_MAX {
+            return Err(invalid(
+                "total",
+                format!("{} characters combined, max {}", total, EMBED_TOTAL_MAX),
+            ));
+        }
+
+        Ok(Embed {
+            title: self.title,
+            embed_type: Some("rich".to_string()),
+            description: self.description,
+            url: self.url,
+            timestamp: self.timestamp,
+            color: self.color,
+            footer: self.footer,
+            image: self.image.map(|url| EmbedImage {
+                url,
+                proxy_url: None,
+                height: None,
+                width: None,
+            }),
+            thumbnail: self.thumbnail.map(|url| EmbedThumbnail {
+                url,
+                proxy_url: None,
+                height: None,
+                width: None,
+            }),
+            video: None,
+            provider: None,
+            author: self.author,
+            fields: match self.fields.is_empty() {
+                true => None,
+                false => Some(self.fields),
+            },
+        })
+    }
+}
+
+fn invalid(field: &str, reason: String) -> EmbedValidationError {
+    EmbedValidationError {
+        field: field.to_string(),
+        reason,
+    }
+}
+
+// Returns the length in characters so it can count towards the combined limit
+fn check_length(field: &str, value: &str, max: usize) -> Result<usize, EmbedValidationError> {
+    let length = value.chars().count();
+    if length > max {
+        return Err(invalid(
+            field,
+            format!("{} characters, max {}", length, max),
+        ));
+    }
+    Ok(length)
+}
+
+fn check_not_empty(field: &str, value: &str) -> Result<(), EmbedValidationError> {
+    if value.trim().is_empty() {
+        return Err(invalid(field, "must not be empty".to_string()));
+    }
+    Ok(())
+}
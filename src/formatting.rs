@@ -0,0 +1,463 @@
+// Message formatting: mentions, timestamps, markdown escaping and long message splitting
+// https://discord.com/developers/docs/reference#message-formatting
+
+pub const MESSAGE_CONTENT_MAX: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampStyle {
+    ShortTime,     // t, 16:20
+    LongTime,      // T, 16:20:30
+    ShortDate,     // d, 20/04/2021
+    LongDate,      // D, 20 April 2021
+    ShortDateTime, // f, 20 April 2021 16:20 (default)
+    LongDateTime,  // F, Tuesday, 20 April 2021 16:20
+    Relative,      // R, 2 months ago
+}
+
+impl TimestampStyle {
+    pub fn as_char(&self) -> char {
+        match self {
+            TimestampStyle::ShortTime => 't',
+            TimestampStyle::LongTime => 'T',
+            TimestampStyle::ShortDate => 'd',
+            TimestampStyle::LongDate => 'D',
+            TimestampStyle::ShortDateTime => 'f',
+            TimestampStyle::LongDateTime => 'F',
+            TimestampStyle::Relative => 'R',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            't' => Some(TimestampStyle::ShortTime),
+            'T' => Some(TimestampStyle::LongTime),
+            'd' => Some(TimestampStyle::ShortDate),
+            'D' => Some(TimestampStyle::LongDate),
+            'f' => Some(TimestampStyle::ShortDateTime),
+            'F' => Some(TimestampStyle::LongDateTime),
+            'R' => Some(TimestampStyle::Relative),
+            _ => None,
+        }
+    }
+}
+
+// Everything Discord renders specially inside message content.
+// Display gives the markup, e.g. Mention::User("80351110224678912".into()).to_string() == "<@80351110224678912>"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mention {
+    User(String),
+    Channel(String),
+    Role(String),
+    // name may include a subcommand group and subcommand, e.g. "config set"
+    SlashCommand {
+        name: String,
+        id: String,
+    },
+    CustomEmoji {
+        name: String,
+        id: String,
+        animated: bool,
+    },
+    Timestamp {
+        unix: i64,
+        style: Option<TimestampStyle>,
+    },
+    Everyone,
+    Here,
+}
+
+impl std::fmt::Display for Mention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mention::User(id) => write!(f, "<@{}>", id),
+            Mention::Channel(id) => write!(f, "<#{}>", id),
+            Mention::Role(id) => write!(f, "<@&{}>", id),
+            Mention::SlashCommand { name, id } => write!(f, "</{}:{}>", name, id),
+            Mention::CustomEmoji { name, id, animated } => match animated {
+                true => write!(f, "<a:{}:{}>", name, id),
+                false => write!(f, "<:{}:{}>", name, id),
+            },
+            Mention::Timestamp { unix, style } => match style {
+                Some(style) => write!(f, "<t:{}:{}>", unix, style.as_char()),
+                None => write!(f, "<t:{}>", unix),
+            },
+            Mention::Everyone => write!(f, "@everyone"),
+            Mention::Here => write!(f, "@here"),
+        }
+    }
+}
+
+impl Mention {
+    // Parses a single token such as "<@!123>" or "<t:1618953630:R>"
+    pub fn parse(token: &str) -> Option<Self> {
+        match token {
+            "@everyone" => return Some(Mention::Everyone),
+            "@here" => return Some(Mention::Here),
+            _ => {}
+        }
+
+        let inner = token.strip_prefix('<')?.strip_suffix('>')?;
+        if let Some(id) = inner.strip_prefix("@&") {
+            return snowflake(id).map(Mention::Role);
+        }
+        if let Some(id) = inner.strip_prefix('@') {
+            // <@!id> is the legacy nickname mention
+            return snowflake(id.strip_prefix('!').unwrap_or(id)).map(Mention::User);
+        }
+        if let Some(id) = inner.strip_prefix('#') {
+            return snowflake(id).map(Mention::Channel);
+        }
+        if let Some(command) = inner.strip_prefix('/') {
+            let (name, id) = command.rsplit_once(':')?;
+            if name.is_empty() {
+                return None;
+            }
+            return snowflake(id).map(|id| Mention::SlashCommand {
+                name: name.to_string(),
+                id,
+            });
+        }
+        if let Some(timestamp) = inner.strip_prefix("t:") {
+            let (unix, style) = match timestamp.split_once(':') {
+                Some((unix, style)) => {
+                    let mut chars = style.chars();
+                    let style = match (chars.next(), chars.next()) {
+                        (Some(c), None) => TimestampStyle::from_char(c)?,
+                        _ => return None,
+                    };
+                    (unix, Some(style))
+                }
+                None => (timestamp, None),
+            };
+            return Some(Mention::Timestamp {
+                unix: unix.parse().ok()?,
+                style,
+            });
+        }
+
+        let (animated, emoji) = match inner.strip_prefix("a:") {
+            Some(emoji) => (true, emoji),
+            None => (false, inner.strip_prefix(':')?),
+        };
+        let (name, id) = emoji.split_once(':')?;
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        snowflake(id).map(|id| Mention::CustomEmoji {
+            name: name.to_string(),
+            id,
+            animated,
+        })
+    }
+}
+
+pub fn user_mention(user_id: &str) -> String {
+    Mention::User(user_id.to_string()).to_string()
+}
+
+pub fn channel_mention(channel_id: &str) -> String {
+    Mention::Channel(channel_id.to_string()).to_string()
+}
+
+pub fn role_mention(role_id: &str) -> String {
+    Mention::Role(role_id.to_string()).to_string()
+}
+
+pub fn slash_command_mention(name: &str, command_id: &str) -> String {
+    Mention::SlashCommand {
+        name: name.to_string(),
+        id: command_id.to_string(),
+    }
+    .to_string()
+}
+
+pub fn custom_emoji(name: &str, emoji_id: &str, animated: bool) -> String {
+    Mention::CustomEmoji {
+        name: name.to_string(),
+        id: emoji_id.to_string(),
+        animated,
+    }
+    .to_string()
+}
+
+pub fn timestamp_mention(unix: i64, style: Option<TimestampStyle>) -> String {
+    Mention::Timestamp { unix, style }.to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentToken<'a> {
+    Text(&'a str),
+    Mention(Mention),
+}
+
+// Splits message content into plain text and mentions, in order
+pub fn tokenize(content: &str) -> Vec<ContentToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < content.len() {
+        let rest = &content[i..];
+        let token_len = if rest.starts_with('<') {
+            rest.find('>')
+                .map(|end| &rest[..=end])
+                .filter(|token| Mention::parse(token).is_some())
+                .map(|token| token.len())
+        } else if rest.starts_with("@everyone") {
+            Some("@everyone".len())
+        } else if rest.starts_with("@here") {
+            Some("@here".len())
+        } else {
+            None
+        };
+
+        match token_len {
+            Some(len) => {
+                if text_start < i {
+                    tokens.push(ContentToken::Text(&content[text_start..i]));
+                }
+                // Only matched tokens get here, so this always parses
+                if let Some(mention) = Mention::parse(&content[i..i + len]) {
+                    tokens.push(ContentToken::Mention(mention));
+                }
+                i += len;
+                text_start = i;
+            }
+            None => i += rest.chars().next().map_or(1, |c| c.len_utf8()),
+        }
+    }
+
+    if text_start < content.len() {
+        tokens.push(ContentToken::Text(&content[text_start..]));
+    }
+    tokens
+}
+
+// Every mention in the content, in order
+pub fn parse_mentions(content: &str) -> Vec<Mention> {
+    tokenize(content)
+        .into_iter()
+        .filter_map(|token| match token {
+            ContentToken::Mention(mention) => Some(mention),
+            ContentToken::Text(_) => None,
+        })
+        .collect()
+}
+
+// Backslash-escapes markdown so user-provided text renders literally
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '~' | '|' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Breaks @everyone and @here with a zero width space so they don't ping
+pub fn escape_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200b}everyone")
+        .replace("@here", "@\u{200b}here")
+}
+
+// Splits text into messages of at most max_len characters (use MESSAGE_CONTENT_MAX).
+// Prefers line breaks, and a code block cut between two messages is closed at the end
+// of the first and reopened, with its language, at the start of the next. A message
+// never ends on the line opening a code block, that line moves to the next one, and
+// lines longer than a message aren't cut inside a fence. Blank messages are left out.
+pub fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    let mut splitter = Splitter {
+        max_len,
+        chunks: Vec::new(),
+        current: String::new(),
+        prefix_len: 0,
+        fence: None,
+        opened_at: None,
+    };
+    for line in text.split_inclusive('\n') {
+        splitter.push_line(line);
+    }
+    splitter.finish()
+}
+
+const FENCE: &str = "```";
+
+struct Splitter {
+    max_len: usize,
+    chunks: Vec<String>,
+    current: String,
+    prefix_len: usize,     // Length of the reopened fence at the start of current
+    fence: Option<String>, // Fence and language of the code block current ends in
+    opened_at: Option<usize>, // Where in current the last line (or its rest) opening a code block starts
+}
+
+impl Splitter {
+    fn push_line(&mut self, line: &str) {
+        // Room for the fence that closes a code block still open after the line,
+        // or after any part of it when the line has to be cut
+        let fence_reserve = FENCE.len() + 1;
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            let tokens = fence_tokens(rest, self.fence.is_some());
+            let open_after = tokens
+                .last()
+                .map_or(self.fence.is_some(), |token| token.opening.is_some());
+            let reserve_whole = match open_after {
+                true => fence_reserve,
+                false => 0,
+            };
+            let opened = tokens.iter().any(|token| token.opening.is_some());
+            let reserve_cut = match self.fence.is_some() || opened {
+                true => fence_reserve,
+                false => 0,
+            };
+
+            let current_len = char_len(&self.current);
+            if current_len + char_len(rest) + reserve_whole <= self.max_len {
+                self.opened_at = match self.fence.is_none() && open_after {
+                    true => Some(self.current.len()),
+                    false => None,
+                };
+                self.current.push_str(rest);
+                self.fence = fence_after(self.fence.take(), rest);
+                break;
+            }
+            // A code block opening the message stays with what follows, if any of it fits
+            let available = self.max_len.saturating_sub(current_len + reserve_cut);
+            if current_len > self.prefix_len && (self.opened_at != Some(0) || available == 0) {
+                self.flush();
+                continue;
+            }
+            // A single line longer than a message, cut it, but not inside a fence
+            let available = available.max(1);
+            let mut cut = rest
+                .char_indices()
+                .nth(available)
+                .map_or(rest.len(), |(i, _)| i);
+            if let Some(token) = tokens.iter().find(|t| t.start < cut && cut < t.end) {
+                let fits = current_len + char_len(&rest[..token.end]) + reserve_cut <= self.max_len;
+                match token.start {
+                    0 if fits => cut = token.end,
+                    // Unless the fence alone is longer than a message
+                    0 if current_len == self.prefix_len => {}
+                    0 => {
+                        self.flush();
+                        continue;
+                    }
+                    start => cut = start,
+                }
+            }
+            self.current.push_str(&rest[..cut]);
+            self.fence = fence_after(self.fence.take(), &rest[..cut]);
+            self.opened_at = None;
+            rest = &rest[cut..];
+            self.flush();
+        }
+    }
+
+    // Ends the current message and starts the next one
+    fn flush(&mut self) {
+        // The code block opened on the last line starts the next message instead
+        if let Some(at) = self.opened_at.take().filter(|at| *at > 0) {
+            let opening = self.current.split_off(at);
+            let chunk = std::mem::replace(&mut self.current, opening);
+            self.prefix_len = 0;
+            self.opened_at = Some(0);
+            self.push_chunk(chunk);
+            return;
+        }
+
+        let mut chunk = std::mem::take(&mut self.current);
+        if let Some(open) = &self.fence {
+            if !chunk.ends_with('\n') {
+                chunk.push('\n');
+            }
+            chunk.push_str(FENCE);
+            // Without the language if it would take up most of the message
+            self.current = match char_len(open) + 1 + FENCE.len() < self.max_len / 2 {
+                true => format!("{}\n", open),
+                false => format!("{}\n", FENCE),
+            };
+        }
+        self.prefix_len = char_len(&self.current);
+        self.push_chunk(chunk);
+    }
+
+    // Discord rejects empty messages, so blank chunks are dropped
+    fn push_chunk(&mut self, mut chunk: String) {
+        trim_trailing_newlines(&mut chunk);
+        if !chunk.trim().is_empty() {
+            self.chunks.push(chunk);
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if char_len(&self.current) > self.prefix_len {
+            let chunk = std::mem::take(&mut self.current);
+            self.push_chunk(chunk);
+        }
+        self.chunks
+    }
+}
+
+// A fence in a line, with the language after it when it opens a code block
+struct FenceToken {
+    start: usize,
+    end: usize,
+    opening: Option<String>,
+}
+
+// The fences in text, each opening a code block or closing the one before
+fn fence_tokens(text: &str, mut open: bool) -> Vec<FenceToken> {
+    let mut tokens = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = text[from..].find(FENCE) {
+        let start = from + pos;
+        let mut end = start + FENCE.len();
+        let opening = match open {
+            true => None,
+            false => {
+                end += text[end..]
+                    .find(|c: char| c.is_whitespace() || c == '`')
+                    .unwrap_or(text.len() - end);
+                Some(text[start..end].to_string())
+            }
+        };
+        open = opening.is_some();
+        tokens.push(FenceToken {
+            start,
+            end,
+            opening,
+        });
+        from = end;
+    }
+    tokens
+}
+
+// The code block open after text, given the one open before it
+fn fence_after(fence: Option<String>, text: &str) -> Option<String> {
+    fence_tokens(text, fence.is_some())
+        .into_iter()
+        .fold(fence, |_, token| token.opening)
+}
+
+// Trailing newlines are dropped by Discord anyway
+fn trim_trailing_newlines(chunk: &mut String) {
+    while chunk.ends_with('\n') {
+        chunk.pop();
+    }
+}
+
+fn char_len(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn snowflake(id: &str) -> Option<String> {
+    match !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+        true => Some(id.to_string()),
+        false => None,
+    }
+}
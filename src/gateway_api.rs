@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::gateway_connection::*;
+use super::member_chunks::*;
 use super::types::*;
+use super::voice_gateway::*;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum GatewayReceiveEvent {
@@ -11,6 +14,16 @@ pub enum GatewayReceiveEvent {
     InvalidSession(bool),
     Heartbeat,
     HeartbeatAck,
+    // Not sent by Discord: the process sends it once every guild listed in Ready has arrived,
+    // or been deleted, or after a timeout for the ones that stayed unavailable
+    AllGuildsReady,
+    // Not sent by Discord: the process's answer to a DiscordApiRequest::RequestGuildMembers
+    GuildMembers(GuildMembers),
+    GuildMembersFailed(GuildMembersFailed),
+    // Not sent by Discord: the process's voice connections, see DiscordApiRequest::JoinVoice
+    Voice(VoiceEvent),
+    // Not sent by Discord: the process stopped reconnecting, e.g. after close code 4004
+    GatewayClosed(GatewayClosed),
     ApplicationCommandPermissionsUpdate(ApplicationCommandPermissionsUpdate),
     AutoModerationRuleCreate(AutoModerationRuleCreate),
     AutoModerationRuleUpdate(AutoModerationRuleUpdate),
@@ -29,7 +42,7 @@ pub enum GatewayReceiveEvent {
     EntitlementCreate(Entitlement),
     EntitlementUpdate(Entitlement),
     EntitlementDelete(Entitlement),
-    GuildCreate((Option<Guild>, Option<UnavailableGuild>)),
+    GuildCreate(GuildCreatePayload),
     GuildUpdate(Guild),
     GuildDelete(UnavailableGuild),
     GuildAuditLogEntryCreate(AuditLogEntry),
@@ -64,7 +77,10 @@ pub enum GatewayReceiveEvent {
     MessageReactionRemove(MessageReactionRemove),
     MessageReactionRemoveAll(MessageReactionRemoveAll),
     MessageReactionRemoveEmoji(MessageReactionRemoveEmoji),
+    MessagePollVoteAdd(MessagePollVote),
+    MessagePollVoteRemove(MessagePollVote),
     PresenceUpdate(PresenceUpdate),
+    RateLimited(RateLimited),
     StageInstanceCreate(StageInstance),
     StageInstanceUpdate(StageInstance),
     StageInstanceDelete(StageInstance),
@@ -384,18 +400,15 @@ pub fn parse_gateway_blob(
             GatewayReceiveEvent::EntitlementDelete(data)
         }
         GatewayEventType::GuildCreate => {
-            let data = match serde_json::from_value::<Guild>(payload.d.clone()) {
-                Ok(data) => (Some(data), None),
-                Err(_) => match serde_json::from_value::<UnavailableGuild>(payload.d.clone()) {
-                    Ok(data) => (None, Some(data)),
-                    Err(_) => {
-                        return Err(anyhow::anyhow!(
-                            "Failed to parse {} event with data: {}",
-                            "GuildCreate",
-                            payload.d.to_string()
-                        ))
-                    }
-                },
+            let data = match serde_json::from_value::<GuildCreatePayload>(payload.d.clone()) {
+                Ok(data) => data,
+                Err(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to parse {} event with data: {}",
+                        "GuildCreate",
+                        payload.d.to_string()
+                    ))
+                }
             };
             GatewayReceiveEvent::GuildCreate(data)
         }
@@ -842,6 +855,32 @@ pub fn parse_gateway_blob(
             };
             GatewayReceiveEvent::MessageReactionRemoveEmoji(data)
         }
+        GatewayEventType::MessagePollVoteAdd => {
+            let data = match serde_json::from_value::<MessagePollVote>(payload.d.clone()) {
+                Ok(data) => data,
+                Err(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to parse {} event with data: {}",
+                        "MessagePollVoteAdd",
+                        payload.d.to_string()
+                    ))
+                }
+            };
+            GatewayReceiveEvent::MessagePollVoteAdd(data)
+        }
+        GatewayEventType::MessagePollVoteRemove => {
+            let data = match serde_json::from_value::<MessagePollVote>(payload.d.clone()) {
+                Ok(data) => data,
+                Err(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to parse {} event with data: {}",
+                        "MessagePollVoteRemove",
+                        payload.d.to_string()
+                    ))
+                }
+            };
+            GatewayReceiveEvent::MessagePollVoteRemove(data)
+        }
         GatewayEventType::PresenceUpdate => {
             let data = match serde_json::from_value::<PresenceUpdate>(payload.d.clone()) {
                 Ok(data) => data,
@@ -855,6 +894,19 @@ pub fn parse_gateway_blob(
             };
             GatewayReceiveEvent::PresenceUpdate(data)
         }
+        GatewayEventType::RateLimited => {
+            let data = match serde_json::from_value::<RateLimited>(payload.d.clone()) {
+                Ok(data) => data,
+                Err(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to parse {} event with data: {}",
+                        "RateLimited",
+                        payload.d.to_string()
+                    ))
+                }
+            };
+            GatewayReceiveEvent::RateLimited(data)
+        }
         GatewayEventType::StageInstanceCreate => {
             let data = match serde_json::from_value::<StageInstance>(payload.d.clone()) {
                 Ok(data) => data,
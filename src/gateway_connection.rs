@@ -0,0 +1,387 @@
+// The gateway connection lifecycle, without the websocket.
+//
+// A GatewayConnection is fed what the gateway websocket receives and when it closes, and
+// answers with GatewayActions: payloads to send, the heartbeat interval, when to reconnect
+// (resuming the session or not) and the events left for the bot. It follows
+// https://discord.com/developers/docs/topics/gateway#connection-lifecycle
+//   Hello -> Identify, or Resume on a reopened websocket -> Ready / Resumed
+//   op 1 -> heartbeat now, no ACK since the last heartbeat -> reconnect and resume
+//   op 7 -> reconnect and resume, op 9 -> reconnect and resume if d is true, else identify
+//   websocket closed -> reconnect after a growing wait, or fail on a fatal close code or
+//   after MAX_GATEWAY_RECONNECTS
+// Dispatches at or below the last seq, replayed after a resume, are dropped.
+
+use flate2::{Decompress, FlushDecompress};
+use serde::{Deserialize, Serialize};
+
+use super::gateway_api::*;
+use super::types::*;
+
+// Ends every complete message on a zlib-stream websocket
+pub const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+// Unexpected closes in a row, without a Ready or Resumed between them, before giving up.
+// Without a close code, a bad token would otherwise reconnect forever.
+pub const MAX_GATEWAY_RECONNECTS: u32 = 5;
+// The wait before the first of those reconnects, doubling for each one after
+pub const RECONNECT_BACKOFF_MS: u64 = 1000;
+// What GatewayClosed says when the websocket closed without a code
+pub const CLOSED_WITHOUT_CODE: u16 = 1006;
+
+// Close codes where reconnecting won't help, e.g. 4004 for a bad token or 4014 for
+// intents the bot isn't allowed
+pub fn close_code_is_fatal(code: u16) -> bool {
+    matches!(code, 4004 | 4010 | 4011 | 4012 | 4013 | 4014)
+}
+
+// Close codes after which the session can't be resumed
+pub fn close_code_ends_session(code: u16) -> bool {
+    matches!(code, 4007 | 4009)
+}
+
+// Not sent by Discord: the gateway closed for good, wrapped in GatewayReceiveEvent::GatewayClosed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GatewayClosed {
+    pub code: u16,
+    pub reason: String,
+}
+
+// What receive(), handle(), heartbeat() and on_close() want done, in order
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum GatewayAction {
+    Send(GatewaySendEvent),
+    // Call heartbeat() every this many milliseconds
+    StartHeartbeat(u64),
+    // Replace the websocket with one opened at url, after delay_ms
+    Reconnect { url: String, delay_ms: u64 },
+    // An event for the bot, everything not about the connection itself
+    Dispatch(GatewayReceiveEvent),
+    // Stop, reconnecting won't help
+    Fail(GatewayClosed),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GatewayConnectionState {
+    // Not connected yet, or failed
+    Disconnected,
+    // The websocket is opening, waiting for Hello
+    Connecting,
+    Identifying,
+    Resuming,
+    // Ready or Resumed came
+    Connected,
+}
+
+// Inflates a zlib-stream websocket, which is one zlib context across all its messages
+#[derive(Debug)]
+pub struct ZlibStream {
+    inflater: Decompress,
+    buffer: Vec<u8>,
+}
+
+impl Default for ZlibStream {
+    fn default() -> Self {
+        Self {
+            inflater: Decompress::new(true),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl ZlibStream {
+    // Takes a binary websocket message, returns the JSON once a message ending in
+    // ZLIB_SUFFIX completes it. Large payloads can be split over several.
+    pub fn push(&mut self, bytes: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.buffer.extend_from_slice(bytes);
+        if !self.buffer.ends_with(&ZLIB_SUFFIX) {
+            return Ok(None);
+        }
+
+        let input = std::mem::take(&mut self.buffer);
+        let mut output = Vec::with_capacity(input.len() * 4);
+        let mut consumed = 0;
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity().max(1024));
+            }
+            let total_in = self.inflater.total_in();
+            self.inflater
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|e| anyhow::anyhow!("discord_api: bad zlib-stream message: {}", e))?;
+            consumed += (self.inflater.total_in() - total_in) as usize;
+            if consumed == input.len() && output.len() < output.capacity() {
+                return Ok(Some(output));
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GatewayConnection {
+    pub token: String,
+    pub intents: u128,
+    // Ask for zlib-stream transport compression in connect()'s URL
+    pub compress: bool,
+    pub large_threshold: Option<u64>,
+    pub shard: Option<[u64; 2]>,
+    pub state: GatewayConnectionState,
+    // Where new sessions connect, from GET /gateway
+    pub gateway_url: String,
+    pub session_id: Option<String>,
+    pub resume_gateway_url: Option<String>,
+    // The last dispatch's seq, sent in heartbeats and Resume
+    pub sequence: Option<u64>,
+    pub heartbeat_interval: u64,
+    // A heartbeat went out and hasn't been ACKed
+    awaiting_ack: bool,
+    // Unexpected closes since the last Ready or Resumed
+    reconnects: u32,
+    #[serde(skip)]
+    zlib: Option<ZlibStream>,
+}
+
+impl GatewayConnection {
+    pub fn new(token: &str, intents: u128, gateway_url: &str) -> Self {
+        Self {
+            token: token.to_string(),
+            intents,
+            compress: false,
+            large_threshold: None,
+            shard: None,
+            state: GatewayConnectionState::Disconnected,
+            gateway_url: gateway_url.to_string(),
+            session_id: None,
+            resume_gateway_url: None,
+            sequence: None,
+            heartbeat_interval: 0,
+            awaiting_ack: false,
+            reconnects: 0,
+            zlib: None,
+        }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.state == GatewayConnectionState::Connected
+    }
+
+    // A session that Resume can pick back up
+    pub fn resumable(&self) -> bool {
+        self.session_id.is_some() && self.sequence.is_some()
+    }
+
+    // Call when opening a websocket, returns the URL to open.
+    // With a resumable session that's resume_gateway_url, as Discord asks.
+    pub fn connect(&mut self) -> String {
+        self.state = GatewayConnectionState::Connecting;
+        self.awaiting_ack = false;
+        self.zlib = self.compress.then(ZlibStream::default);
+
+        let base = match (&self.resume_gateway_url, self.resumable()) {
+            (Some(resume_gateway_url), true) => resume_gateway_url,
+            _ => &self.gateway_url,
+        };
+        let mut url = format!("{}/{}", base.trim_end_matches('/'), GATEWAY_PARAMS);
+        if self.compress {
+            url.push_str("&compress=zlib-stream");
+        }
+        url
+    }
+
+    // Takes a websocket message as received, text or zlib-stream binary
+    pub fn receive(&mut self, bytes: &[u8]) -> anyhow::Result<Vec<GatewayAction>> {
+        match self.inflate(bytes)? {
+            Some(json) => self.receive_json(&json),
+            None => Ok(vec![]),
+        }
+    }
+
+    // The JSON payload in a websocket message, or None while a zlib-stream message is incomplete
+    pub fn inflate(&mut self, bytes: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.zlib.as_mut() {
+            Some(zlib) => zlib.push(bytes),
+            None => Ok(Some(bytes.to_vec())),
+        }
+    }
+
+    // Takes a payload already inflated
+    pub fn receive_json(&mut self, json: &[u8]) -> anyhow::Result<Vec<GatewayAction>> {
+        let (event, seq) = parse_gateway_blob(json)?;
+        Ok(self.handle(event, seq))
+    }
+
+    // Acts on a gateway event and its seq
+    pub fn handle(&mut self, event: GatewayReceiveEvent, seq: Option<u64>) -> Vec<GatewayAction> {
+        if let Some(seq) = seq {
+            if self.sequence.is_some_and(|last| seq <= last) {
+                return vec![]; // Already seen, replayed after a resume
+            }
+            self.sequence = Some(seq);
+        }
+
+        match event {
+            GatewayReceiveEvent::Hello(hello) => {
+                self.heartbeat_interval = hello.heartbeat_interval;
+                let start = GatewayAction::StartHeartbeat(hello.heartbeat_interval);
+                match (self.session_id.clone(), self.sequence) {
+                    (Some(session_id), Some(seq)) => {
+                        self.state = GatewayConnectionState::Resuming;
+                        vec![
+                            start,
+                            GatewayAction::Send(GatewaySendEvent::Resume {
+                                token: self.token.clone(),
+                                session_id,
+                                seq,
+                            }),
+                        ]
+                    }
+                    _ => {
+                        self.state = GatewayConnectionState::Identifying;
+                        self.forget_session();
+                        vec![start, GatewayAction::Send(self.identify())]
+                    }
+                }
+            }
+            GatewayReceiveEvent::Ready(ready) => {
+                self.state = GatewayConnectionState::Connected;
+                self.reconnects = 0;
+                self.session_id = Some(ready.session_id.clone());
+                self.resume_gateway_url = Some(ready.resume_gateway_url.clone());
+                vec![GatewayAction::Dispatch(GatewayReceiveEvent::Ready(ready))]
+            }
+            GatewayReceiveEvent::Resumed => {
+                self.state = GatewayConnectionState::Connected;
+                self.reconnects = 0;
+                vec![GatewayAction::Dispatch(GatewayReceiveEvent::Resumed)]
+            }
+            GatewayReceiveEvent::HeartbeatAck => {
+                self.awaiting_ack = false;
+                vec![]
+            }
+            // Discord wants a heartbeat right away
+            GatewayReceiveEvent::Heartbeat => {
+                self.awaiting_ack = true;
+                vec![GatewayAction::Send(GatewaySendEvent::Heartbeat {
+                    seq: self.sequence,
+                })]
+            }
+            GatewayReceiveEvent::Reconnect => self.reconnect(),
+            GatewayReceiveEvent::InvalidSession(resumable) => {
+                if !resumable {
+                    self.forget_session();
+                }
+                self.reconnect()
+            }
+            event => vec![GatewayAction::Dispatch(event)],
+        }
+    }
+
+    // Call every heartbeat_interval milliseconds once StartHeartbeat says so
+    pub fn heartbeat(&mut self) -> Vec<GatewayAction> {
+        match self.state {
+            GatewayConnectionState::Disconnected | GatewayConnectionState::Connecting => vec![],
+            // Nothing came back since the last one, the connection is a zombie
+            _ if self.awaiting_ack => self.reconnect(),
+            _ => {
+                self.awaiting_ack = true;
+                vec![GatewayAction::Send(GatewaySendEvent::Heartbeat {
+                    seq: self.sequence,
+                })]
+            }
+        }
+    }
+
+    // Call when the current websocket closed, with Discord's close code if there is one.
+    // A websocket replaced after Reconnect closing is no news, don't pass it on.
+    pub fn on_close(&mut self, code: Option<u16>) -> Vec<GatewayAction> {
+        match code {
+            Some(code) if close_code_is_fatal(code) => self.fail(code, close_code_reason(code)),
+            _ if self.reconnects >= MAX_GATEWAY_RECONNECTS => {
+                let code = code.unwrap_or(CLOSED_WITHOUT_CODE);
+                let reason = format!(
+                    "{}, gave up after {} reconnects",
+                    close_code_reason(code),
+                    self.reconnects
+                );
+                self.fail(code, &reason)
+            }
+            Some(code) if close_code_ends_session(code) => {
+                self.forget_session();
+                self.back_off()
+            }
+            _ => self.back_off(),
+        }
+    }
+
+    fn fail(&mut self, code: u16, reason: &str) -> Vec<GatewayAction> {
+        self.state = GatewayConnectionState::Disconnected;
+        self.reconnects = 0;
+        self.forget_session();
+        vec![GatewayAction::Fail(GatewayClosed {
+            code,
+            reason: reason.to_string(),
+        })]
+    }
+
+    // Reconnect after an unexpected close, waiting longer each time
+    fn back_off(&mut self) -> Vec<GatewayAction> {
+        let delay_ms = RECONNECT_BACKOFF_MS << self.reconnects;
+        self.reconnects += 1;
+        vec![GatewayAction::Reconnect {
+            url: self.connect(),
+            delay_ms,
+        }]
+    }
+
+    // Reconnect when Discord or a missing heartbeat ACK asks for it
+    fn reconnect(&mut self) -> Vec<GatewayAction> {
+        vec![GatewayAction::Reconnect {
+            url: self.connect(),
+            delay_ms: 0,
+        }]
+    }
+
+    fn forget_session(&mut self) {
+        self.session_id = None;
+        self.resume_gateway_url = None;
+        self.sequence = None;
+    }
+
+    fn identify(&self) -> GatewaySendEvent {
+        GatewaySendEvent::Identify {
+            token: self.token.clone(),
+            properties: GatewayIdentifyProperties {
+                os: "kinode".to_string(),
+                browser: "kinode".to_string(),
+                device: "kinode".to_string(),
+            },
+            compress: None,
+            large_threshold: self.large_threshold,
+            shard: self.shard,
+            presence: None,
+            guild_subscriptions: None,
+            intents: self.intents,
+        }
+    }
+}
+
+// https://discord.com/developers/docs/topics/opcodes-and-status-codes#gateway-gateway-close-event-codes
+pub fn close_code_reason(code: u16) -> &'static str {
+    match code {
+        4000 => "Unknown error",
+        4001 => "Unknown opcode",
+        4002 => "Decode error",
+        4003 => "Not authenticated",
+        4004 => "Authentication failed",
+        4005 => "Already authenticated",
+        4007 => "Invalid seq",
+        4008 => "Rate limited",
+        4009 => "Session timed out",
+        4010 => "Invalid shard",
+        4011 => "Sharding required",
+        4012 => "Invalid API version",
+        4013 => "Invalid intent(s)",
+        4014 => "Disallowed intent(s)",
+        _ => "Closed",
+    }
+}
@@ -0,0 +1,169 @@
+// Recordings of gateway traffic, to reproduce what a bot saw.
+//
+// A recording is JSON lines of GatewayFrames, each a payload the process received or sent, or
+// the websocket closing, with when it happened. Tokens are redacted before anything is written.
+// DiscordApiRequest::Record makes one per bot, and GatewayReplay plays one back through a
+// GatewayConnection, so the events a parent gets can be checked against real traffic.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::gateway_api::*;
+use super::gateway_connection::*;
+
+pub const REDACTED: &str = "[redacted]";
+
+// The response to DiscordApiRequest::Replay
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayResponse {
+    // Frames played, including any that didn't parse
+    pub frames: usize,
+    // Why the replay stopped early, if it did
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GatewayFrameDirection {
+    Received,
+    Sent,
+    // The websocket closed, without a payload
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GatewayFrame {
+    pub timestamp: u64, // Unix milliseconds
+    pub direction: GatewayFrameDirection,
+    pub payload: String,
+}
+
+impl GatewayFrame {
+    // Now, with the bot's token and every "token" field redacted from the payload
+    pub fn new(direction: GatewayFrameDirection, payload: &[u8], token: &str) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_millis() as u64)
+                .unwrap_or_default(),
+            direction,
+            payload: redact_tokens(payload, token),
+        }
+    }
+
+    // One line of a recording
+    pub fn to_line(&self) -> Vec<u8> {
+        let mut line = serde_json::to_vec(self).unwrap_or_default();
+        line.push(b'\n');
+        line
+    }
+}
+
+// Takes out the bot's token wherever it appears, and the value of every "token" field,
+// e.g. in Identify, Resume, VOICE_SERVER_UPDATE and INTERACTION_CREATE
+pub fn redact_tokens(payload: &[u8], token: &str) -> String {
+    let mut text = String::from_utf8_lossy(payload).to_string();
+    if !token.is_empty() {
+        text = text.replace(token, REDACTED);
+    }
+    let Ok(mut json) = serde_json::from_str::<Value>(&text) else {
+        return text;
+    };
+    match redact_token_fields(&mut json) {
+        true => json.to_string(),
+        false => text,
+    }
+}
+
+// Returns whether anything was redacted
+fn redact_token_fields(json: &mut Value) -> bool {
+    match json {
+        Value::Object(object) => {
+            let mut redacted = false;
+            for (key, value) in object.iter_mut() {
+                if key == "token" && value.is_string() && value != REDACTED {
+                    *value = Value::String(REDACTED.to_string());
+                    redacted = true;
+                } else {
+                    redacted |= redact_token_fields(value);
+                }
+            }
+            redacted
+        }
+        Value::Array(values) => values.iter_mut().fold(false, |redacted, value| {
+            redact_token_fields(value) | redacted
+        }),
+        _ => false,
+    }
+}
+
+pub fn read_recording(bytes: &[u8]) -> anyhow::Result<Vec<GatewayFrame>> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                anyhow::anyhow!(
+                    "discord_api: recording line {} is not a frame: {}",
+                    number + 1,
+                    e
+                )
+            })
+        })
+        .collect()
+}
+
+// Plays the received and closed frames of a recording through a GatewayConnection, in order.
+// Sent frames are skipped, the connection decides what it would send itself.
+pub struct GatewayReplay {
+    pub connection: GatewayConnection,
+    frames: VecDeque<GatewayFrame>,
+}
+
+impl GatewayReplay {
+    pub fn new(frames: Vec<GatewayFrame>) -> Self {
+        let mut connection = GatewayConnection::new(REDACTED, 0, "");
+        connection.connect();
+        Self {
+            connection,
+            frames: frames.into(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self::new(read_recording(bytes)?))
+    }
+
+    // The next frame played, with what the connection made of it.
+    // Frames that don't parse are errors, the replay can go on past them.
+    pub fn next_frame(&mut self) -> Option<(GatewayFrame, anyhow::Result<Vec<GatewayAction>>)> {
+        loop {
+            let frame = self.frames.pop_front()?;
+            let actions = match frame.direction {
+                GatewayFrameDirection::Sent => continue,
+                GatewayFrameDirection::Received => {
+                    self.connection.receive(frame.payload.as_bytes())
+                }
+                GatewayFrameDirection::Closed => Ok(self.connection.on_close(None)),
+            };
+            return Some((frame, actions));
+        }
+    }
+
+    // Every event the bot would have been given, stopping at the first frame that doesn't parse
+    pub fn events(mut self) -> anyhow::Result<Vec<GatewayReceiveEvent>> {
+        let mut events = Vec::new();
+        while let Some((_, actions)) = self.next_frame() {
+            for action in actions? {
+                if let GatewayAction::Dispatch(event) = action {
+                    events.push(event);
+                }
+            }
+        }
+        Ok(events)
+    }
+}
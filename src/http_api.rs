@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use http::Method;
 use serde::{Deserialize, Serialize};
 
+use super::oauth2::*;
 use super::types::*;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,6 +42,35 @@ pub enum ApplicationsCall {
     },
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AutoModerationCall {
+    // {{baseUrl}}/guilds/:guild_id/auto-moderation/rules
+    ListRules {
+        guild_id: String,
+    },
+    // {{baseUrl}}/guilds/:guild_id/auto-moderation/rules/:rule_id
+    GetRule {
+        guild_id: String,
+        rule_id: String,
+    },
+    // {{baseUrl}}/guilds/:guild_id/auto-moderation/rules
+    CreateRule {
+        guild_id: String,
+        rule: NewAutoModerationRule,
+    },
+    // {{baseUrl}}/guilds/:guild_id/auto-moderation/rules/:rule_id
+    UpdateRule {
+        guild_id: String,
+        rule_id: String,
+        rule: UpdateAutoModerationRule,
+    },
+    // {{baseUrl}}/guilds/:guild_id/auto-moderation/rules/:rule_id
+    DeleteRule {
+        guild_id: String,
+        rule_id: String,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateDm {
     recipient_id: String,
@@ -59,7 +89,7 @@ pub enum ChannelsCall {
         allow: Option<u32>,
         deny: Option<u32>,
         #[serde(rename = "type")]
-        overwrite_type: u32,
+        overwrite_type: OverwriteType,
     },
     // {{baseUrl}}/channels/:channel_id/permissions/:overwrite_id
     DeletePermission {
@@ -126,6 +156,14 @@ pub enum ChannelsCall {
         channel_id: String,
         name: Option<String>,
         icon: Option<String>,
+        flags: Option<u64>,
+        // Forum and media channels
+        available_tags: Option<Vec<ForumTag>>,
+        default_reaction_emoji: Option<DefaultReaction>,
+        default_sort_order: Option<SortOrderType>,
+        default_forum_layout: Option<ForumLayoutType>,
+        // Threads in a forum or media channel
+        applied_tags: Option<Vec<String>>,
     },
 }
 
@@ -166,7 +204,7 @@ pub enum CommandsCall {
         application_id: String,
         guild_id: String,
         command_id: String,
-        command: ApplicationCommand,
+        command: NewApplicationCommand,
     },
     // {{baseUrl}}/applications/:application_id/guilds/:guild_id/commands?with_localizations=<boolean,null>
     ListGuildApplicationCommands {
@@ -178,7 +216,7 @@ pub enum CommandsCall {
     BulkSetGuildApplicationCommands {
         application_id: String,
         guild_id: String,
-        commands: Vec<ApplicationCommand>,
+        commands: Vec<NewApplicationCommand>, // Replaces every command in the scope
     },
     // {{baseUrl}}/applications/:application_id/guilds/:guild_id/commands
     CreateGuildApplicationCommand {
@@ -200,7 +238,7 @@ pub enum CommandsCall {
     UpdateApplicationCommand {
         application_id: String,
         command_id: String,
-        command: ApplicationCommand,
+        command: NewApplicationCommand,
     },
     // {{baseUrl}}/applications/:application_id/commands?with_localizations=<boolean,null>
     ListApplicationCommands {
@@ -210,7 +248,7 @@ pub enum CommandsCall {
     // {{baseUrl}}/applications/:application_id/commands
     BulkSetApplicationCommands {
         application_id: String,
-        commands: Vec<ApplicationCommand>,
+        commands: Vec<NewApplicationCommand>, // Replaces every command in the scope
     },
     // {{baseUrl}}/applications/:application_id/commands
     CreateApplicationCommand {
@@ -379,13 +417,18 @@ pub enum GuildsCall {
     GetPreview {
         guild_id: String,
     },
-    // {{baseUrl}}/guilds/:guild_id/prune
+    // {{baseUrl}}/guilds/:guild_id/prune?days=<integer,null>&include_roles=<string,null>
     PreviewPrune {
         guild_id: String,
+        days: Option<u8>, // 1-30, defaults to 7
+        include_roles: Option<Vec<String>>,
     },
     // {{baseUrl}}/guilds/:guild_id/prune
     Prune {
         guild_id: String,
+        days: Option<u8>,                  // 1-30, defaults to 7
+        compute_prune_count: Option<bool>, // Discord recommends false for large guilds
+        include_roles: Option<Vec<String>>,
     },
     // {{baseUrl}}/guilds/:guild_id/bans/:user_id
     GetUserBan {
@@ -396,6 +439,13 @@ pub enum GuildsCall {
     BanUser {
         guild_id: String,
         user_id: String,
+        delete_message_seconds: Option<u32>, // 0-604800
+    },
+    // {{baseUrl}}/guilds/:guild_id/bulk-ban
+    BulkBan {
+        guild_id: String,
+        user_ids: Vec<String>,               // Up to 200
+        delete_message_seconds: Option<u32>, // 0-604800
     },
     // {{baseUrl}}/guilds/:guild_id/bans/:user_id
     UnbanUser {
@@ -412,7 +462,7 @@ pub enum GuildsCall {
     // {{baseUrl}}/guilds/:guild_id/mfa
     SetMfaLevel {
         guild_id: String,
-        level: PermissionType,
+        level: MfaLevel,
     },
 }
 
@@ -457,7 +507,7 @@ pub enum GuildTemplatesCall {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum InteractionType {
     Pong = 1,
     ChannelMessageWithSource = 4,
@@ -466,7 +516,160 @@ pub enum InteractionType {
     UpdateMessage = 7,
     ApplicationCommandAutocompleteResult = 8,
     Modal = 9,
-    PremiumRequired = 0,
+    PremiumRequired = 10, // Deprecated, use a premium button instead
+}
+
+// The callback sent in reply to an interaction, with the data each InteractionType expects
+#[derive(Serialize, Deserialize, Debug)]
+pub enum InteractionResponse {
+    // Acknowledges a PING, only for HTTP interactions
+    Pong,
+    ChannelMessageWithSource(InteractionCallbackData),
+    // Shows a loading state, send the message later with a follow-up or by editing @original
+    DeferredChannelMessageWithSource {
+        ephemeral: bool,
+    },
+    // Component interactions only, acknowledges without a loading state
+    DeferredUpdateMessage,
+    // Component interactions only, edits the message the component is on
+    UpdateMessage(InteractionCallbackData),
+    ApplicationCommandAutocompleteResult(Vec<ApplicationCommandOptionChoice>), // Up to 25
+    Modal {
+        custom_id: String,
+        title: String,
+        components: Vec<serde_json::Value>, // 1-5 action rows with text inputs
+    },
+    PremiumRequired,
+}
+
+impl InteractionResponse {
+    pub fn interaction_type(&self) -> InteractionType {
+        match self {
+            InteractionResponse::Pong => InteractionType::Pong,
+            InteractionResponse::ChannelMessageWithSource(_) => {
+                InteractionType::ChannelMessageWithSource
+            }
+            InteractionResponse::DeferredChannelMessageWithSource { .. } => {
+                InteractionType::DeferredChannelMessageWithSource
+            }
+            InteractionResponse::DeferredUpdateMessage => InteractionType::DeferredUpdateMessage,
+            InteractionResponse::UpdateMessage(_) => InteractionType::UpdateMessage,
+            InteractionResponse::ApplicationCommandAutocompleteResult(_) => {
+                InteractionType::ApplicationCommandAutocompleteResult
+            }
+            InteractionResponse::Modal { .. } => InteractionType::Modal,
+            InteractionResponse::PremiumRequired => InteractionType::PremiumRequired,
+        }
+    }
+
+    // The callback body: {"type": ..., "data": ...}
+    pub fn to_json(&self) -> serde_json::Value {
+        let data = match self {
+            InteractionResponse::Pong
+            | InteractionResponse::DeferredUpdateMessage
+            | InteractionResponse::PremiumRequired => None,
+            InteractionResponse::ChannelMessageWithSource(data)
+            | InteractionResponse::UpdateMessage(data) => Some(serde_json::json!(data)),
+            InteractionResponse::DeferredChannelMessageWithSource { ephemeral } => {
+                match ephemeral {
+                    true => Some(serde_json::json!({ "flags": MESSAGE_FLAG_EPHEMERAL })),
+                    false => None,
+                }
+            }
+            InteractionResponse::ApplicationCommandAutocompleteResult(choices) => {
+                Some(serde_json::json!({ "choices": choices }))
+            }
+            InteractionResponse::Modal {
+                custom_id,
+                title,
+                components,
+            } => Some(serde_json::json!({
+              "custom_id": custom_id,
+              "title": title,
+              "components": components,
+            })),
+        };
+
+        let mut body = serde_json::json!({
+          "type": self.interaction_type() as u8,
+        });
+        if let Some(data) = data {
+            body["data"] = data;
+        }
+        body
+    }
+}
+
+// Shorthands for answering an interaction, these fill in its id, token and application id
+impl Interaction {
+    pub fn respond(&self, response: InteractionResponse) -> HttpApiCall {
+        HttpApiCall::Interactions(InteractionsCall::CreateInteractionResponse {
+            interaction_id: self.id.clone(),
+            interaction_token: self.token.clone(),
+            response,
+        })
+    }
+
+    pub fn reply(&self, data: InteractionCallbackData) -> HttpApiCall {
+        self.respond(InteractionResponse::ChannelMessageWithSource(data))
+    }
+
+    pub fn reply_content(&self, content: impl Into<String>) -> HttpApiCall {
+        self.reply(InteractionCallbackData::content(content))
+    }
+
+    pub fn reply_ephemeral(&self, content: impl Into<String>) -> HttpApiCall {
+        self.reply(InteractionCallbackData::content(content).ephemeral())
+    }
+
+    // Gives 15 minutes instead of 3 seconds to answer, with followup or edit_original
+    pub fn defer(&self, ephemeral: bool) -> HttpApiCall {
+        self.respond(InteractionResponse::DeferredChannelMessageWithSource { ephemeral })
+    }
+
+    pub fn defer_update(&self) -> HttpApiCall {
+        self.respond(InteractionResponse::DeferredUpdateMessage)
+    }
+
+    pub fn update(&self, data: InteractionCallbackData) -> HttpApiCall {
+        self.respond(InteractionResponse::UpdateMessage(data))
+    }
+
+    pub fn show_modal(
+        &self,
+        custom_id: impl Into<String>,
+        title: impl Into<String>,
+        components: Vec<serde_json::Value>,
+    ) -> HttpApiCall {
+        self.respond(InteractionResponse::Modal {
+            custom_id: custom_id.into(),
+            title: title.into(),
+            components,
+        })
+    }
+
+    pub fn autocomplete(&self, choices: Vec<ApplicationCommandOptionChoice>) -> HttpApiCall {
+        self.respond(InteractionResponse::ApplicationCommandAutocompleteResult(
+            choices,
+        ))
+    }
+
+    pub fn followup(&self, message: WebhookMessage) -> HttpApiCall {
+        HttpApiCall::Interactions(InteractionsCall::CreateFollowupMessage {
+            application_id: self.application_id.clone(),
+            interaction_token: self.token.clone(),
+            message,
+        })
+    }
+
+    pub fn edit_original(&self, message: WebhookMessage) -> HttpApiCall {
+        HttpApiCall::Interactions(InteractionsCall::UpdateOriginalWebhookMessage {
+            webhook_id: self.application_id.clone(),
+            webhook_token: self.token.clone(),
+            thread_id: None,
+            message,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -494,9 +697,13 @@ pub enum InteractionsCall {
     CreateInteractionResponse {
         interaction_id: String,
         interaction_token: String,
-        #[serde(rename = "type")]
-        interaction_type: u8, // See InteractionType, you probably want 4 for simple cases
-        data: Option<InteractionCallbackData>,
+        response: InteractionResponse,
+    },
+    // {{baseUrl}}/webhooks/:application_id/:interaction_token
+    CreateFollowupMessage {
+        application_id: String,
+        interaction_token: String,
+        message: WebhookMessage,
     },
     // {{baseUrl}}/webhooks/:webhook_id/:webhook_token/messages/:message_id?thread_id=<string,null>
     GetWebhookMessage {
@@ -547,6 +754,13 @@ pub enum InvitesCall {
     },
 }
 
+// Keeps Some(None) apart from None through JSON: a null field is Some(None), a missing one None
+fn double_option<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum MembersCall {
     GetMe {
@@ -587,12 +801,30 @@ pub enum MembersCall {
     Update {
         guild_id: String,
         user_id: String,
-        nick: Option<String>,
+        // Some(None) sends null, which resets the nickname
+        #[serde(
+            default,
+            deserialize_with = "double_option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        nick: Option<Option<String>>,
         roles: Option<Vec<String>>,
         mute: Option<bool>,
         deaf: Option<bool>,
-        channel_id: Option<String>,
-        communication_disabled_until: Option<String>,
+        // Some(None) sends null, which disconnects the member from voice
+        #[serde(
+            default,
+            deserialize_with = "double_option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        channel_id: Option<Option<String>>,
+        // Some(None) sends null, which removes an active timeout
+        #[serde(
+            default,
+            deserialize_with = "double_option",
+            skip_serializing_if = "Option::is_none"
+        )]
+        communication_disabled_until: Option<Option<String>>,
         flags: Option<u64>,
     },
     Delete {
@@ -601,6 +833,138 @@ pub enum MembersCall {
     },
 }
 
+impl GuildsCall {
+    // Bans a user, deleting up to 7 days of their messages
+    pub fn ban(
+        guild_id: String,
+        user_id: String,
+        delete_message_seconds: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        check_delete_message_seconds(delete_message_seconds)?;
+        Ok(GuildsCall::BanUser {
+            guild_id,
+            user_id,
+            delete_message_seconds,
+        })
+    }
+
+    // Bans up to 200 users at once, deleting up to 7 days of their messages
+    pub fn bulk_ban(
+        guild_id: String,
+        user_ids: Vec<String>,
+        delete_message_seconds: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        if user_ids.is_empty() || user_ids.len() > MAX_BULK_BAN_USERS {
+            return Err(anyhow::anyhow!(
+                "bulk ban takes between 1 and {} users, got {}",
+                MAX_BULK_BAN_USERS,
+                user_ids.len()
+            ));
+        }
+        check_delete_message_seconds(delete_message_seconds)?;
+        Ok(GuildsCall::BulkBan {
+            guild_id,
+            user_ids,
+            delete_message_seconds,
+        })
+    }
+}
+
+fn check_delete_message_seconds(seconds: Option<u32>) -> anyhow::Result<()> {
+    match seconds {
+        Some(seconds) if seconds > MAX_DELETE_MESSAGE_SECONDS => Err(anyhow::anyhow!(
+            "delete_message_seconds must be at most {} (7 days), got {}",
+            MAX_DELETE_MESSAGE_SECONDS,
+            seconds
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl ThreadsCall {
+    // The JSON body of a CreateForumPost, or its payload_json part when files are attached
+    fn forum_post_payload(&self) -> Option<serde_json::Value> {
+        let ThreadsCall::CreateForumPost {
+            name,
+            auto_archive_duration,
+            rate_limit_per_user,
+            applied_tags,
+            message,
+            files,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let mut message = serde_json::json!(message);
+        let files = files.as_deref().unwrap_or_default();
+        if !files.is_empty() {
+            message["attachments"] = gen_attachments_json(files);
+        }
+        let mut payload = serde_json::json!({
+          "name": name,
+          "message": message,
+        });
+        // Only send the optional fields that are set
+        for (key, value) in [
+            (
+                "auto_archive_duration",
+                serde_json::json!(auto_archive_duration),
+            ),
+            (
+                "rate_limit_per_user",
+                serde_json::json!(rate_limit_per_user),
+            ),
+            ("applied_tags", serde_json::json!(applied_tags)),
+        ] {
+            if !value.is_null() {
+                payload[key] = value;
+            }
+        }
+        Some(payload)
+    }
+}
+
+impl MembersCall {
+    // Times a member out for the given duration, up to Discord's 28-day maximum
+    pub fn timeout(
+        guild_id: String,
+        user_id: String,
+        duration: std::time::Duration,
+    ) -> anyhow::Result<Self> {
+        if duration.is_zero() || duration.as_secs() > MAX_TIMEOUT_SECONDS {
+            return Err(anyhow::anyhow!(
+                "timeout must be between 1 second and 28 days, got {}s",
+                duration.as_secs()
+            ));
+        }
+        let until = std::time::SystemTime::now() + duration;
+        Ok(Self::member_timeout_update(
+            guild_id,
+            user_id,
+            Some(format_timestamp(until)),
+        ))
+    }
+
+    pub fn remove_timeout(guild_id: String, user_id: String) -> Self {
+        Self::member_timeout_update(guild_id, user_id, None)
+    }
+
+    fn member_timeout_update(guild_id: String, user_id: String, until: Option<String>) -> Self {
+        MembersCall::Update {
+            guild_id,
+            user_id,
+            nick: None,
+            roles: None,
+            mute: None,
+            deaf: None,
+            channel_id: None,
+            communication_disabled_until: Some(until),
+            flags: None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum MessagesCall {
     GetOne {
@@ -617,6 +981,7 @@ pub enum MessagesCall {
     Create {
         channel_id: String,
         content: String,
+        poll: Option<PollCreateRequest>,
     },
     Update {
         channel_id: String,
@@ -659,12 +1024,115 @@ pub enum MessagesCall {
     },
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub enum MonetizationCall {
+    // {{baseUrl}}/applications/:application_id/skus
+    ListSkus {
+        application_id: String,
+    },
+    // {{baseUrl}}/applications/:application_id/entitlements?user_id=<string,null>&sku_ids=<string,null>&before=<string,null>&after=<string,null>&limit=<integer,null>&guild_id=<string,null>&exclude_ended=<boolean,null>
+    ListEntitlements {
+        application_id: String,
+        user_id: Option<String>,
+        sku_ids: Option<Vec<String>>,
+        before: Option<String>,
+        after: Option<String>,
+        limit: Option<i64>,
+        guild_id: Option<String>,
+        exclude_ended: Option<bool>,
+    },
+    // {{baseUrl}}/applications/:application_id/entitlements/:entitlement_id
+    GetEntitlement {
+        application_id: String,
+        entitlement_id: String,
+    },
+    // {{baseUrl}}/applications/:application_id/entitlements/:entitlement_id/consume
+    ConsumeEntitlement {
+        application_id: String,
+        entitlement_id: String,
+    },
+    // {{baseUrl}}/applications/:application_id/entitlements
+    CreateTestEntitlement {
+        application_id: String,
+        sku_id: String,
+        owner_id: String,
+        owner_type: EntitlementOwnerType,
+    },
+    // {{baseUrl}}/applications/:application_id/entitlements/:entitlement_id
+    DeleteTestEntitlement {
+        application_id: String,
+        entitlement_id: String,
+    },
+    // {{baseUrl}}/skus/:sku_id/subscriptions?before=<string,null>&after=<string,null>&limit=<integer,null>&user_id=<string,null>
+    ListSkuSubscriptions {
+        sku_id: String,
+        before: Option<String>,
+        after: Option<String>,
+        limit: Option<i64>,
+        user_id: Option<String>,
+    },
+    // {{baseUrl}}/skus/:sku_id/subscriptions/:subscription_id
+    GetSkuSubscription {
+        sku_id: String,
+        subscription_id: String,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum OAuthCall {
     // {{baseUrl}}/oauth2/applications/@me
     GetApplications,
-    // {{baseUrl}}/oauth2/@me
+    // {{baseUrl}}/oauth2/@me, with the user's Bearer token
     GetAuthorization,
+    // {{baseUrl}}/oauth2/token
+    ExchangeCode {
+        credentials: OAuth2Credentials,
+        code: String,
+        redirect_uri: String,
+    },
+    // {{baseUrl}}/oauth2/token
+    RefreshToken {
+        credentials: OAuth2Credentials,
+        refresh_token: String,
+    },
+    // {{baseUrl}}/oauth2/token, a token for the application's owner
+    ClientCredentials {
+        credentials: OAuth2Credentials,
+        scopes: Vec<OAuth2Scope>,
+    },
+    // {{baseUrl}}/oauth2/token/revoke
+    RevokeToken {
+        credentials: OAuth2Credentials,
+        token: String,
+        token_type_hint: Option<String>,
+    },
+}
+
+impl OAuthCall {
+    // The token endpoints authenticate with the credentials in the body instead of a token
+    pub fn sends_credentials(&self) -> bool {
+        !matches!(
+            self,
+            OAuthCall::GetApplications | OAuthCall::GetAuthorization
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum PollsCall {
+    // {{baseUrl}}/channels/:channel_id/polls/:message_id/answers/:answer_id?after=<string,null>&limit=<integer,null>
+    GetAnswerVoters {
+        channel_id: String,
+        message_id: String,
+        answer_id: u32,
+        after: Option<String>,
+        limit: Option<i64>,
+    },
+    // {{baseUrl}}/channels/:channel_id/polls/:message_id/expire
+    End {
+        channel_id: String,
+        message_id: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -788,7 +1256,7 @@ pub enum StagesCall {
     Update {
         channel_id: String,
         topic: String,
-        privacy_level: PermissionType,
+        privacy_level: PrivacyLevel,
     },
 }
 
@@ -900,6 +1368,17 @@ pub enum ThreadsCall {
         channel_id: String,
         channel: Channel,
     },
+    // {{baseUrl}}/channels/:channel_id/threads
+    // Starts a post in a forum or media channel, sent as multipart/form-data when files are attached
+    CreateForumPost {
+        channel_id: String,
+        name: String,
+        auto_archive_duration: Option<u32>,
+        rate_limit_per_user: Option<u32>,
+        applied_tags: Option<Vec<String>>,
+        message: ForumThreadMessage,
+        files: Option<Vec<FileUpload>>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1100,17 +1579,37 @@ pub enum WebhooksCall {
     },
 }
 
+// What goes in a request's Authorization header
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum HttpAuth {
+    Bot(String),
+    // A user's OAuth2 access token
+    Bearer(String),
+    None,
+}
+
+impl HttpAuth {
+    pub fn header(&self) -> Option<String> {
+        match self {
+            HttpAuth::Bot(token) => Some(format!("Bot {}", token)),
+            HttpAuth::Bearer(token) => Some(format!("Bearer {}", token)),
+            HttpAuth::None => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum HttpApiCall {
     AuditLog {
         guild_id: String,
         user_id: Option<String>,
-        action_type: Option<i64>,
+        action_type: Option<AuditLogEvent>,
         before: Option<String>,
         after: Option<String>,
         limit: Option<i64>,
     },
     Applications(ApplicationsCall),
+    AutoModeration(AutoModerationCall),
     Channels(ChannelsCall),
     Commands(CommandsCall),
     Emoji(EmojiCall),
@@ -1121,7 +1620,9 @@ pub enum HttpApiCall {
     Invites(InvitesCall),
     Members(MembersCall),
     Messages(MessagesCall),
+    Monetization(MonetizationCall),
     OAuth(OAuthCall),
+    Polls(PollsCall),
     Roles(RolesCall),
     RoleConnections(RoleConnectionsCall),
     ScheduledEvents(ScheduledEventsCall),
@@ -1133,6 +1634,131 @@ pub enum HttpApiCall {
     Webhooks(WebhooksCall),
 }
 
+// A multipart/form-data boundary for this request. It's derived from the parts so
+// content_type() and to_request() agree on it, and changed until no part contains it.
+fn gen_multipart_boundary(payload_json: &serde_json::Value, files: &[FileUpload]) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let payload_json = payload_json.to_string();
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    payload_json.hash(&mut hasher);
+    for file in files {
+        file.filename.hash(&mut hasher);
+        file.bytes.hash(&mut hasher);
+    }
+    let seed = hasher.finish();
+
+    let contains = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    };
+    (0u64..)
+        .map(|attempt| format!("discord_api_{:016x}", seed.wrapping_add(attempt)))
+        .find(|boundary| {
+            !contains(payload_json.as_bytes(), boundary.as_bytes())
+                && !files
+                    .iter()
+                    .any(|file| contains(&file.bytes, boundary.as_bytes()))
+        })
+        .unwrap()
+}
+
+// Builds a multipart/form-data body with a payload_json part followed by one files[n] part per file
+fn gen_multipart_body(payload_json: &serde_json::Value, files: &[FileUpload]) -> Vec<u8> {
+    let boundary = gen_multipart_boundary(payload_json, files);
+    let mut body = Vec::new();
+
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\nContent-Type: application/json\r\n\r\n{}\r\n",
+            boundary, payload_json
+        )
+        .as_bytes(),
+    );
+
+    for (i, file) in files.iter().enumerate() {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"files[{}]\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                boundary,
+                i,
+                header_safe(&file.filename),
+                header_safe(
+                    file.content_type
+                        .as_deref()
+                        .unwrap_or("application/octet-stream")
+                )
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&file.bytes);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+// Replaces the characters that would end a quoted header value or the header line itself
+fn header_safe(value: &str) -> String {
+    value.replace(['"', '\r', '\n'], "_")
+}
+
+// Attachment metadata referencing each files[n] part of a multipart body
+fn gen_attachments_json(files: &[FileUpload]) -> serde_json::Value {
+    serde_json::json!(files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| serde_json::json!({
+            "id": i,
+            "filename": file.filename,
+            "description": file.description,
+        }))
+        .collect::<Vec<_>>())
+}
+
+// Formats a time as an ISO8601 timestamp in UTC, e.g. 2024-01-31T12:00:00.000Z
+pub fn format_timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (hour, minute, second) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        since_epoch.subsec_millis()
+    )
+}
+
+// An x-www-form-urlencoded body for the OAuth2 token endpoints, credentials included
+fn gen_form_body(credentials: &OAuth2Credentials, params: Vec<(&str, &str)>) -> Vec<u8> {
+    url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("client_id", &credentials.client_id)
+        .append_pair("client_secret", &credentials.client_secret)
+        .extend_pairs(params)
+        .finish()
+        .into_bytes()
+}
+
 fn gen_query_params(params: Vec<(&str, &Option<String>)>) -> String {
     let mut query = String::new();
 
@@ -1155,6 +1781,36 @@ fn gen_query_params(params: Vec<(&str, &Option<String>)>) -> String {
 }
 
 impl HttpApiCall {
+    // Content-Type header to send with the body from to_request
+    pub fn content_type(&self) -> String {
+        match self {
+            HttpApiCall::Threads(
+                call @ ThreadsCall::CreateForumPost {
+                    files: Some(files), ..
+                },
+            ) if !files.is_empty() => {
+                let payload = call.forum_post_payload().unwrap();
+                format!(
+                    "multipart/form-data; boundary={}",
+                    gen_multipart_boundary(&payload, files)
+                )
+            }
+            HttpApiCall::OAuth(call) if call.sends_credentials() => {
+                "application/x-www-form-urlencoded".to_string()
+            }
+            _ => "application/json".to_string(),
+        }
+    }
+
+    // Like to_request, with base_url (e.g. a mock server's) in place of HTTP_URL
+    pub fn to_request_at(&self, base_url: &str) -> (url::Url, Method, Vec<u8>) {
+        let (url, method, body) = self.to_request();
+        let rebased = url
+            .as_str()
+            .replacen(HTTP_URL, base_url.trim_end_matches('/'), 1);
+        (url::Url::parse(&rebased).unwrap_or(url), method, body)
+    }
+
     // Converts to http_client request with URL, method, and body
     pub fn to_request(&self) -> (url::Url, Method, Vec<u8>) {
         match self {
@@ -1168,7 +1824,7 @@ impl HttpApiCall {
             } => {
                 let query_params = gen_query_params(vec![
                     ("user_id", user_id),
-                    ("action_type", &action_type.map(|x| x.to_string())),
+                    ("action_type", &action_type.map(|x| x.as_u32().to_string())),
                     ("before", before),
                     ("after", after),
                     ("limit", &limit.map(|x| x.to_string())),
@@ -1215,6 +1871,54 @@ impl HttpApiCall {
                     (url, Method::PATCH, body)
                 }
             },
+            HttpApiCall::AutoModeration(call) => match call {
+                AutoModerationCall::ListRules { guild_id } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/guilds/{}/auto-moderation/rules",
+                        HTTP_URL, guild_id
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                AutoModerationCall::GetRule { guild_id, rule_id } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/guilds/{}/auto-moderation/rules/{}",
+                        HTTP_URL, guild_id, rule_id
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                AutoModerationCall::CreateRule { guild_id, rule } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/guilds/{}/auto-moderation/rules",
+                        HTTP_URL, guild_id
+                    ))
+                    .unwrap();
+                    let body = serde_json::to_vec(rule).unwrap();
+                    (url, Method::POST, body)
+                }
+                AutoModerationCall::UpdateRule {
+                    guild_id,
+                    rule_id,
+                    rule,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/guilds/{}/auto-moderation/rules/{}",
+                        HTTP_URL, guild_id, rule_id
+                    ))
+                    .unwrap();
+                    let body = serde_json::to_vec(rule).unwrap();
+                    (url, Method::PATCH, body)
+                }
+                AutoModerationCall::DeleteRule { guild_id, rule_id } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/guilds/{}/auto-moderation/rules/{}",
+                        HTTP_URL, guild_id, rule_id
+                    ))
+                    .unwrap();
+                    (url, Method::DELETE, Vec::new())
+                }
+            },
             HttpApiCall::Channels(call) => match call {
                 ChannelsCall::CreateDm(create) => {
                     let url = url::Url::parse(&format!("{}/users/@me/channels", HTTP_URL)).unwrap();
@@ -1354,17 +2058,38 @@ impl HttpApiCall {
                     channel_id,
                     name,
                     icon,
+                    flags,
+                    available_tags,
+                    default_reaction_emoji,
+                    default_sort_order,
+                    default_forum_layout,
+                    applied_tags,
                 } => {
                     let url =
                         url::Url::parse(&format!("{}/channels/{}", HTTP_URL, channel_id)).unwrap();
-                    let body = serde_json::json!({
-                      "name": name,
-                      "icon": icon,
-                    })
-                    .to_string()
-                    .as_bytes()
-                    .to_vec();
-                    (url, Method::PATCH, body)
+                    // Only send the fields that are set, null would reset them
+                    let mut body = serde_json::json!({});
+                    for (key, value) in [
+                        ("name", serde_json::json!(name)),
+                        ("icon", serde_json::json!(icon)),
+                        ("flags", serde_json::json!(flags)),
+                        ("available_tags", serde_json::json!(available_tags)),
+                        (
+                            "default_reaction_emoji",
+                            serde_json::json!(default_reaction_emoji),
+                        ),
+                        ("default_sort_order", serde_json::json!(default_sort_order)),
+                        (
+                            "default_forum_layout",
+                            serde_json::json!(default_forum_layout),
+                        ),
+                        ("applied_tags", serde_json::json!(applied_tags)),
+                    ] {
+                        if !value.is_null() {
+                            body[key] = value;
+                        }
+                    }
+                    (url, Method::PATCH, body.to_string().as_bytes().to_vec())
                 }
                 ChannelsCall::Delete { channel_id } => {
                     let url =
@@ -1679,7 +2404,7 @@ impl HttpApiCall {
                             url::Url::parse(&format!("{}/guilds/{}/mfa-level", HTTP_URL, guild_id))
                                 .unwrap();
                         let body = serde_json::json!({
-                          "code": level,
+                          "level": level,
                         })
                         .to_string()
                         .as_bytes()
@@ -1712,13 +2437,42 @@ impl HttpApiCall {
                         .unwrap();
                         (url, Method::DELETE, Vec::new())
                     }
-                    GuildsCall::BanUser { guild_id, user_id } => {
+                    GuildsCall::BanUser {
+                        guild_id,
+                        user_id,
+                        delete_message_seconds,
+                    } => {
                         let url = url::Url::parse(&format!(
                             "{}/guilds/{}/bans/{}",
                             HTTP_URL, guild_id, user_id
                         ))
                         .unwrap();
-                        (url, Method::PUT, Vec::new())
+                        let body = match delete_message_seconds {
+                            Some(seconds) => serde_json::json!({
+                              "delete_message_seconds": seconds,
+                            })
+                            .to_string()
+                            .as_bytes()
+                            .to_vec(),
+                            None => Vec::new(),
+                        };
+                        (url, Method::PUT, body)
+                    }
+                    GuildsCall::BulkBan {
+                        guild_id,
+                        user_ids,
+                        delete_message_seconds,
+                    } => {
+                        let url =
+                            url::Url::parse(&format!("{}/guilds/{}/bulk-ban", HTTP_URL, guild_id))
+                                .unwrap();
+                        let mut body = serde_json::json!({
+                          "user_ids": user_ids,
+                        });
+                        if let Some(seconds) = delete_message_seconds {
+                            body["delete_message_seconds"] = serde_json::json!(seconds);
+                        }
+                        (url, Method::POST, body.to_string().as_bytes().to_vec())
                     }
                     GuildsCall::GetUserBan { guild_id, user_id } => {
                         let url = url::Url::parse(&format!(
@@ -1728,16 +2482,47 @@ impl HttpApiCall {
                         .unwrap();
                         (url, Method::GET, Vec::new())
                     }
-                    GuildsCall::Prune { guild_id } => {
+                    GuildsCall::Prune {
+                        guild_id,
+                        days,
+                        compute_prune_count,
+                        include_roles,
+                    } => {
                         let url =
                             url::Url::parse(&format!("{}/guilds/{}/prune", HTTP_URL, guild_id))
                                 .unwrap();
-                        (url, Method::POST, Vec::new())
+                        let mut body = serde_json::json!({});
+                        for (key, value) in [
+                            ("days", serde_json::json!(days)),
+                            (
+                                "compute_prune_count",
+                                serde_json::json!(compute_prune_count),
+                            ),
+                            ("include_roles", serde_json::json!(include_roles)),
+                        ] {
+                            if !value.is_null() {
+                                body[key] = value;
+                            }
+                        }
+                        (url, Method::POST, body.to_string().as_bytes().to_vec())
                     }
-                    GuildsCall::PreviewPrune { guild_id } => {
-                        let url =
-                            url::Url::parse(&format!("{}/guilds/{}/prune", HTTP_URL, guild_id))
-                                .unwrap();
+                    GuildsCall::PreviewPrune {
+                        guild_id,
+                        days,
+                        include_roles,
+                    } => {
+                        let query_params = gen_query_params(vec![
+                            ("days", &days.map(|x| x.to_string())),
+                            (
+                                "include_roles",
+                                &include_roles.as_ref().map(|roles| roles.join(",")),
+                            ),
+                        ]);
+                        let url = url::Url::parse(&format!(
+                            "{}/guilds/{}/prune{}",
+                            HTTP_URL, guild_id, query_params
+                        ))
+                        .unwrap();
                         (url, Method::GET, Vec::new())
                     }
                     GuildsCall::GetVanityUrl { guild_id } => {
@@ -2089,21 +2874,30 @@ impl HttpApiCall {
                 InteractionsCall::CreateInteractionResponse {
                     interaction_id,
                     interaction_token,
-                    interaction_type,
-                    data,
+                    response,
                 } => {
                     let url = url::Url::parse(&format!(
                         "{}/interactions/{}/{}/callback",
                         HTTP_URL, interaction_id, interaction_token
                     ))
                     .unwrap();
-                    let body = serde_json::json!({
-                      "type": interaction_type,
-                      "data": data,
-                    })
-                    .to_string()
-                    .as_bytes()
-                    .to_vec();
+                    (
+                        url,
+                        Method::POST,
+                        response.to_json().to_string().into_bytes(),
+                    )
+                }
+                InteractionsCall::CreateFollowupMessage {
+                    application_id,
+                    interaction_token,
+                    message,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/webhooks/{}/{}",
+                        HTTP_URL, application_id, interaction_token
+                    ))
+                    .unwrap();
+                    let body = serde_json::to_vec(message).unwrap();
                     (url, Method::POST, body)
                 }
                 InteractionsCall::DeleteOriginalWebhookMessage {
@@ -2236,19 +3030,28 @@ impl HttpApiCall {
                         HTTP_URL, guild_id, user_id
                     ))
                     .unwrap();
-                    let body = serde_json::json!({
-                      "nick": nick,
-                      "roles": roles,
-                      "mute": mute,
-                      "deaf": deaf,
-                      "channel_id": channel_id,
-                      "communication_disabled_until": communication_disabled_until,
-                      "flags": flags,
-                    })
-                    .to_string()
-                    .as_bytes()
-                    .to_vec();
-                    (url, Method::PATCH, body)
+                    // Only send the fields that are set, null would reset them
+                    let mut body = serde_json::json!({});
+                    for (key, value) in [
+                        ("roles", serde_json::json!(roles)),
+                        ("mute", serde_json::json!(mute)),
+                        ("deaf", serde_json::json!(deaf)),
+                        ("flags", serde_json::json!(flags)),
+                    ] {
+                        if !value.is_null() {
+                            body[key] = value;
+                        }
+                    }
+                    for (key, value) in [
+                        ("nick", nick),
+                        ("channel_id", channel_id),
+                        ("communication_disabled_until", communication_disabled_until),
+                    ] {
+                        if let Some(value) = value {
+                            body[key] = serde_json::json!(value);
+                        }
+                    }
+                    (url, Method::PATCH, body.to_string().as_bytes().to_vec())
                 }
                 MembersCall::GetOne { guild_id, user_id } => {
                     let url = url::Url::parse(&format!(
@@ -2383,17 +3186,18 @@ impl HttpApiCall {
                 MessagesCall::Create {
                     channel_id,
                     content,
+                    poll,
                 } => {
                     let url =
                         url::Url::parse(&format!("{}/channels/{}/messages", HTTP_URL, channel_id))
                             .unwrap();
-                    let body = serde_json::json!({
+                    let mut body = serde_json::json!({
                       "content": content,
-                    })
-                    .to_string()
-                    .as_bytes()
-                    .to_vec();
-                    (url, Method::POST, body)
+                    });
+                    if let Some(poll) = poll {
+                        body["poll"] = serde_json::json!(poll);
+                    }
+                    (url, Method::POST, body.to_string().as_bytes().to_vec())
                 }
                 MessagesCall::Update {
                     channel_id,
@@ -2463,6 +3267,127 @@ impl HttpApiCall {
                     (url, Method::GET, Vec::new())
                 }
             },
+            HttpApiCall::Monetization(call) => match call {
+                MonetizationCall::ListSkus { application_id } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/skus",
+                        HTTP_URL, application_id
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                MonetizationCall::ListEntitlements {
+                    application_id,
+                    user_id,
+                    sku_ids,
+                    before,
+                    after,
+                    limit,
+                    guild_id,
+                    exclude_ended,
+                } => {
+                    let query_params = gen_query_params(vec![
+                        ("user_id", user_id),
+                        ("sku_ids", &sku_ids.as_ref().map(|x| x.join(","))),
+                        ("before", before),
+                        ("after", after),
+                        ("limit", &limit.map(|x| x.to_string())),
+                        ("guild_id", guild_id),
+                        ("exclude_ended", &exclude_ended.map(|x| x.to_string())),
+                    ]);
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/entitlements{}",
+                        HTTP_URL, application_id, query_params
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                MonetizationCall::GetEntitlement {
+                    application_id,
+                    entitlement_id,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/entitlements/{}",
+                        HTTP_URL, application_id, entitlement_id
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                MonetizationCall::ConsumeEntitlement {
+                    application_id,
+                    entitlement_id,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/entitlements/{}/consume",
+                        HTTP_URL, application_id, entitlement_id
+                    ))
+                    .unwrap();
+                    (url, Method::POST, Vec::new())
+                }
+                MonetizationCall::CreateTestEntitlement {
+                    application_id,
+                    sku_id,
+                    owner_id,
+                    owner_type,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/entitlements",
+                        HTTP_URL, application_id
+                    ))
+                    .unwrap();
+                    let body = serde_json::json!({
+                      "sku_id": sku_id,
+                      "owner_id": owner_id,
+                      "owner_type": owner_type,
+                    })
+                    .to_string()
+                    .as_bytes()
+                    .to_vec();
+                    (url, Method::POST, body)
+                }
+                MonetizationCall::DeleteTestEntitlement {
+                    application_id,
+                    entitlement_id,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/applications/{}/entitlements/{}",
+                        HTTP_URL, application_id, entitlement_id
+                    ))
+                    .unwrap();
+                    (url, Method::DELETE, Vec::new())
+                }
+                MonetizationCall::ListSkuSubscriptions {
+                    sku_id,
+                    before,
+                    after,
+                    limit,
+                    user_id,
+                } => {
+                    let query_params = gen_query_params(vec![
+                        ("before", before),
+                        ("after", after),
+                        ("limit", &limit.map(|x| x.to_string())),
+                        ("user_id", user_id),
+                    ]);
+                    let url = url::Url::parse(&format!(
+                        "{}/skus/{}/subscriptions{}",
+                        HTTP_URL, sku_id, query_params
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                MonetizationCall::GetSkuSubscription {
+                    sku_id,
+                    subscription_id,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/skus/{}/subscriptions/{}",
+                        HTTP_URL, sku_id, subscription_id
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+            },
             HttpApiCall::OAuth(call) => {
                 // implement logic for OAuth
                 match call {
@@ -2475,8 +3400,96 @@ impl HttpApiCall {
                         let url = url::Url::parse(&format!("{}/oauth2/@me", HTTP_URL)).unwrap();
                         (url, Method::GET, Vec::new())
                     }
+                    OAuthCall::ExchangeCode {
+                        credentials,
+                        code,
+                        redirect_uri,
+                    } => {
+                        let url = url::Url::parse(&format!("{}/oauth2/token", HTTP_URL)).unwrap();
+                        let body = gen_form_body(
+                            credentials,
+                            vec![
+                                ("grant_type", "authorization_code"),
+                                ("code", code),
+                                ("redirect_uri", redirect_uri),
+                            ],
+                        );
+                        (url, Method::POST, body)
+                    }
+                    OAuthCall::RefreshToken {
+                        credentials,
+                        refresh_token,
+                    } => {
+                        let url = url::Url::parse(&format!("{}/oauth2/token", HTTP_URL)).unwrap();
+                        let body = gen_form_body(
+                            credentials,
+                            vec![
+                                ("grant_type", "refresh_token"),
+                                ("refresh_token", refresh_token),
+                            ],
+                        );
+                        (url, Method::POST, body)
+                    }
+                    OAuthCall::ClientCredentials {
+                        credentials,
+                        scopes,
+                    } => {
+                        let url = url::Url::parse(&format!("{}/oauth2/token", HTTP_URL)).unwrap();
+                        let body = gen_form_body(
+                            credentials,
+                            vec![
+                                ("grant_type", "client_credentials"),
+                                ("scope", &scopes_param(scopes)),
+                            ],
+                        );
+                        (url, Method::POST, body)
+                    }
+                    OAuthCall::RevokeToken {
+                        credentials,
+                        token,
+                        token_type_hint,
+                    } => {
+                        let url =
+                            url::Url::parse(&format!("{}/oauth2/token/revoke", HTTP_URL)).unwrap();
+                        let mut params = vec![("token", token.as_str())];
+                        if let Some(hint) = token_type_hint {
+                            params.push(("token_type_hint", hint));
+                        }
+                        (url, Method::POST, gen_form_body(credentials, params))
+                    }
                 }
             }
+            HttpApiCall::Polls(call) => match call {
+                PollsCall::GetAnswerVoters {
+                    channel_id,
+                    message_id,
+                    answer_id,
+                    after,
+                    limit,
+                } => {
+                    let query_params = gen_query_params(vec![
+                        ("after", after),
+                        ("limit", &limit.map(|x| x.to_string())),
+                    ]);
+                    let url = url::Url::parse(&format!(
+                        "{}/channels/{}/polls/{}/answers/{}{}",
+                        HTTP_URL, channel_id, message_id, answer_id, query_params
+                    ))
+                    .unwrap();
+                    (url, Method::GET, Vec::new())
+                }
+                PollsCall::End {
+                    channel_id,
+                    message_id,
+                } => {
+                    let url = url::Url::parse(&format!(
+                        "{}/channels/{}/polls/{}/expire",
+                        HTTP_URL, channel_id, message_id
+                    ))
+                    .unwrap();
+                    (url, Method::POST, Vec::new())
+                }
+            },
             HttpApiCall::Roles(call) => match call {
                 RolesCall::BulkUpdateGuildRoles { guild_id, roles } => {
                     let url = url::Url::parse(&format!("{}/guilds/{}/roles", HTTP_URL, guild_id))
@@ -2822,6 +3835,21 @@ impl HttpApiCall {
                     let body = serde_json::to_vec(channel).unwrap();
                     (url, Method::POST, body)
                 }
+                ThreadsCall::CreateForumPost {
+                    channel_id, files, ..
+                } => {
+                    let url =
+                        url::Url::parse(&format!("{}/channels/{}/threads", HTTP_URL, channel_id))
+                            .unwrap();
+                    let payload = call.forum_post_payload().unwrap();
+                    let files = files.as_deref().unwrap_or_default();
+                    let body = if files.is_empty() {
+                        payload.to_string().as_bytes().to_vec()
+                    } else {
+                        gen_multipart_body(&payload, files)
+                    };
+                    (url, Method::POST, body)
+                }
                 ThreadsCall::CreateFromMessage {
                     channel_id,
                     message_id,
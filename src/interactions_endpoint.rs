@@ -0,0 +1,117 @@
+// Receiving interactions over HTTP instead of the gateway.
+// https://discord.com/developers/docs/interactions/overview#setting-up-an-endpoint
+//
+// Discord POSTs each interaction to the application's Interactions Endpoint URL, signed with
+// the application's key. Requests that fail verification must get a 401, and PINGs a PONG,
+// otherwise Discord will refuse to save the URL.
+
+use std::collections::HashMap;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use super::http_api::*;
+use super::types::*;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature-Ed25519";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+pub struct InteractionVerifier {
+    public_key: VerifyingKey,
+}
+
+impl InteractionVerifier {
+    // `public_key` is the hex encoded "Public Key" from the application's General Information page
+    pub fn new(public_key: &str) -> anyhow::Result<Self> {
+        let bytes: [u8; 32] = hex::decode(public_key.trim())?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("public key must be 32 bytes"))?;
+        Ok(Self {
+            public_key: VerifyingKey::from_bytes(&bytes)?,
+        })
+    }
+
+    // Checks the signature over timestamp + body. Strict verification also rejects weak keys
+    // and non-canonical signatures, which plain verify lets through.
+    pub fn verify(&self, signature: &str, timestamp: &str, body: &[u8]) -> anyhow::Result<()> {
+        let signature: [u8; 64] = hex::decode(signature.trim())?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("signature must be 64 bytes"))?;
+        let mut message = timestamp.as_bytes().to_vec();
+        message.extend_from_slice(body);
+        self.public_key
+            .verify_strict(&message, &Signature::from_bytes(&signature))?;
+        Ok(())
+    }
+
+    // Same as verify, reading the signature headers (any case) from the request
+    pub fn verify_request(
+        &self,
+        headers: &HashMap<String, String>,
+        body: &[u8],
+    ) -> anyhow::Result<()> {
+        let signature = header(headers, SIGNATURE_HEADER)
+            .ok_or_else(|| anyhow::anyhow!("missing {} header", SIGNATURE_HEADER))?;
+        let timestamp = header(headers, TIMESTAMP_HEADER)
+            .ok_or_else(|| anyhow::anyhow!("missing {} header", TIMESTAMP_HEADER))?;
+        self.verify(signature, timestamp, body)
+    }
+
+    // Verifies and decodes a request, answers PING itself and passes every other
+    // interaction to `handler`. The result is the HTTP response to send back.
+    pub fn handle<F>(
+        &self,
+        headers: &HashMap<String, String>,
+        body: &[u8],
+        handler: F,
+    ) -> InteractionEndpointResponse
+    where
+        F: FnOnce(Interaction) -> anyhow::Result<InteractionResponse>,
+    {
+        if self.verify_request(headers, body).is_err() {
+            return InteractionEndpointResponse::error(401, "invalid request signature");
+        }
+
+        let interaction = match serde_json::from_slice::<Interaction>(body) {
+            Ok(interaction) => interaction,
+            Err(e) => return InteractionEndpointResponse::error(400, &e.to_string()),
+        };
+
+        let response = match interaction.interaction_type {
+            InteractionKind::Ping => InteractionResponse::Pong,
+            _ => match handler(interaction) {
+                Ok(response) => response,
+                Err(e) => return InteractionEndpointResponse::error(500, &e.to_string()),
+            },
+        };
+
+        InteractionEndpointResponse {
+            status: 200,
+            content_type: "application/json".to_string(),
+            body: response.to_json().to_string().into_bytes(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InteractionEndpointResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl InteractionEndpointResponse {
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain".to_string(),
+            body: message.as_bytes().to_vec(),
+        }
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
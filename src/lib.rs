@@ -4,6 +4,46 @@ mod http_api;
 pub use http_api::*;
 mod gateway_api;
 pub use gateway_api::*;
+mod gateway_connection;
+pub use gateway_connection::*;
+mod gateway_recorder;
+pub use gateway_recorder::*;
+mod audit_log;
+pub use audit_log::*;
+mod cache;
+pub use cache::*;
+mod cdn;
+pub use cdn::*;
+mod command_sync;
+pub use command_sync::*;
+mod commands;
+pub use commands::*;
+mod embed;
+pub use embed::*;
+mod formatting;
+pub use formatting::*;
+mod interactions_endpoint;
+pub use interactions_endpoint::*;
+mod member_chunks;
+pub use member_chunks::*;
+#[cfg(feature = "mock_server")]
+mod mock_gateway;
+#[cfg(feature = "mock_server")]
+pub use mock_gateway::*;
+#[cfg(feature = "mock_server")]
+mod mock_rest;
+#[cfg(feature = "mock_server")]
+pub use mock_rest::*;
+mod oauth2;
+pub use oauth2::*;
+mod pagination;
+pub use pagination::*;
+mod voice_gateway;
+pub use voice_gateway::*;
+#[cfg(feature = "voice_transport")]
+mod voice_transport;
+#[cfg(feature = "voice_transport")]
+pub use voice_transport::*;
 
 #[cfg(feature = "with_process_lib")]
 mod process;
@@ -0,0 +1,182 @@
+// Requesting guild members over the gateway (op 8) and stitching the replies together.
+//
+// Discord answers a RequestGuildMembers with one or more GUILD_MEMBERS_CHUNK events that
+// carry the request's nonce. A MemberChunks collects them until chunk_count have arrived,
+// then finish() gives the whole GuildMembers. The discord_api process does this for
+// DiscordApiRequest::RequestGuildMembers and sends the result to the parent.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::types::*;
+
+// Which members to ask for
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum MemberSelector {
+    // Every member, needs the GUILD_MEMBERS intent
+    All,
+    // Members whose username or nickname starts with the query, at most `limit` (max 100)
+    Query { query: String, limit: u64 },
+    // Up to 100 members by user id
+    UserIds(Vec<String>),
+}
+
+// Most members a Query or UserIds request can ask for
+pub const MAX_MEMBERS_PER_REQUEST: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuildMembersRequest {
+    pub guild_id: String,
+    pub members: MemberSelector,
+    // Also fetch their presences, needs the GUILD_PRESENCES intent
+    pub presences: bool,
+    // Seconds to wait for every chunk, 30 by default
+    pub timeout: Option<u64>,
+}
+
+impl GuildMembersRequest {
+    pub fn new(guild_id: &str, members: MemberSelector) -> Self {
+        Self {
+            guild_id: guild_id.to_string(),
+            members,
+            presences: false,
+            timeout: None,
+        }
+    }
+
+    // Checks the limits Discord would otherwise answer with no chunks at all
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match &self.members {
+            MemberSelector::All => {}
+            // An empty query with limit 0 asks for every member, like All
+            MemberSelector::Query { query, limit } => {
+                if !query.is_empty() && !(1..=MAX_MEMBERS_PER_REQUEST as u64).contains(limit) {
+                    return Err(anyhow::anyhow!(
+                        "limit must be between 1 and {} with a query, got {}",
+                        MAX_MEMBERS_PER_REQUEST,
+                        limit
+                    ));
+                }
+            }
+            MemberSelector::UserIds(user_ids) => {
+                if user_ids.is_empty() || user_ids.len() > MAX_MEMBERS_PER_REQUEST {
+                    return Err(anyhow::anyhow!(
+                        "between 1 and {} user ids can be requested, got {}",
+                        MAX_MEMBERS_PER_REQUEST,
+                        user_ids.len()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_send_event(&self, nonce: &str) -> GatewaySendEvent {
+        let (query, limit, user_ids) = match &self.members {
+            MemberSelector::All => (Some(String::new()), 0, None),
+            MemberSelector::Query { query, limit } => (Some(query.clone()), *limit, None),
+            MemberSelector::UserIds(user_ids) => (None, 0, Some(user_ids.clone())),
+        };
+
+        GatewaySendEvent::RequestGuildMembers {
+            guild_id: self.guild_id.clone(),
+            query,
+            limit,
+            presences: Some(self.presences),
+            user_ids,
+            nonce: nonce.to_string(),
+        }
+    }
+
+    pub fn timeout_seconds(&self) -> u64 {
+        self.timeout.unwrap_or(30)
+    }
+}
+
+// Everything the chunks of one request carried
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuildMembers {
+    pub nonce: String,
+    pub guild_id: String,
+    pub members: Vec<GuildMember>,
+    pub not_found: Vec<String>, // User ids that aren't members, for MemberSelector::UserIds
+    pub presences: Vec<PresenceUpdate>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GuildMembersFailed {
+    pub nonce: String,
+    pub guild_id: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MemberChunks {
+    pub request: GuildMembersRequest,
+    pub nonce: String,
+    chunk_count: Option<u64>,
+    received: HashSet<u64>,
+    members: Vec<GuildMember>,
+    not_found: Vec<String>,
+    presences: Vec<PresenceUpdate>,
+}
+
+impl MemberChunks {
+    pub fn new(request: GuildMembersRequest, nonce: &str) -> Self {
+        Self {
+            request,
+            nonce: nonce.to_string(),
+            chunk_count: None,
+            received: HashSet::new(),
+            members: Vec::new(),
+            not_found: Vec::new(),
+            presences: Vec::new(),
+        }
+    }
+
+    pub fn matches(&self, chunk: &GuildMembersChunk) -> bool {
+        chunk.nonce.as_deref() == Some(self.nonce.as_str())
+            && chunk.guild_id == self.request.guild_id
+    }
+
+    // Whether any chunk has arrived, a request can only be resent before that
+    pub fn started(&self) -> bool {
+        self.chunk_count.is_some()
+    }
+
+    // Adds a chunk of this request, a repeated chunk_index is ignored.
+    // Returns true once every chunk has arrived.
+    pub fn add(&mut self, chunk: GuildMembersChunk) -> bool {
+        self.chunk_count = Some(chunk.chunk_count);
+        if self.received.insert(chunk.chunk_index) {
+            self.members.extend(chunk.members);
+            self.not_found.extend(chunk.not_found.unwrap_or_default());
+            self.presences.extend(chunk.presences.unwrap_or_default());
+        }
+        self.is_complete()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.chunk_count
+            .is_some_and(|count| self.received.len() as u64 >= count)
+    }
+
+    pub fn finish(self) -> GuildMembers {
+        GuildMembers {
+            nonce: self.nonce,
+            guild_id: self.request.guild_id,
+            members: self.members,
+            not_found: self.not_found,
+            presences: self.presences,
+        }
+    }
+
+    pub fn fail(self, error: &str) -> GuildMembersFailed {
+        GuildMembersFailed {
+            nonce: self.nonce,
+            guild_id: self.request.guild_id,
+            error: error.to_string(),
+        }
+    }
+}
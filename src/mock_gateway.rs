@@ -0,0 +1,498 @@
+// A scriptable stand-in for Discord's gateway, for connection lifecycle tests.
+//
+// Every websocket MockGatewayServer accepts runs the next GatewayScript in line: send Hello,
+// wait for Identify, send Ready and some dispatches, then op 7, op 9, a close code or nothing.
+// Heartbeats are ACKed on their own unless the script says to delay or drop the ACKs, and a
+// client asking for compress=zlib-stream gets its messages through one zlib context, as
+// Discord does. Everything clients send is recorded with the connection and time it came, so
+// tests can check identify fields, resume seqs and heartbeat cadence.
+//
+// Seqs carry across connections like a session's do: Ready starts them over, Replay sends
+// dispatches a resumed client already has.
+
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use flate2::{Compress, Compression, FlushCompress};
+use serde_json::{json, Value};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::{Message, WebSocket};
+
+// How long Expect waits for the client
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone)]
+pub enum GatewayStep {
+    // op 10 with this heartbeat interval in milliseconds
+    Hello(u64),
+    // READY with a resume_gateway_url back to this server, seqs start over at 1
+    Ready { session_id: String },
+    // op 0 with the next seq
+    Dispatch { t: String, d: Value },
+    // RESUMED with the next seq
+    Resumed,
+    // Send again every dispatch after this seq, as Discord does before RESUMED
+    Replay(u64),
+    // op 1, asking for a heartbeat now
+    RequestHeartbeat,
+    // op 7
+    Reconnect,
+    // op 9, resumable or not
+    InvalidSession(bool),
+    // op 11, for scripts that don't ACK on their own
+    HeartbeatAck,
+    // Anything else, sent as is
+    Raw(Value),
+    // Wait for the client to send this op, the script fails after EXPECT_TIMEOUT
+    Expect(u8),
+    Sleep(Duration),
+    // Close the websocket with this code, ending the script
+    Close(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct GatewayScript {
+    pub steps: Vec<GatewayStep>,
+    // None leaves heartbeats unanswered, as a zombied connection would
+    pub ack_delay: Option<Duration>,
+    // Split each zlib-stream message over two websocket messages
+    pub split_frames: bool,
+}
+
+impl GatewayScript {
+    // ACKs heartbeats right away
+    pub fn new(steps: Vec<GatewayStep>) -> Self {
+        Self {
+            steps,
+            ack_delay: Some(Duration::ZERO),
+            split_frames: false,
+        }
+    }
+
+    pub fn ack_after(mut self, delay: Duration) -> Self {
+        self.ack_delay = Some(delay);
+        self
+    }
+
+    pub fn without_acks(mut self) -> Self {
+        self.ack_delay = None;
+        self
+    }
+
+    pub fn split_frames(mut self) -> Self {
+        self.split_frames = true;
+        self
+    }
+}
+
+// A payload a client sent
+#[derive(Debug, Clone)]
+pub struct ReceivedPayload {
+    // Index of the websocket, in accept order
+    pub connection: usize,
+    // Since the server started
+    pub at: Duration,
+    pub payload: Value,
+}
+
+impl ReceivedPayload {
+    pub fn op(&self) -> u64 {
+        self.payload["op"].as_u64().unwrap_or_default()
+    }
+
+    pub fn d(&self) -> &Value {
+        &self.payload["d"]
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    // Request targets clients connected to, e.g. /?v=9&encoding=json
+    connections: Vec<String>,
+    received: Vec<ReceivedPayload>,
+    errors: Vec<String>,
+    sequence: u64,
+    // Dispatches sent since Ready, with their seqs
+    dispatches: Vec<Value>,
+}
+
+pub struct MockGatewayServer {
+    address: SocketAddr,
+    session: Arc<Mutex<Session>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockGatewayServer {
+    // On a free port on localhost, running scripts in order as clients connect.
+    // Connections past the last script only get Hello and heartbeat ACKs.
+    pub fn start(scripts: Vec<GatewayScript>) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let session = Arc::new(Mutex::new(Session::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let started = Instant::now();
+
+        let thread = {
+            let session = session.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut scripts = scripts.into_iter();
+                let mut handlers = Vec::new();
+                let mut index = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let stream = match listener.accept() {
+                        Ok((stream, _)) => stream,
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            std::thread::sleep(POLL_INTERVAL);
+                            continue;
+                        }
+                        Err(_) => break,
+                    };
+                    let script = scripts
+                        .next()
+                        .unwrap_or(GatewayScript::new(vec![GatewayStep::Hello(41250)]));
+                    let mut connection = MockConnection {
+                        index,
+                        address,
+                        started,
+                        script,
+                        session: session.clone(),
+                        stop: stop.clone(),
+                        zlib: None,
+                        acks_due: Vec::new(),
+                        expected: 0,
+                    };
+                    index += 1;
+                    handlers.push(std::thread::spawn(move || {
+                        if let Err(e) = connection.run(stream) {
+                            connection.error(&e.to_string());
+                        }
+                    }));
+                }
+                for handler in handlers {
+                    let _ = handler.join();
+                }
+            })
+        };
+
+        Ok(Self {
+            address,
+            session,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    // What GET /gateway would return, e.g. ws://127.0.0.1:38123
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.address)
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // Request targets of the websockets opened so far, in order
+    pub fn connections(&self) -> Vec<String> {
+        self.session.lock().unwrap().connections.clone()
+    }
+
+    pub fn received(&self) -> Vec<ReceivedPayload> {
+        self.session.lock().unwrap().received.clone()
+    }
+
+    // What a connection received with this op, in order
+    pub fn received_op(&self, connection: usize, op: u64) -> Vec<ReceivedPayload> {
+        self.received()
+            .into_iter()
+            .filter(|received| received.connection == connection && received.op() == op)
+            .collect()
+    }
+
+    // Scripts that failed, e.g. an Expect that timed out
+    pub fn errors(&self) -> Vec<String> {
+        self.session.lock().unwrap().errors.clone()
+    }
+}
+
+impl Drop for MockGatewayServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// A non-dispatch payload, shaped like Discord's
+fn payload(op: u8, d: Value) -> Value {
+    json!({ "op": op, "d": d, "s": null, "t": null })
+}
+
+struct MockConnection {
+    index: usize,
+    address: SocketAddr,
+    started: Instant,
+    script: GatewayScript,
+    session: Arc<Mutex<Session>>,
+    stop: Arc<AtomicBool>,
+    zlib: Option<Compress>,
+    // Heartbeat ACKs waiting for ack_delay
+    acks_due: Vec<Instant>,
+    // Received payloads Expect has gone past
+    expected: usize,
+}
+
+impl MockConnection {
+    #[allow(clippy::result_large_err)]
+    fn run(&mut self, stream: TcpStream) -> anyhow::Result<()> {
+        stream.set_nonblocking(false)?;
+        let mut target = String::new();
+        let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response| {
+            target = request.uri().to_string();
+            Ok::<Response, _>(response)
+        })
+        .map_err(|e| anyhow::anyhow!("handshake failed: {}", e))?;
+        socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+        if target.contains("compress=zlib-stream") {
+            self.zlib = Some(Compress::new(Compression::default(), true));
+        }
+        self.session.lock().unwrap().connections.push(target);
+
+        let steps = std::mem::take(&mut self.script.steps);
+        for step in steps {
+            if !self.step(&mut socket, step)? {
+                return Ok(());
+            }
+        }
+        // Keep answering heartbeats until the client leaves
+        while self.pump(&mut socket)? {}
+        Ok(())
+    }
+
+    // Returns false once the websocket is closed
+    fn step(
+        &mut self,
+        socket: &mut WebSocket<TcpStream>,
+        step: GatewayStep,
+    ) -> anyhow::Result<bool> {
+        match step {
+            GatewayStep::Hello(interval) => {
+                self.send(
+                    socket,
+                    payload(10, json!({ "heartbeat_interval": interval })),
+                )?;
+            }
+            GatewayStep::Ready { session_id } => {
+                {
+                    let mut session = self.session.lock().unwrap();
+                    session.sequence = 0;
+                    session.dispatches.clear();
+                }
+                let d = json!({
+                    "v": 9,
+                    "user": { "id": "1", "username": "kinode-bot", "discriminator": "0", "bot": true },
+                    "guilds": [],
+                    "session_id": session_id,
+                    "resume_gateway_url": format!("ws://{}/resume", self.address),
+                    "shard": null,
+                    "application": { "id": "1", "flags": 0 },
+                });
+                self.dispatch(socket, "READY", d)?;
+            }
+            GatewayStep::Dispatch { t, d } => self.dispatch(socket, &t, d)?,
+            GatewayStep::Resumed => self.dispatch(socket, "RESUMED", Value::Null)?,
+            GatewayStep::Replay(after) => {
+                let dispatches = self.session.lock().unwrap().dispatches.clone();
+                for payload in dispatches {
+                    if payload["s"].as_u64().is_some_and(|seq| seq > after) {
+                        self.send(socket, payload)?;
+                    }
+                }
+            }
+            GatewayStep::RequestHeartbeat => self.send(socket, payload(1, Value::Null))?,
+            GatewayStep::Reconnect => self.send(socket, payload(7, Value::Null))?,
+            GatewayStep::InvalidSession(resumable) => {
+                self.send(socket, payload(9, json!(resumable)))?;
+            }
+            GatewayStep::HeartbeatAck => self.send(socket, payload(11, Value::Null))?,
+            GatewayStep::Raw(payload) => self.send(socket, payload)?,
+            GatewayStep::Expect(op) => {
+                let deadline = Instant::now() + EXPECT_TIMEOUT;
+                loop {
+                    if self.take_expected(op) {
+                        break;
+                    }
+                    if Instant::now() > deadline {
+                        let ops: Vec<u64> =
+                            self.received().iter().map(ReceivedPayload::op).collect();
+                        self.error(&format!("expected op {}, received {:?}", op, ops));
+                        return Ok(false);
+                    }
+                    if !self.pump(socket)? {
+                        self.error(&format!("closed while expecting op {}", op));
+                        return Ok(false);
+                    }
+                }
+            }
+            GatewayStep::Sleep(duration) => {
+                let deadline = Instant::now() + duration;
+                while Instant::now() < deadline {
+                    if !self.pump(socket)? {
+                        return Ok(false);
+                    }
+                }
+            }
+            GatewayStep::Close(code) => {
+                let _ = socket.close(Some(CloseFrame {
+                    code: CloseCode::from(code),
+                    reason: "".into(),
+                }));
+                // Let the close handshake finish
+                let deadline = Instant::now() + Duration::from_secs(1);
+                while Instant::now() < deadline && self.pump(socket).unwrap_or(false) {}
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Sends any ACKs due and reads what came in. Returns false once the websocket is closed.
+    fn pump(&mut self, socket: &mut WebSocket<TcpStream>) -> anyhow::Result<bool> {
+        if self.stop.load(Ordering::Relaxed) {
+            let _ = socket.close(None);
+            return Ok(false);
+        }
+
+        let now = Instant::now();
+        let due = self.acks_due.iter().filter(|at| **at <= now).count();
+        self.acks_due.retain(|at| *at > now);
+        for _ in 0..due {
+            self.send(socket, payload(11, Value::Null))?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => self.record(text.as_bytes()),
+            Ok(Message::Binary(bytes)) => self.record(&bytes),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn record(&mut self, bytes: &[u8]) {
+        let Ok(payload) = serde_json::from_slice::<Value>(bytes) else {
+            self.error(&format!(
+                "not a gateway payload: {}",
+                String::from_utf8_lossy(bytes)
+            ));
+            return;
+        };
+        if payload["op"] == 1 {
+            if let Some(delay) = self.script.ack_delay {
+                self.acks_due.push(Instant::now() + delay);
+            }
+        }
+        self.session.lock().unwrap().received.push(ReceivedPayload {
+            connection: self.index,
+            at: self.started.elapsed(),
+            payload,
+        });
+    }
+
+    // Whether the client sent op since the last Expect, moving past it if so
+    fn take_expected(&mut self, op: u8) -> bool {
+        let received = self.received();
+        match received[self.expected..]
+            .iter()
+            .position(|received| received.op() == op as u64)
+        {
+            Some(position) => {
+                self.expected += position + 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn received(&self) -> Vec<ReceivedPayload> {
+        self.session
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|received| received.connection == self.index)
+            .cloned()
+            .collect()
+    }
+
+    fn dispatch(
+        &mut self,
+        socket: &mut WebSocket<TcpStream>,
+        t: &str,
+        d: Value,
+    ) -> anyhow::Result<()> {
+        let payload = {
+            let mut session = self.session.lock().unwrap();
+            session.sequence += 1;
+            let payload = json!({ "op": 0, "t": t, "s": session.sequence, "d": d });
+            session.dispatches.push(payload.clone());
+            payload
+        };
+        self.send(socket, payload)
+    }
+
+    fn send(&mut self, socket: &mut WebSocket<TcpStream>, payload: Value) -> anyhow::Result<()> {
+        let text = payload.to_string();
+        let Some(zlib) = self.zlib.as_mut() else {
+            socket.send(Message::Text(text))?;
+            return Ok(());
+        };
+
+        let mut compressed = Vec::with_capacity(text.len() + 64);
+        let mut consumed = 0;
+        loop {
+            if compressed.len() == compressed.capacity() {
+                compressed.reserve(1024);
+            }
+            let total_in = zlib.total_in();
+            zlib.compress_vec(
+                &text.as_bytes()[consumed..],
+                &mut compressed,
+                FlushCompress::Sync,
+            )?;
+            consumed += (zlib.total_in() - total_in) as usize;
+            if consumed == text.len() && compressed.len() < compressed.capacity() {
+                break;
+            }
+        }
+        if self.script.split_frames && compressed.len() > 1 {
+            let rest = compressed.split_off(compressed.len() / 2);
+            socket.send(Message::Binary(compressed))?;
+            socket.send(Message::Binary(rest))?;
+        } else {
+            socket.send(Message::Binary(compressed))?;
+        }
+        Ok(())
+    }
+
+    fn error(&self, error: &str) {
+        self.session
+            .lock()
+            .unwrap()
+            .errors
+            .push(format!("connection {}: {}", self.index, error));
+    }
+}
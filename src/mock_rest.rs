@@ -0,0 +1,1412 @@
+// A local stand-in for Discord's REST API, for testing bots without the network.
+//
+// MockDiscord is an in-memory model of users, guilds, channels, messages, members and roles
+// that answers the routes HttpApiCall::to_request produces with the JSON Discord would send,
+// error bodies included. MockRestServer serves it over HTTP at base_url(), so a bot can be
+// pointed at it with HttpApiCall::to_request_at or DiscordApiRequest::SetHttpUrl.
+//
+// The OAuth2 token endpoints take the bot's id as client_id and MockDiscord::client_secret,
+// and hand out Bearer tokens for codes made with add_authorization_code().
+//
+// Every request is recorded for assertions. Responses carry rate limit headers, 429s can be
+// queued with rate_limit(), and respond() stubs routes the model doesn't cover, which
+// otherwise get Discord's 404.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use super::formatting::*;
+use super::http_api::format_timestamp;
+use super::oauth2::*;
+use super::types::HTTP_URL;
+
+const DISCORD_EPOCH_MS: u64 = 1420070400000;
+// Every bucket allows this many requests per window, as reported in the headers
+const BUCKET_LIMIT: u64 = 5;
+const BUCKET_WINDOW: Duration = Duration::from_secs(5);
+const EVERYONE_PERMISSIONS: &str = "2248473465835073";
+
+// A request as the server received it
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    // Below the API base, e.g. /channels/123/messages, without the query
+    pub path: String,
+    pub query: HashMap<String, String>,
+    // Lowercase header names
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+    // What the mock answered with
+    pub status: u16,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    // The body as JSON, Null when there is none
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: &Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    pub fn no_content() -> Self {
+        Self {
+            status: 204,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    // A JSON error body like Discord's, see https://discord.com/developers/docs/topics/opcodes-and-status-codes#json
+    pub fn error(status: u16, code: u64, message: &str) -> Self {
+        Self::json(status, &json!({ "message": message, "code": code }))
+    }
+
+    // 400 Invalid Form Body with a single field error
+    pub fn form_error(field: &str, code: &str, message: &str) -> Self {
+        Self::json(
+            400,
+            &json!({
+                "code": 50035,
+                "errors": { field: { "_errors": [{ "code": code, "message": message }] } },
+                "message": "Invalid Form Body",
+            }),
+        )
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body_json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+// Makes the next matching request fail with a 429
+#[derive(Debug, Clone)]
+pub struct MockRateLimit {
+    // Only requests whose path starts with this, any request when None
+    pub path: Option<String>,
+    pub retry_after: f64,
+    pub global: bool,
+}
+
+impl MockRateLimit {
+    pub fn new(retry_after: f64) -> Self {
+        Self {
+            path: None,
+            retry_after,
+            global: false,
+        }
+    }
+}
+
+type Handled = Result<MockResponse, MockResponse>;
+
+// What an OAuth2 code or access token was granted for
+#[derive(Debug, Clone)]
+struct MockGrant {
+    user_id: String,
+    scope: String,
+}
+
+pub struct MockDiscord {
+    // What GET /gateway and /gateway/bot point at
+    pub gateway_url: String,
+    bot_user_id: String,
+    last_id: u64,
+    users: HashMap<String, Value>,
+    guilds: BTreeMap<String, Value>,
+    channels: BTreeMap<String, Value>,
+    // channel_id -> message_id -> message
+    messages: HashMap<String, BTreeMap<u64, Value>>,
+    // guild_id -> user_id -> member
+    members: HashMap<String, BTreeMap<u64, Value>>,
+    rate_limits: VecDeque<MockRateLimit>,
+    // (method, path) -> response, checked before the model
+    stubs: HashMap<(String, String), MockResponse>,
+    // bucket -> requests made and when the window resets
+    buckets: HashMap<String, (u64, Instant)>,
+    requests: Vec<RecordedRequest>,
+    // The application's secret for the OAuth2 token endpoints
+    pub client_secret: String,
+    oauth2_codes: HashMap<String, MockGrant>,
+    access_tokens: HashMap<String, MockGrant>,
+    // refresh token -> the access token it was issued with
+    refresh_tokens: HashMap<String, String>,
+}
+
+impl Default for MockDiscord {
+    fn default() -> Self {
+        Self::new("kinode-bot")
+    }
+}
+
+impl MockDiscord {
+    // Starts out with just the bot's own user
+    pub fn new(bot_username: &str) -> Self {
+        let mut discord = Self {
+            gateway_url: "wss://gateway.discord.gg".to_string(),
+            bot_user_id: String::new(),
+            last_id: 0,
+            users: HashMap::new(),
+            guilds: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            messages: HashMap::new(),
+            members: HashMap::new(),
+            rate_limits: VecDeque::new(),
+            stubs: HashMap::new(),
+            buckets: HashMap::new(),
+            requests: Vec::new(),
+            client_secret: "mock_client_secret".to_string(),
+            oauth2_codes: HashMap::new(),
+            access_tokens: HashMap::new(),
+            refresh_tokens: HashMap::new(),
+        };
+        discord.bot_user_id = discord.add_user(bot_username);
+        let bot = discord.users.get_mut(&discord.bot_user_id).unwrap();
+        bot["bot"] = json!(true);
+        bot["discriminator"] = json!("1337");
+        bot["global_name"] = Value::Null;
+        discord
+    }
+
+    pub fn bot_user_id(&self) -> &str {
+        &self.bot_user_id
+    }
+
+    pub fn add_user(&mut self, username: &str) -> String {
+        let id = self.next_id();
+        self.users.insert(
+            id.clone(),
+            json!({
+                "id": id,
+                "username": username,
+                "discriminator": "0",
+                "global_name": username,
+                "avatar": null,
+                "public_flags": 0,
+                "banner": null,
+                "accent_color": null,
+                "avatar_decoration_data": null,
+            }),
+        );
+        id
+    }
+
+    // A guild owned by the bot, with an @everyone role and the bot as a member
+    pub fn add_guild(&mut self, name: &str) -> String {
+        let id = self.next_id();
+        self.guilds.insert(
+            id.clone(),
+            json!({
+                "id": id,
+                "name": name,
+                "icon": null,
+                "description": null,
+                "splash": null,
+                "discovery_splash": null,
+                "features": [],
+                "banner": null,
+                "owner_id": self.bot_user_id,
+                "application_id": null,
+                "afk_channel_id": null,
+                "afk_timeout": 300,
+                "system_channel_id": null,
+                "system_channel_flags": 0,
+                "widget_enabled": false,
+                "widget_channel_id": null,
+                "verification_level": 0,
+                "roles": [role_json(&id, "@everyone", 0, EVERYONE_PERMISSIONS)],
+                "emojis": [],
+                "stickers": [],
+                "default_message_notifications": 0,
+                "mfa_level": 0,
+                "explicit_content_filter": 0,
+                "max_presences": null,
+                "max_members": 500000,
+                "max_video_channel_users": 25,
+                "max_stage_video_channel_users": 50,
+                "vanity_url_code": null,
+                "premium_tier": 0,
+                "premium_subscription_count": 0,
+                "preferred_locale": "en-US",
+                "rules_channel_id": null,
+                "safety_alerts_channel_id": null,
+                "public_updates_channel_id": null,
+                "nsfw_level": 0,
+                "premium_progress_bar_enabled": false,
+            }),
+        );
+        let bot_user_id = self.bot_user_id.clone();
+        self.add_member(&id, &bot_user_id);
+        id
+    }
+
+    // channel_type is Discord's number, 0 for text, 2 for voice, 4 for a category
+    pub fn add_channel(&mut self, guild_id: &str, name: &str, channel_type: u64) -> String {
+        let id = self.next_id();
+        let position = self
+            .channels
+            .values()
+            .filter(|channel| channel["guild_id"] == guild_id)
+            .count();
+        let mut channel = json!({
+            "id": id,
+            "type": channel_type,
+            "guild_id": guild_id,
+            "position": position,
+            "permission_overwrites": [],
+            "name": name,
+            "nsfw": false,
+            "parent_id": null,
+            "flags": 0,
+        });
+        match channel_type {
+            2 | 13 => {
+                channel["bitrate"] = json!(64000);
+                channel["user_limit"] = json!(0);
+                channel["rtc_region"] = Value::Null;
+            }
+            4 => {}
+            _ => {
+                channel["topic"] = Value::Null;
+                channel["last_message_id"] = Value::Null;
+                channel["rate_limit_per_user"] = json!(0);
+            }
+        }
+        self.channels.insert(id.clone(), channel);
+        id
+    }
+
+    // The user must have been added first
+    pub fn add_member(&mut self, guild_id: &str, user_id: &str) {
+        let member = json!({
+            "user": self.users.get(user_id).cloned().unwrap_or(Value::Null),
+            "nick": null,
+            "avatar": null,
+            "banner": null,
+            "roles": [],
+            "joined_at": now_timestamp(),
+            "premium_since": null,
+            "deaf": false,
+            "mute": false,
+            "flags": 0,
+            "pending": false,
+            "communication_disabled_until": null,
+        });
+        self.members
+            .entry(guild_id.to_string())
+            .or_default()
+            .insert(snowflake(user_id), member);
+    }
+
+    pub fn add_role(&mut self, guild_id: &str, name: &str) -> String {
+        let id = self.next_id();
+        if let Some(roles) = self
+            .guilds
+            .get_mut(guild_id)
+            .map(|guild| &mut guild["roles"])
+        {
+            let position = roles.as_array().map_or(0, Vec::len);
+            roles
+                .as_array_mut()
+                .unwrap()
+                .push(role_json(&id, name, position, "0"));
+        }
+        id
+    }
+
+    // A message someone else sent, for the bot to read
+    pub fn add_message(&mut self, channel_id: &str, author_id: &str, content: &str) -> String {
+        let message = self.message_json(channel_id, author_id, content);
+        let id = message["id"].as_str().unwrap().to_string();
+        self.store_message(channel_id, message);
+        id
+    }
+
+    pub fn user(&self, user_id: &str) -> Option<&Value> {
+        self.users.get(user_id)
+    }
+
+    pub fn guild(&self, guild_id: &str) -> Option<&Value> {
+        self.guilds.get(guild_id)
+    }
+
+    pub fn channel(&self, channel_id: &str) -> Option<&Value> {
+        self.channels.get(channel_id)
+    }
+
+    pub fn member(&self, guild_id: &str, user_id: &str) -> Option<&Value> {
+        self.members.get(guild_id)?.get(&snowflake(user_id))
+    }
+
+    // Oldest first
+    pub fn messages(&self, channel_id: &str) -> Vec<&Value> {
+        self.messages
+            .get(channel_id)
+            .map(|messages| messages.values().collect())
+            .unwrap_or_default()
+    }
+
+    // A code as if user_id had approved the scopes, for OAuthCall::ExchangeCode
+    pub fn add_authorization_code(&mut self, user_id: &str, scopes: &[OAuth2Scope]) -> String {
+        let code = format!("mock_code_{}", self.next_id());
+        self.oauth2_codes.insert(
+            code.clone(),
+            MockGrant {
+                user_id: user_id.to_string(),
+                scope: scopes_param(scopes),
+            },
+        );
+        code
+    }
+
+    pub fn requests(&self) -> &[RecordedRequest] {
+        &self.requests
+    }
+
+    pub fn clear_requests(&mut self) {
+        self.requests.clear();
+    }
+
+    // Queues a 429, rate limits are used up in order
+    pub fn rate_limit(&mut self, rate_limit: MockRateLimit) {
+        self.rate_limits.push_back(rate_limit);
+    }
+
+    // Always answers method and path (below the API base, without the query) with the response
+    pub fn respond(&mut self, method: &str, path: &str, response: MockResponse) {
+        self.stubs
+            .insert((method.to_uppercase(), path.to_string()), response);
+    }
+
+    // Answers one request. target is the path and query as sent, under the /api/v9 base.
+    pub fn handle(
+        &mut self,
+        method: &str,
+        target: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> MockResponse {
+        let method = method.to_uppercase();
+        let url = url::Url::parse(&format!("http://mock{}", target)).unwrap();
+        let api_path = url::Url::parse(HTTP_URL).unwrap().path().to_string();
+        let path = url.path().strip_prefix(&api_path).map(str::to_string);
+        let mut request = RecordedRequest {
+            method: method.clone(),
+            path: path.clone().unwrap_or(url.path().to_string()),
+            query: url.query_pairs().into_owned().collect(),
+            headers: headers
+                .iter()
+                .map(|(key, value)| (key.to_ascii_lowercase(), value.clone()))
+                .collect(),
+            body: body.to_vec(),
+            status: 0,
+        };
+
+        let response = match path {
+            Some(path) => self.route(&request, &path),
+            None => MockResponse::error(404, 0, "404: Not Found"),
+        };
+        request.status = response.status;
+        self.requests.push(request);
+        response
+    }
+
+    fn route(&mut self, request: &RecordedRequest, path: &str) -> MockResponse {
+        // Authenticated by the credentials in the body
+        if path.starts_with("/oauth2/token") {
+            return self.oauth2_token(request, path);
+        }
+        let auth = request.header("authorization").unwrap_or_default();
+        if !auth.starts_with("Bot ") && !auth.starts_with("Bearer ") {
+            return MockResponse::error(401, 0, "401: Unauthorized");
+        }
+        if let Some(access_token) = auth.strip_prefix("Bearer ") {
+            let Some(grant) = self.access_tokens.get(access_token) else {
+                return MockResponse::error(401, 0, "401: Unauthorized");
+            };
+            if request.method == "GET" && path == "/oauth2/@me" {
+                return MockResponse::json(
+                    200,
+                    &json!({
+                        "scopes": grant.scope.split(' ').collect::<Vec<_>>(),
+                        "user": self.users.get(&grant.user_id),
+                    }),
+                );
+            }
+        }
+
+        let position = self.rate_limits.iter().position(|limit| {
+            limit
+                .path
+                .as_ref()
+                .is_none_or(|prefix| path.starts_with(prefix))
+        });
+        if let Some(limit) = position.and_then(|i| self.rate_limits.remove(i)) {
+            return rate_limited(&limit, &bucket_id(&request.method, path));
+        }
+
+        let mut response = match self.stubs.get(&(request.method.clone(), path.to_string())) {
+            Some(response) => response.clone(),
+            None => {
+                let body = match request.body.is_empty() {
+                    true => Value::Null,
+                    false => match serde_json::from_slice(&request.body) {
+                        Ok(body) => body,
+                        Err(_) => {
+                            return MockResponse::error(
+                                400,
+                                50109,
+                                "The request body contains invalid JSON.",
+                            )
+                        }
+                    },
+                };
+                let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+                self.dispatch(&request.method, &segments, &request.query, &body)
+                    .unwrap_or_else(|error| error)
+            }
+        };
+        self.add_rate_limit_headers(&mut response, &bucket_id(&request.method, path));
+        response
+    }
+
+    fn oauth2_token(&mut self, request: &RecordedRequest, path: &str) -> MockResponse {
+        let form: HashMap<String, String> = url::form_urlencoded::parse(&request.body)
+            .into_owned()
+            .collect();
+        let field = |key: &str| form.get(key).cloned().unwrap_or_default();
+        if request.method != "POST" {
+            return MockResponse::error(405, 0, "405: Method Not Allowed");
+        }
+        if field("client_id") != self.bot_user_id || field("client_secret") != self.client_secret {
+            return oauth2_error(401, "invalid_client", None);
+        }
+
+        if path == "/oauth2/token/revoke" {
+            // Either token of a pair revokes both
+            let token = field("token");
+            let access_token = self.refresh_tokens.remove(&token).unwrap_or(token);
+            self.access_tokens.remove(&access_token);
+            self.refresh_tokens
+                .retain(|_, issued_with| *issued_with != access_token);
+            return MockResponse::json(200, &json!({}));
+        }
+
+        let (grant, refreshable) = match field("grant_type").as_str() {
+            "authorization_code" => match self.oauth2_codes.remove(&field("code")) {
+                Some(grant) => (grant, true),
+                None => {
+                    return oauth2_error(400, "invalid_grant", Some("Invalid \"code\" in request."))
+                }
+            },
+            "refresh_token" => {
+                let grant = self
+                    .refresh_tokens
+                    .remove(&field("refresh_token"))
+                    .and_then(|access_token| self.access_tokens.remove(&access_token));
+                match grant {
+                    Some(grant) => (grant, true),
+                    None => return oauth2_error(400, "invalid_grant", None),
+                }
+            }
+            // For the application's owner, here the bot's user
+            "client_credentials" => (
+                MockGrant {
+                    user_id: self.bot_user_id.clone(),
+                    scope: field("scope"),
+                },
+                false,
+            ),
+            _ => return oauth2_error(400, "unsupported_grant_type", None),
+        };
+
+        let access_token = format!("mock_access_{}", self.next_id());
+        let mut body = json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": 604800,
+            "scope": grant.scope,
+        });
+        if refreshable {
+            let refresh_token = format!("mock_refresh_{}", self.next_id());
+            self.refresh_tokens
+                .insert(refresh_token.clone(), access_token.clone());
+            body["refresh_token"] = json!(refresh_token);
+        }
+        self.access_tokens.insert(access_token, grant);
+        MockResponse::json(200, &body)
+    }
+
+    fn dispatch(
+        &mut self,
+        method: &str,
+        segments: &[&str],
+        query: &HashMap<String, String>,
+        body: &Value,
+    ) -> Handled {
+        let ok = |value: Value| Ok(MockResponse::json(200, &value));
+        match (method, segments) {
+            ("GET", ["gateway"]) => ok(json!({ "url": self.gateway_url })),
+            ("GET", ["gateway", "bot"]) => ok(json!({
+                "url": self.gateway_url,
+                "shards": 1,
+                "session_start_limit": {
+                    "total": 1000,
+                    "remaining": 999,
+                    "reset_after": 14400000,
+                    "max_concurrency": 1,
+                },
+            })),
+
+            // Users
+            ("GET", ["users", "@me"]) => ok(self.users[&self.bot_user_id].clone()),
+            ("PATCH", ["users", "@me"]) => {
+                let bot = self.users.get_mut(&self.bot_user_id).unwrap();
+                merge(bot, body, &["username", "avatar"]);
+                ok(bot.clone())
+            }
+            ("GET", ["users", "@me", "guilds"]) => ok(self.my_guilds(query)),
+            ("POST", ["users", "@me", "channels"]) => self.create_dm(body),
+            ("GET", ["users", user_id]) => ok(self.find_user(user_id)?.clone()),
+
+            // Guilds
+            ("GET", ["guilds", guild_id]) => {
+                let mut guild = self.find_guild(guild_id)?.clone();
+                if query.get("with_counts").map(String::as_str) == Some("true") {
+                    let count = self.members.get(*guild_id).map_or(0, BTreeMap::len);
+                    guild["approximate_member_count"] = json!(count);
+                    guild["approximate_presence_count"] = json!(0);
+                }
+                ok(guild)
+            }
+            ("GET", ["guilds", guild_id, "channels"]) => {
+                self.find_guild(guild_id)?;
+                ok(json!(self
+                    .channels
+                    .values()
+                    .filter(|channel| channel["guild_id"] == *guild_id)
+                    .collect::<Vec<_>>()))
+            }
+            ("POST", ["guilds", guild_id, "channels"]) => self.create_channel(guild_id, body),
+            ("PATCH", ["guilds", guild_id, "channels"]) => {
+                self.find_guild(guild_id)?;
+                for update in body.as_array().into_iter().flatten() {
+                    let id = update["id"].as_str().unwrap_or_default();
+                    let channel = self.find_channel_mut(id)?;
+                    merge(channel, update, &["position", "parent_id"]);
+                }
+                Ok(MockResponse::no_content())
+            }
+
+            // Channels
+            ("GET", ["channels", channel_id]) => ok(self.find_channel(channel_id)?.clone()),
+            ("PATCH", ["channels", channel_id]) => {
+                let channel = self.find_channel_mut(channel_id)?;
+                merge(
+                    channel,
+                    body,
+                    &[
+                        "name",
+                        "topic",
+                        "nsfw",
+                        "position",
+                        "parent_id",
+                        "rate_limit_per_user",
+                        "flags",
+                        "available_tags",
+                        "applied_tags",
+                    ],
+                );
+                ok(channel.clone())
+            }
+            ("DELETE", ["channels", channel_id]) => {
+                let channel = self.find_channel(channel_id)?.clone();
+                self.channels.remove(*channel_id);
+                self.messages.remove(*channel_id);
+                ok(channel)
+            }
+            ("POST", ["channels", channel_id, "typing"]) => {
+                self.find_channel(channel_id)?;
+                Ok(MockResponse::no_content())
+            }
+
+            // Messages
+            ("GET", ["channels", channel_id, "messages"]) => self.list_messages(channel_id, query),
+            ("POST", ["channels", channel_id, "messages"]) => self.create_message(channel_id, body),
+            ("POST", ["channels", channel_id, "messages", "bulk-delete"]) => {
+                self.bulk_delete(channel_id, body)
+            }
+            ("GET", ["channels", channel_id, "messages", message_id]) => {
+                ok(self.find_message(channel_id, message_id)?.clone())
+            }
+            ("PATCH", ["channels", channel_id, "messages", message_id]) => {
+                let bot_user_id = self.bot_user_id.clone();
+                let message = self.find_message_mut(channel_id, message_id)?;
+                if message["author"]["id"] != bot_user_id.as_str() {
+                    return Err(MockResponse::error(
+                        403,
+                        50005,
+                        "Cannot edit a message authored by another user",
+                    ));
+                }
+                if let Some(content) = body["content"].as_str() {
+                    message["content"] = json!(content);
+                }
+                message["edited_timestamp"] = json!(now_timestamp());
+                ok(message.clone())
+            }
+            ("DELETE", ["channels", channel_id, "messages", message_id]) => {
+                self.find_message(channel_id, message_id)?;
+                self.messages
+                    .get_mut(*channel_id)
+                    .unwrap()
+                    .remove(&snowflake(message_id));
+                Ok(MockResponse::no_content())
+            }
+            ("GET", ["channels", channel_id, "pins"]) => {
+                self.find_channel(channel_id)?;
+                let mut pinned: Vec<&Value> = self
+                    .messages(channel_id)
+                    .into_iter()
+                    .filter(|message| message["pinned"] == true)
+                    .collect();
+                pinned.reverse();
+                ok(json!(pinned))
+            }
+            ("PUT" | "DELETE", ["channels", channel_id, "pins", message_id]) => {
+                let message = self.find_message_mut(channel_id, message_id)?;
+                message["pinned"] = json!(method == "PUT");
+                Ok(MockResponse::no_content())
+            }
+
+            // Members
+            ("GET", ["guilds", guild_id, "members"]) => self.list_members(guild_id, query),
+            ("GET", ["guilds", guild_id, "members", "search"]) => {
+                self.search_members(guild_id, query)
+            }
+            ("GET", ["guilds", guild_id, "members", user_id]) => {
+                let user_id = self.resolve_me(user_id);
+                ok(self.find_member_mut(guild_id, &user_id)?.clone())
+            }
+            ("PATCH", ["guilds", guild_id, "members", user_id]) => {
+                let user_id = self.resolve_me(user_id);
+                if let Some(roles) = body["roles"].as_array() {
+                    for role_id in roles {
+                        self.find_role(guild_id, role_id.as_str().unwrap_or_default())?;
+                    }
+                }
+                let member = self.find_member_mut(guild_id, &user_id)?;
+                merge(member, body, &["roles", "mute", "deaf", "flags"]);
+                // null is meaningful here, it resets the nickname or lifts a timeout
+                for field in ["nick", "communication_disabled_until"] {
+                    if let Some(value) = body.get(field) {
+                        member[field] = value.clone();
+                    }
+                }
+                ok(member.clone())
+            }
+            ("DELETE", ["guilds", guild_id, "members", user_id]) => {
+                self.find_member_mut(guild_id, user_id)?;
+                self.members
+                    .get_mut(*guild_id)
+                    .unwrap()
+                    .remove(&snowflake(user_id));
+                Ok(MockResponse::no_content())
+            }
+            ("PUT" | "DELETE", ["guilds", guild_id, "members", user_id, "roles", role_id]) => {
+                self.find_role(guild_id, role_id)?;
+                let member = self.find_member_mut(guild_id, user_id)?;
+                let roles = member["roles"].as_array_mut().unwrap();
+                roles.retain(|role| role != role_id);
+                if method == "PUT" {
+                    roles.push(json!(role_id));
+                }
+                Ok(MockResponse::no_content())
+            }
+
+            // Roles
+            ("GET", ["guilds", guild_id, "roles"]) => {
+                ok(self.find_guild(guild_id)?["roles"].clone())
+            }
+            ("POST", ["guilds", guild_id, "roles"]) => {
+                self.find_guild(guild_id)?;
+                let name = body["name"].as_str().unwrap_or("new role").to_string();
+                let role_id = self.add_role(guild_id, &name);
+                let role = self.find_role(guild_id, &role_id)?;
+                update_role(role, body);
+                ok(role.clone())
+            }
+            ("PATCH", ["guilds", guild_id, "roles", role_id]) => {
+                let role = self.find_role(guild_id, role_id)?;
+                update_role(role, body);
+                ok(role.clone())
+            }
+            ("DELETE", ["guilds", guild_id, "roles", role_id]) => {
+                self.find_role(guild_id, role_id)?;
+                let roles = self.guilds.get_mut(*guild_id).unwrap()["roles"]
+                    .as_array_mut()
+                    .unwrap();
+                roles.retain(|role| role["id"] != *role_id);
+                for member in self
+                    .members
+                    .entry(guild_id.to_string())
+                    .or_default()
+                    .values_mut()
+                {
+                    member["roles"]
+                        .as_array_mut()
+                        .unwrap()
+                        .retain(|role| role != role_id);
+                }
+                Ok(MockResponse::no_content())
+            }
+
+            // Interaction responses have nowhere to go, the recorded request is what matters
+            ("POST", ["interactions", _, _, "callback"]) => Ok(MockResponse::no_content()),
+
+            _ => Err(MockResponse::error(404, 0, "404: Not Found")),
+        }
+    }
+
+    fn my_guilds(&self, query: &HashMap<String, String>) -> Value {
+        let with_counts = query.get("with_counts").map(String::as_str) == Some("true");
+        let guilds: Vec<Value> = self
+            .guilds
+            .values()
+            .filter(|guild| {
+                self.member(guild["id"].as_str().unwrap(), &self.bot_user_id)
+                    .is_some()
+            })
+            .map(|guild| {
+                let mut partial = json!({
+                    "id": guild["id"],
+                    "name": guild["name"],
+                    "icon": guild["icon"],
+                    "banner": guild["banner"],
+                    "owner": guild["owner_id"] == self.bot_user_id.as_str(),
+                    "permissions": EVERYONE_PERMISSIONS,
+                    "features": guild["features"],
+                });
+                if with_counts {
+                    let count = self
+                        .members
+                        .get(guild["id"].as_str().unwrap())
+                        .map_or(0, BTreeMap::len);
+                    partial["approximate_member_count"] = json!(count);
+                    partial["approximate_presence_count"] = json!(0);
+                }
+                partial
+            })
+            .collect();
+        json!(page(guilds, query, 200))
+    }
+
+    fn create_dm(&mut self, body: &Value) -> Handled {
+        let recipient_id = body["recipient_id"].as_str().unwrap_or_default();
+        let recipient = self.find_user(recipient_id)?.clone();
+        let existing = self
+            .channels
+            .values()
+            .find(|channel| channel["type"] == 1 && channel["recipients"][0]["id"] == recipient_id);
+        if let Some(channel) = existing {
+            return Ok(MockResponse::json(200, channel));
+        }
+
+        let id = self.next_id();
+        let channel = json!({
+            "id": id,
+            "type": 1,
+            "last_message_id": null,
+            "recipients": [recipient],
+            "flags": 0,
+        });
+        self.channels.insert(id, channel.clone());
+        Ok(MockResponse::json(200, &channel))
+    }
+
+    fn create_channel(&mut self, guild_id: &str, body: &Value) -> Handled {
+        self.find_guild(guild_id)?;
+        let Some(name) = body["name"].as_str().filter(|name| !name.is_empty()) else {
+            return Err(MockResponse::form_error(
+                "name",
+                "BASE_TYPE_REQUIRED",
+                "This field is required",
+            ));
+        };
+        let channel_type = body["type"].as_u64().unwrap_or(0);
+        let id = self.add_channel(guild_id, name, channel_type);
+        let channel = self.channels.get_mut(&id).unwrap();
+        merge(
+            channel,
+            body,
+            &[
+                "topic",
+                "nsfw",
+                "position",
+                "parent_id",
+                "rate_limit_per_user",
+                "bitrate",
+                "user_limit",
+                "permission_overwrites",
+            ],
+        );
+        Ok(MockResponse::json(201, channel))
+    }
+
+    fn list_messages(&self, channel_id: &str, query: &HashMap<String, String>) -> Handled {
+        self.find_channel(channel_id)?;
+        let limit = query_limit(query, 50, 100)?;
+        let messages = self.messages.get(channel_id).cloned().unwrap_or_default();
+        // Newest first whatever the cursor, like Discord
+        let mut page: Vec<Value> = if let Some(before) = query.get("before") {
+            messages
+                .range(..snowflake(before))
+                .rev()
+                .take(limit)
+                .map(|(_, m)| m.clone())
+                .collect()
+        } else if let Some(after) = query.get("after") {
+            let mut page: Vec<Value> = messages
+                .range(snowflake(after) + 1..)
+                .take(limit)
+                .map(|(_, m)| m.clone())
+                .collect();
+            page.reverse();
+            page
+        } else if let Some(around) = query.get("around") {
+            let around = snowflake(around);
+            let newer = messages.range(around..).take(limit / 2 + limit % 2);
+            let older = messages.range(..around).rev().take(limit / 2);
+            let mut page: Vec<Value> = newer.map(|(_, m)| m.clone()).collect();
+            page.reverse();
+            page.extend(older.map(|(_, m)| m.clone()));
+            page
+        } else {
+            messages.values().rev().take(limit).cloned().collect()
+        };
+        page.truncate(limit);
+        Ok(MockResponse::json(200, &json!(page)))
+    }
+
+    fn create_message(&mut self, channel_id: &str, body: &Value) -> Handled {
+        self.find_channel(channel_id)?;
+        let content = body["content"].as_str().unwrap_or_default();
+        let has_embeds = body["embeds"]
+            .as_array()
+            .is_some_and(|embeds| !embeds.is_empty());
+        if content.is_empty() && !has_embeds && body["poll"].is_null() {
+            return Err(MockResponse::error(
+                400,
+                50006,
+                "Cannot send an empty message",
+            ));
+        }
+        if content.chars().count() > 2000 {
+            return Err(MockResponse::form_error(
+                "content",
+                "BASE_TYPE_MAX_LENGTH",
+                "Must be 2000 or fewer in length.",
+            ));
+        }
+
+        let bot_user_id = self.bot_user_id.clone();
+        let mut message = self.message_json(channel_id, &bot_user_id, content);
+        for field in ["embeds", "components", "poll", "nonce", "tts", "flags"] {
+            if !body[field].is_null() {
+                message[field] = body[field].clone();
+            }
+        }
+        self.store_message(channel_id, message.clone());
+        Ok(MockResponse::json(200, &message))
+    }
+
+    fn bulk_delete(&mut self, channel_id: &str, body: &Value) -> Handled {
+        self.find_channel(channel_id)?;
+        let ids = body["messages"].as_array().cloned().unwrap_or_default();
+        if ids.len() < 2 || ids.len() > 100 {
+            return Err(MockResponse::form_error(
+                "messages",
+                "BASE_TYPE_BAD_LENGTH",
+                "Must be between 2 and 100 in length.",
+            ));
+        }
+        if let Some(messages) = self.messages.get_mut(channel_id) {
+            for id in ids {
+                messages.remove(&snowflake(id.as_str().unwrap_or_default()));
+            }
+        }
+        Ok(MockResponse::no_content())
+    }
+
+    fn list_members(&self, guild_id: &str, query: &HashMap<String, String>) -> Handled {
+        self.find_guild(guild_id)?;
+        let limit = query_limit(query, 1, 1000)?;
+        let after = query.get("after").map_or(0, |after| snowflake(after));
+        let members: Vec<&Value> = self
+            .members
+            .get(guild_id)
+            .map(|members| {
+                members
+                    .range(after + 1..)
+                    .take(limit)
+                    .map(|(_, m)| m)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(MockResponse::json(200, &json!(members)))
+    }
+
+    fn search_members(&self, guild_id: &str, query: &HashMap<String, String>) -> Handled {
+        self.find_guild(guild_id)?;
+        let limit = query_limit(query, 1, 1000)?;
+        let Some(search) = query.get("query") else {
+            return Err(MockResponse::form_error(
+                "query",
+                "BASE_TYPE_REQUIRED",
+                "This field is required",
+            ));
+        };
+        let search = search.to_lowercase();
+        let members: Vec<&Value> = self
+            .members
+            .get(guild_id)
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .filter(|member| {
+                [&member["nick"], &member["user"]["username"]]
+                    .iter()
+                    .filter_map(|name| name.as_str())
+                    .any(|name| name.to_lowercase().starts_with(&search))
+            })
+            .take(limit)
+            .collect();
+        Ok(MockResponse::json(200, &json!(members)))
+    }
+
+    fn message_json(&mut self, channel_id: &str, author_id: &str, content: &str) -> Value {
+        let mentions = parse_mentions(content);
+        let users: Vec<Value> = mentions
+            .iter()
+            .filter_map(|mention| match mention {
+                Mention::User(id) => self.users.get(id).cloned(),
+                _ => None,
+            })
+            .collect();
+        let roles: Vec<&String> = mentions
+            .iter()
+            .filter_map(|mention| match mention {
+                Mention::Role(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+
+        json!({
+            "id": self.next_id(),
+            "channel_id": channel_id,
+            "author": self.users.get(author_id).cloned().unwrap_or(Value::Null),
+            "content": content,
+            "timestamp": now_timestamp(),
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": mentions.contains(&Mention::Everyone),
+            "mentions": users,
+            "mention_roles": roles,
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+            "flags": 0,
+            "components": [],
+        })
+    }
+
+    fn store_message(&mut self, channel_id: &str, message: Value) {
+        let id = message["id"].as_str().unwrap().to_string();
+        if let Some(channel) = self.channels.get_mut(channel_id) {
+            channel["last_message_id"] = json!(id);
+        }
+        self.messages
+            .entry(channel_id.to_string())
+            .or_default()
+            .insert(snowflake(&id), message);
+    }
+
+    fn add_rate_limit_headers(&mut self, response: &mut MockResponse, bucket: &str) {
+        let now = Instant::now();
+        let (used, reset_at) = self
+            .buckets
+            .entry(bucket.to_string())
+            .or_insert((0, now + BUCKET_WINDOW));
+        if *reset_at <= now {
+            *used = 0;
+            *reset_at = now + BUCKET_WINDOW;
+        }
+        *used += 1;
+        let reset_after = reset_at.duration_since(now).as_secs_f64();
+        let reset = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            + reset_after;
+        response.headers.extend([
+            ("X-RateLimit-Limit".to_string(), BUCKET_LIMIT.to_string()),
+            (
+                "X-RateLimit-Remaining".to_string(),
+                BUCKET_LIMIT.saturating_sub(*used).to_string(),
+            ),
+            ("X-RateLimit-Reset".to_string(), format!("{:.3}", reset)),
+            (
+                "X-RateLimit-Reset-After".to_string(),
+                format!("{:.3}", reset_after),
+            ),
+            ("X-RateLimit-Bucket".to_string(), bucket.to_string()),
+        ]);
+    }
+
+    fn next_id(&mut self) -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.last_id = ((millis.saturating_sub(DISCORD_EPOCH_MS)) << 22).max(self.last_id + 1);
+        self.last_id.to_string()
+    }
+
+    fn resolve_me(&self, user_id: &str) -> String {
+        match user_id {
+            "@me" => self.bot_user_id.clone(),
+            _ => user_id.to_string(),
+        }
+    }
+
+    fn find_user(&self, user_id: &str) -> Result<&Value, MockResponse> {
+        self.users
+            .get(user_id)
+            .ok_or_else(|| MockResponse::error(404, 10013, "Unknown User"))
+    }
+
+    fn find_guild(&self, guild_id: &str) -> Result<&Value, MockResponse> {
+        self.guilds
+            .get(guild_id)
+            .ok_or_else(|| MockResponse::error(404, 10004, "Unknown Guild"))
+    }
+
+    fn find_channel(&self, channel_id: &str) -> Result<&Value, MockResponse> {
+        self.channels
+            .get(channel_id)
+            .ok_or_else(|| MockResponse::error(404, 10003, "Unknown Channel"))
+    }
+
+    fn find_channel_mut(&mut self, channel_id: &str) -> Result<&mut Value, MockResponse> {
+        self.channels
+            .get_mut(channel_id)
+            .ok_or_else(|| MockResponse::error(404, 10003, "Unknown Channel"))
+    }
+
+    fn find_message(&self, channel_id: &str, message_id: &str) -> Result<&Value, MockResponse> {
+        self.find_channel(channel_id)?;
+        self.messages
+            .get(channel_id)
+            .and_then(|messages| messages.get(&snowflake(message_id)))
+            .ok_or_else(|| MockResponse::error(404, 10008, "Unknown Message"))
+    }
+
+    fn find_message_mut(
+        &mut self,
+        channel_id: &str,
+        message_id: &str,
+    ) -> Result<&mut Value, MockResponse> {
+        self.find_channel(channel_id)?;
+        self.messages
+            .get_mut(channel_id)
+            .and_then(|messages| messages.get_mut(&snowflake(message_id)))
+            .ok_or_else(|| MockResponse::error(404, 10008, "Unknown Message"))
+    }
+
+    fn find_member_mut(
+        &mut self,
+        guild_id: &str,
+        user_id: &str,
+    ) -> Result<&mut Value, MockResponse> {
+        self.find_guild(guild_id)?;
+        self.members
+            .get_mut(guild_id)
+            .and_then(|members| members.get_mut(&snowflake(user_id)))
+            .ok_or_else(|| MockResponse::error(404, 10007, "Unknown Member"))
+    }
+
+    fn find_role(&mut self, guild_id: &str, role_id: &str) -> Result<&mut Value, MockResponse> {
+        self.guilds
+            .get_mut(guild_id)
+            .ok_or_else(|| MockResponse::error(404, 10004, "Unknown Guild"))?["roles"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|role| role["id"] == role_id)
+            .ok_or_else(|| MockResponse::error(404, 10011, "Unknown Role"))
+    }
+}
+
+// The token endpoints' errors, as in RFC 6749 rather than Discord's usual shape
+fn oauth2_error(status: u16, error: &str, description: Option<&str>) -> MockResponse {
+    let mut body = json!({ "error": error });
+    if let Some(description) = description {
+        body["error_description"] = json!(description);
+    }
+    MockResponse::json(status, &body)
+}
+
+// Serves a MockDiscord over HTTP on a background thread until dropped
+pub struct MockRestServer {
+    discord: Arc<Mutex<MockDiscord>>,
+    server: Arc<tiny_http::Server>,
+    address: std::net::SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockRestServer {
+    // On a free port on localhost
+    pub fn start() -> anyhow::Result<Self> {
+        Self::bind("127.0.0.1:0", MockDiscord::default())
+    }
+
+    pub fn bind(address: &str, discord: MockDiscord) -> anyhow::Result<Self> {
+        let server = Arc::new(tiny_http::Server::http(address).map_err(|e| anyhow::anyhow!(e))?);
+        let Some(address) = server.server_addr().to_ip() else {
+            return Err(anyhow::anyhow!("mock server is not on an ip address"));
+        };
+        let discord = Arc::new(Mutex::new(discord));
+
+        let thread = {
+            let server = server.clone();
+            let discord = discord.clone();
+            std::thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let mut body = Vec::new();
+                    let _ = request.as_reader().read_to_end(&mut body);
+                    let headers: Vec<(String, String)> = request
+                        .headers()
+                        .iter()
+                        .map(|header| (header.field.to_string(), header.value.to_string()))
+                        .collect();
+                    let response = discord.lock().unwrap().handle(
+                        &request.method().to_string(),
+                        request.url(),
+                        &headers,
+                        &body,
+                    );
+
+                    let mut reply = tiny_http::Response::from_data(response.body)
+                        .with_status_code(response.status);
+                    for (key, value) in response.headers {
+                        if let Ok(header) = tiny_http::Header::from_bytes(key, value) {
+                            reply.add_header(header);
+                        }
+                    }
+                    let _ = request.respond(reply);
+                }
+            })
+        };
+
+        Ok(Self {
+            discord,
+            server,
+            address,
+            thread: Some(thread),
+        })
+    }
+
+    // What to use in place of HTTP_URL, e.g. http://127.0.0.1:38123/api/v9
+    pub fn base_url(&self) -> String {
+        let api_path = url::Url::parse(HTTP_URL).unwrap().path().to_string();
+        format!("http://{}{}", self.address, api_path)
+    }
+
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+
+    // The model behind the server, to seed it or look at what requests did.
+    // Requests wait while this is held, so don't keep it across one.
+    pub fn discord(&self) -> MutexGuard<'_, MockDiscord> {
+        self.discord.lock().unwrap()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.discord().requests().to_vec()
+    }
+}
+
+impl Drop for MockRestServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn role_json(id: &str, name: &str, position: usize, permissions: &str) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "color": 0,
+        "hoist": false,
+        "icon": null,
+        "unicode_emoji": null,
+        "position": position,
+        "permissions": permissions,
+        "managed": false,
+        "mentionable": false,
+        "flags": 0,
+    })
+}
+
+// Role bodies send permissions as a number, Discord returns them as a string
+fn update_role(role: &mut Value, body: &Value) {
+    merge(
+        role,
+        body,
+        &["name", "color", "hoist", "mentionable", "unicode_emoji"],
+    );
+    if let Some(permissions) = body["permissions"].as_u64() {
+        role["permissions"] = json!(permissions.to_string());
+    } else if let Some(permissions) = body["permissions"].as_str() {
+        role["permissions"] = json!(permissions);
+    }
+}
+
+// Copies the listed fields that the body sets, leaving out nulls
+fn merge(target: &mut Value, body: &Value, fields: &[&str]) {
+    for field in fields {
+        if !body[field].is_null() {
+            target[*field] = body[field].clone();
+        }
+    }
+}
+
+fn snowflake(id: &str) -> u64 {
+    id.parse().unwrap_or_default()
+}
+
+fn now_timestamp() -> String {
+    // Discord's timestamps have microseconds and an offset
+    format_timestamp(SystemTime::now()).replace('Z', "000+00:00")
+}
+
+fn query_limit(
+    query: &HashMap<String, String>,
+    default: usize,
+    max: usize,
+) -> Result<usize, MockResponse> {
+    let Some(limit) = query.get("limit") else {
+        return Ok(default);
+    };
+    match limit.parse::<usize>() {
+        Ok(limit) if (1..=max).contains(&limit) => Ok(limit),
+        _ => Err(MockResponse::form_error(
+            "limit",
+            "NUMBER_TYPE_MAX",
+            &format!("int value should be between 1 and {}.", max),
+        )),
+    }
+}
+
+// before/after/limit over items sorted by id
+fn page(items: Vec<Value>, query: &HashMap<String, String>, default_limit: usize) -> Vec<Value> {
+    let id = |item: &Value| snowflake(item["id"].as_str().unwrap_or_default());
+    let limit = query
+        .get("limit")
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(default_limit);
+    let before = query.get("before").map(|before| snowflake(before));
+    let after = query.get("after").map(|after| snowflake(after));
+    items
+        .into_iter()
+        .filter(|item| before.is_none_or(|before| id(item) < before))
+        .filter(|item| after.is_none_or(|after| id(item) > after))
+        .take(limit)
+        .collect()
+}
+
+// Discord buckets routes by method and path, with ids other than the major parameters
+// (channel, guild, webhook) treated as the same route
+fn bucket_id(method: &str, path: &str) -> String {
+    let mut route = method.to_string();
+    let mut previous = "";
+    for segment in path.trim_start_matches('/').split('/') {
+        let major = matches!(previous, "channels" | "guilds" | "webhooks");
+        let is_id = segment.chars().all(|c| c.is_ascii_digit()) && !segment.is_empty();
+        route.push('/');
+        route.push_str(if is_id && !major { ":id" } else { segment });
+        previous = segment;
+    }
+    let mut hasher = DefaultHasher::new();
+    route.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn rate_limited(limit: &MockRateLimit, bucket: &str) -> MockResponse {
+    let mut response = MockResponse::json(
+        429,
+        &json!({
+            "message": "You are being rate limited.",
+            "retry_after": limit.retry_after,
+            "global": limit.global,
+        }),
+    );
+    response.headers.push((
+        "Retry-After".to_string(),
+        (limit.retry_after.ceil() as u64).to_string(),
+    ));
+    match limit.global {
+        true => response.headers.extend([
+            ("X-RateLimit-Global".to_string(), "true".to_string()),
+            ("X-RateLimit-Scope".to_string(), "global".to_string()),
+        ]),
+        false => response.headers.extend([
+            ("X-RateLimit-Limit".to_string(), BUCKET_LIMIT.to_string()),
+            ("X-RateLimit-Remaining".to_string(), "0".to_string()),
+            (
+                "X-RateLimit-Reset-After".to_string(),
+                format!("{:.3}", limit.retry_after),
+            ),
+            ("X-RateLimit-Bucket".to_string(), bucket.to_string()),
+            ("X-RateLimit-Scope".to_string(), "user".to_string()),
+        ]),
+    }
+    response
+}
@@ -0,0 +1,350 @@
+// OAuth2, for acting as a user rather than as the bot.
+//
+// AuthorizationUrl sends a user to Discord to approve some scopes, and Discord redirects them
+// back with a code. OAuthCall::ExchangeCode trades the code for an OAuth2TokenResponse, which
+// OAuth2Token keeps along with when it expires, and OAuthCall::RefreshToken renews it before
+// then. Calls made with the token need Bearer auth, see HttpAuth.
+//
+// See https://discord.com/developers/docs/topics/oauth2
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::http_api::*;
+use super::types::*;
+
+pub const OAUTH2_AUTHORIZE_URL: &str = "https://discord.com/oauth2/authorize";
+
+// Tokens this close to expiring are due for a refresh
+pub const OAUTH2_REFRESH_MARGIN: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OAuth2Scope {
+    #[serde(rename = "activities.read")]
+    ActivitiesRead,
+    #[serde(rename = "activities.write")]
+    ActivitiesWrite,
+    #[serde(rename = "applications.builds.read")]
+    ApplicationsBuildsRead,
+    #[serde(rename = "applications.builds.upload")]
+    ApplicationsBuildsUpload,
+    #[serde(rename = "applications.commands")]
+    ApplicationsCommands,
+    #[serde(rename = "applications.commands.update")]
+    ApplicationsCommandsUpdate,
+    #[serde(rename = "applications.commands.permissions.update")]
+    ApplicationsCommandsPermissionsUpdate,
+    #[serde(rename = "applications.entitlements")]
+    ApplicationsEntitlements,
+    #[serde(rename = "applications.store.update")]
+    ApplicationsStoreUpdate,
+    #[serde(rename = "bot")]
+    Bot,
+    #[serde(rename = "connections")]
+    Connections,
+    #[serde(rename = "dm_channels.read")]
+    DmChannelsRead,
+    #[serde(rename = "email")]
+    Email,
+    #[serde(rename = "gdm.join")]
+    GdmJoin,
+    #[serde(rename = "guilds")]
+    Guilds,
+    #[serde(rename = "guilds.join")]
+    GuildsJoin,
+    #[serde(rename = "guilds.members.read")]
+    GuildsMembersRead,
+    #[serde(rename = "identify")]
+    Identify,
+    #[serde(rename = "messages.read")]
+    MessagesRead,
+    #[serde(rename = "relationships.read")]
+    RelationshipsRead,
+    #[serde(rename = "role_connections.write")]
+    RoleConnectionsWrite,
+    #[serde(rename = "rpc")]
+    Rpc,
+    #[serde(rename = "rpc.activities.write")]
+    RpcActivitiesWrite,
+    #[serde(rename = "rpc.notifications.read")]
+    RpcNotificationsRead,
+    #[serde(rename = "rpc.voice.read")]
+    RpcVoiceRead,
+    #[serde(rename = "rpc.voice.write")]
+    RpcVoiceWrite,
+    #[serde(rename = "voice")]
+    Voice,
+    #[serde(rename = "webhook.incoming")]
+    WebhookIncoming,
+}
+
+impl OAuth2Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuth2Scope::ActivitiesRead => "activities.read",
+            OAuth2Scope::ActivitiesWrite => "activities.write",
+            OAuth2Scope::ApplicationsBuildsRead => "applications.builds.read",
+            OAuth2Scope::ApplicationsBuildsUpload => "applications.builds.upload",
+            OAuth2Scope::ApplicationsCommands => "applications.commands",
+            OAuth2Scope::ApplicationsCommandsUpdate => "applications.commands.update",
+            OAuth2Scope::ApplicationsCommandsPermissionsUpdate => {
+                "applications.commands.permissions.update"
+            }
+            OAuth2Scope::ApplicationsEntitlements => "applications.entitlements",
+            OAuth2Scope::ApplicationsStoreUpdate => "applications.store.update",
+            OAuth2Scope::Bot => "bot",
+            OAuth2Scope::Connections => "connections",
+            OAuth2Scope::DmChannelsRead => "dm_channels.read",
+            OAuth2Scope::Email => "email",
+            OAuth2Scope::GdmJoin => "gdm.join",
+            OAuth2Scope::Guilds => "guilds",
+            OAuth2Scope::GuildsJoin => "guilds.join",
+            OAuth2Scope::GuildsMembersRead => "guilds.members.read",
+            OAuth2Scope::Identify => "identify",
+            OAuth2Scope::MessagesRead => "messages.read",
+            OAuth2Scope::RelationshipsRead => "relationships.read",
+            OAuth2Scope::RoleConnectionsWrite => "role_connections.write",
+            OAuth2Scope::Rpc => "rpc",
+            OAuth2Scope::RpcActivitiesWrite => "rpc.activities.write",
+            OAuth2Scope::RpcNotificationsRead => "rpc.notifications.read",
+            OAuth2Scope::RpcVoiceRead => "rpc.voice.read",
+            OAuth2Scope::RpcVoiceWrite => "rpc.voice.write",
+            OAuth2Scope::Voice => "voice",
+            OAuth2Scope::WebhookIncoming => "webhook.incoming",
+        }
+    }
+}
+
+// Space separated, as the authorize URL and token endpoint want them
+pub fn scopes_param(scopes: &[OAuth2Scope]) -> String {
+    scopes
+        .iter()
+        .map(OAuth2Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuth2Prompt {
+    // Ask again even if the user already approved these scopes
+    Consent,
+    // Skip the screen when they already have
+    None,
+}
+
+// The application's id and secret, from the developer portal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OAuth2Credentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl OAuth2Credentials {
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationUrl {
+    client_id: String,
+    scopes: Vec<OAuth2Scope>,
+    redirect_uri: Option<String>,
+    state: Option<String>,
+    prompt: Option<OAuth2Prompt>,
+    permissions: Option<u64>,
+    guild_id: Option<String>,
+    disable_guild_select: bool,
+    integration_type: Option<ApplicationIntegrationType>,
+}
+
+impl AuthorizationUrl {
+    pub fn new(client_id: impl Into<String>, scopes: &[OAuth2Scope]) -> Self {
+        Self {
+            client_id: client_id.into(),
+            scopes: scopes.to_vec(),
+            redirect_uri: None,
+            state: None,
+            prompt: None,
+            permissions: None,
+            guild_id: None,
+            disable_guild_select: false,
+            integration_type: None,
+        }
+    }
+
+    // Where Discord sends the user back with ?code=..., which asks for the code grant.
+    // It has to be one of the application's registered redirects.
+    pub fn redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(redirect_uri.into());
+        self
+    }
+
+    // Sent back unchanged with the code, to tie the redirect to the user who started it
+    pub fn state(mut self, state: impl Into<String>) -> Self {
+        self.state = Some(state.into());
+        self
+    }
+
+    pub fn prompt(mut self, prompt: OAuth2Prompt) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    // With the bot scope, the permissions the bot is added with
+    pub fn permissions(mut self, permissions: u64) -> Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    // With the bot scope, the guild picked for the user, and whether they can pick another
+    pub fn guild(mut self, guild_id: impl Into<String>, disable_guild_select: bool) -> Self {
+        self.guild_id = Some(guild_id.into());
+        self.disable_guild_select = disable_guild_select;
+        self
+    }
+
+    // Install to a guild or to the user's account
+    pub fn integration_type(mut self, integration_type: ApplicationIntegrationType) -> Self {
+        self.integration_type = Some(integration_type);
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let mut url = url::Url::parse(OAUTH2_AUTHORIZE_URL).unwrap();
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("client_id", &self.client_id);
+            if let Some(redirect_uri) = &self.redirect_uri {
+                query.append_pair("response_type", "code");
+                query.append_pair("redirect_uri", redirect_uri);
+            }
+            query.append_pair("scope", &scopes_param(&self.scopes));
+            if let Some(state) = &self.state {
+                query.append_pair("state", state);
+            }
+            if let Some(prompt) = self.prompt {
+                query.append_pair(
+                    "prompt",
+                    match prompt {
+                        OAuth2Prompt::Consent => "consent",
+                        OAuth2Prompt::None => "none",
+                    },
+                );
+            }
+            if let Some(permissions) = self.permissions {
+                query.append_pair("permissions", &permissions.to_string());
+            }
+            if let Some(guild_id) = &self.guild_id {
+                query.append_pair("guild_id", guild_id);
+                if self.disable_guild_select {
+                    query.append_pair("disable_guild_select", "true");
+                }
+            }
+            if let Some(integration_type) = self.integration_type {
+                query.append_pair("integration_type", &integration_type.as_u32().to_string());
+            }
+        }
+        url.to_string()
+    }
+}
+
+// What /oauth2/token answers with
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OAuth2TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: u64, // Seconds
+    // Not given for client credentials
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+    // The guild the bot was added to, with the bot scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guild: Option<Guild>,
+    // The webhook made, with the webhook.incoming scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<Webhook>,
+}
+
+// An access token and when to stop using it, worth persisting between runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub scopes: Vec<String>,
+    pub expires_at: u64, // Unix seconds
+}
+
+impl OAuth2Token {
+    pub fn new(response: &OAuth2TokenResponse) -> Self {
+        Self::received_at(response, SystemTime::now())
+    }
+
+    // expires_in counts from when the response arrived
+    pub fn received_at(response: &OAuth2TokenResponse, received: SystemTime) -> Self {
+        Self {
+            access_token: response.access_token.clone(),
+            refresh_token: response.refresh_token.clone(),
+            scopes: response.scope.split(' ').map(str::to_string).collect(),
+            expires_at: unix_seconds(received) + response.expires_in,
+        }
+    }
+
+    // Takes a refresh's response, keeping the old refresh token if it didn't come with one
+    pub fn refreshed(&mut self, response: &OAuth2TokenResponse, received: SystemTime) {
+        let refresh_token = self.refresh_token.take();
+        *self = Self::received_at(response, received);
+        if self.refresh_token.is_none() {
+            self.refresh_token = refresh_token;
+        }
+    }
+
+    pub fn expires(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires_at)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        unix_seconds(now) >= self.expires_at
+    }
+
+    // Expired or within OAUTH2_REFRESH_MARGIN of it, and there's a refresh token to use
+    pub fn needs_refresh(&self, now: SystemTime) -> bool {
+        self.refresh_token.is_some() && self.is_expired(now + OAUTH2_REFRESH_MARGIN)
+    }
+
+    pub fn has_scope(&self, scope: OAuth2Scope) -> bool {
+        self.scopes.iter().any(|granted| granted == scope.as_str())
+    }
+
+    pub fn auth(&self) -> HttpAuth {
+        HttpAuth::Bearer(self.access_token.clone())
+    }
+
+    pub fn refresh_call(&self, credentials: &OAuth2Credentials) -> Option<HttpApiCall> {
+        Some(HttpApiCall::OAuth(OAuthCall::RefreshToken {
+            credentials: credentials.clone(),
+            refresh_token: self.refresh_token.clone()?,
+        }))
+    }
+
+    // Revoking the access token revokes its refresh token too
+    pub fn revoke_call(&self, credentials: &OAuth2Credentials) -> HttpApiCall {
+        HttpApiCall::OAuth(OAuthCall::RevokeToken {
+            credentials: credentials.clone(),
+            token: self.access_token.clone(),
+            token_type_hint: Some("access_token".to_string()),
+        })
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
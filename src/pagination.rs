@@ -0,0 +1,330 @@
+// Cursor pagination over the list endpoints that take before/after/limit.
+//
+// A Paginator holds the call for the next page. Send it however you like (plain Rust,
+// or a DiscordApiRequest::Paginate to the discord_api process), then feed the response
+// body back with advance() to get the page's items and move the cursor.
+//
+// The process fetches at most MAX_PAGINATE_PAGES per request so it isn't kept from its
+// heartbeats, and answers with a PaginateResponse to carry on from.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::http_api::*;
+
+// Pages fetched for one DiscordApiRequest::Paginate
+pub const MAX_PAGINATE_PAGES: usize = 5;
+
+// What DiscordApiRequest::Paginate responds with
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PaginateResponse {
+    pub items: Vec<serde_json::Value>,
+    // Send another Paginate with this to get the rest, None once every page has been fetched
+    pub next: Option<Paginator>,
+    // Why the last page failed, if it did. next is the paginator for that page.
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PageDirection {
+    // Walk towards older items, moving `before` to the smallest cursor seen
+    Before,
+    // Walk towards newer items, moving `after` to the largest cursor seen
+    After,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Paginator {
+    call: HttpApiCall,
+    direction: PageDirection,
+    max_items: Option<usize>,
+    fetched: usize,
+    done: bool,
+}
+
+impl Paginator {
+    // Fails if the call is not a paginated list endpoint.
+    // The direction follows the cursor already set on the call, otherwise the endpoint's default order.
+    pub fn new(call: HttpApiCall) -> anyhow::Result<Self> {
+        let direction = match &call {
+            HttpApiCall::AuditLog { after, .. }
+            | HttpApiCall::Messages(MessagesCall::GetAll { after, .. }) => match after {
+                Some(_) => PageDirection::After,
+                None => PageDirection::Before,
+            },
+            HttpApiCall::Guilds(GuildsCall::ListBans { before, .. })
+            | HttpApiCall::Guilds(GuildsCall::ListMyGuilds { before, .. })
+            | HttpApiCall::ScheduledEvents(ScheduledEventsCall::ListUsers { before, .. }) => {
+                match before {
+                    Some(_) => PageDirection::Before,
+                    None => PageDirection::After,
+                }
+            }
+            HttpApiCall::Members(MembersCall::GetAll { .. })
+            | HttpApiCall::Emoji(EmojiCall::ListReactionsByEmoji { .. }) => PageDirection::After,
+            HttpApiCall::Threads(ThreadsCall::ListMyPrivateArchivedThreads { .. })
+            | HttpApiCall::Threads(ThreadsCall::ListPrivateArchivedThreads { .. })
+            | HttpApiCall::Threads(ThreadsCall::ListPublicArchivedThreads { .. }) => {
+                PageDirection::Before
+            }
+            _ => return Err(anyhow::anyhow!("call is not paginated: {:?}", call)),
+        };
+
+        if let HttpApiCall::Messages(MessagesCall::GetAll {
+            around: Some(_), ..
+        }) = &call
+        {
+            return Err(anyhow::anyhow!(
+                "messages fetched `around` cannot be paginated"
+            ));
+        }
+
+        Ok(Self {
+            call,
+            direction,
+            max_items: None,
+            fetched: 0,
+            done: false,
+        })
+    }
+
+    // Stop after this many items in total, the last page is truncated to fit
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self.done = max_items == 0;
+        self
+    }
+
+    pub fn direction(&self) -> PageDirection {
+        self.direction
+    }
+
+    // The call that fetches the next page, None once pagination is finished
+    pub fn next_call(&self) -> Option<&HttpApiCall> {
+        match self.done {
+            true => None,
+            false => Some(&self.call),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // Number of items returned so far
+    pub fn fetched(&self) -> usize {
+        self.fetched
+    }
+
+    // Ends pagination early, e.g. when a caller's own stop condition is met
+    pub fn stop(&mut self) {
+        self.done = true;
+    }
+
+    // Takes the response body for next_call() and returns the items on that page.
+    // Moves the cursor, or marks pagination done when the page is the last one.
+    pub fn advance(&mut self, body: &[u8]) -> anyhow::Result<Vec<serde_json::Value>> {
+        if self.done {
+            return Ok(Vec::new());
+        }
+
+        let mut page = serde_json::from_slice::<serde_json::Value>(body)?;
+        let (items_key, cursor_path) = self.page_layout();
+
+        let has_more = page
+            .get("has_more")
+            .and_then(|x| x.as_bool())
+            .unwrap_or(true);
+        let mut items = match items_key {
+            Some(key) => page.get_mut(key).map(|x| x.take()),
+            None => Some(page),
+        }
+        .and_then(|x| match x {
+            serde_json::Value::Array(items) => Some(items),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!("unexpected page body: {}", String::from_utf8_lossy(body))
+        })?;
+
+        let cursor = items
+            .iter()
+            .filter_map(|item| {
+                cursor_path
+                    .iter()
+                    .try_fold(item, |value, key| value.get(key))
+                    .and_then(cursor_string)
+            })
+            .reduce(|a, b| {
+                let b_is_further = match self.direction {
+                    PageDirection::Before => cursor_cmp(&b, &a).is_lt(),
+                    PageDirection::After => cursor_cmp(&b, &a).is_gt(),
+                };
+                if b_is_further {
+                    b
+                } else {
+                    a
+                }
+            });
+
+        let page_len = items.len();
+        if let Some(max_items) = self.max_items {
+            items.truncate(max_items.saturating_sub(self.fetched));
+        }
+        self.fetched += items.len();
+
+        let short_page = match self.limit() {
+            Some(limit) => (page_len as i64) < limit,
+            None => false,
+        };
+        let reached_max = self.max_items.is_some_and(|max| self.fetched >= max);
+
+        match cursor {
+            Some(cursor) if has_more && !short_page && !reached_max => self.set_cursor(cursor),
+            _ => self.done = true,
+        }
+
+        Ok(items)
+    }
+
+    // Same as advance, decoding each item
+    pub fn advance_as<T: DeserializeOwned>(&mut self, body: &[u8]) -> anyhow::Result<Vec<T>> {
+        self.advance(body)?
+            .into_iter()
+            .map(|item| Ok(serde_json::from_value(item)?))
+            .collect()
+    }
+
+    // Drives pagination from plain Rust, `fetch` sends a call and returns the response body.
+    // Yields one page of items per call and stops after the first error.
+    pub fn pages<F>(self, fetch: F) -> Pages<F>
+    where
+        F: FnMut(&HttpApiCall) -> anyhow::Result<Vec<u8>>,
+    {
+        Pages {
+            paginator: self,
+            fetch,
+        }
+    }
+
+    // Where the items sit in a page body, and where each item keeps its cursor
+    fn page_layout(&self) -> (Option<&'static str>, &'static [&'static str]) {
+        match &self.call {
+            HttpApiCall::AuditLog { .. } => (Some("audit_log_entries"), &["id"]),
+            HttpApiCall::Guilds(GuildsCall::ListBans { .. })
+            | HttpApiCall::Members(MembersCall::GetAll { .. })
+            | HttpApiCall::ScheduledEvents(ScheduledEventsCall::ListUsers { .. }) => {
+                (None, &["user", "id"])
+            }
+            HttpApiCall::Threads(ThreadsCall::ListMyPrivateArchivedThreads { .. }) => {
+                (Some("threads"), &["id"])
+            }
+            // These two page by archive time rather than by snowflake
+            HttpApiCall::Threads(ThreadsCall::ListPrivateArchivedThreads { .. })
+            | HttpApiCall::Threads(ThreadsCall::ListPublicArchivedThreads { .. }) => {
+                (Some("threads"), &["thread_metadata", "archive_timestamp"])
+            }
+            _ => (None, &["id"]),
+        }
+    }
+
+    fn limit(&self) -> Option<i64> {
+        match &self.call {
+            HttpApiCall::AuditLog { limit, .. }
+            | HttpApiCall::Guilds(GuildsCall::ListBans { limit, .. })
+            | HttpApiCall::Guilds(GuildsCall::ListMyGuilds { limit, .. })
+            | HttpApiCall::Members(MembersCall::GetAll { limit, .. })
+            | HttpApiCall::Emoji(EmojiCall::ListReactionsByEmoji { limit, .. })
+            | HttpApiCall::Messages(MessagesCall::GetAll { limit, .. })
+            | HttpApiCall::ScheduledEvents(ScheduledEventsCall::ListUsers { limit, .. })
+            | HttpApiCall::Threads(ThreadsCall::ListMyPrivateArchivedThreads { limit, .. })
+            | HttpApiCall::Threads(ThreadsCall::ListPrivateArchivedThreads { limit, .. })
+            | HttpApiCall::Threads(ThreadsCall::ListPublicArchivedThreads { limit, .. }) => *limit,
+            _ => None,
+        }
+    }
+
+    fn set_cursor(&mut self, cursor: String) {
+        match (&mut self.call, self.direction) {
+            (HttpApiCall::AuditLog { before, .. }, PageDirection::Before)
+            | (HttpApiCall::Guilds(GuildsCall::ListBans { before, .. }), PageDirection::Before)
+            | (
+                HttpApiCall::Guilds(GuildsCall::ListMyGuilds { before, .. }),
+                PageDirection::Before,
+            )
+            | (HttpApiCall::Messages(MessagesCall::GetAll { before, .. }), PageDirection::Before)
+            | (
+                HttpApiCall::ScheduledEvents(ScheduledEventsCall::ListUsers { before, .. }),
+                PageDirection::Before,
+            )
+            | (HttpApiCall::Threads(ThreadsCall::ListMyPrivateArchivedThreads { before, .. }), _) =>
+            {
+                *before = Some(cursor);
+            }
+            // Query params are not encoded, and the timestamp offset's `+` would read as a space
+            (HttpApiCall::Threads(ThreadsCall::ListPrivateArchivedThreads { before, .. }), _)
+            | (HttpApiCall::Threads(ThreadsCall::ListPublicArchivedThreads { before, .. }), _) => {
+                *before = Some(cursor.replace('+', "%2B"));
+            }
+            (HttpApiCall::AuditLog { after, .. }, PageDirection::After)
+            | (HttpApiCall::Guilds(GuildsCall::ListBans { after, .. }), PageDirection::After)
+            | (HttpApiCall::Guilds(GuildsCall::ListMyGuilds { after, .. }), PageDirection::After)
+            | (HttpApiCall::Messages(MessagesCall::GetAll { after, .. }), PageDirection::After)
+            | (
+                HttpApiCall::ScheduledEvents(ScheduledEventsCall::ListUsers { after, .. }),
+                PageDirection::After,
+            )
+            | (HttpApiCall::Emoji(EmojiCall::ListReactionsByEmoji { after, .. }), _) => {
+                *after = Some(cursor);
+            }
+            // Members take the snowflake as an integer
+            (HttpApiCall::Members(MembersCall::GetAll { after, .. }), _) => match cursor.parse() {
+                Ok(cursor) => *after = Some(cursor),
+                Err(_) => self.done = true,
+            },
+            _ => self.done = true,
+        }
+    }
+}
+
+pub struct Pages<F> {
+    paginator: Paginator,
+    fetch: F,
+}
+
+impl<F> Pages<F> {
+    pub fn paginator(&self) -> &Paginator {
+        &self.paginator
+    }
+}
+
+impl<F> Iterator for Pages<F>
+where
+    F: FnMut(&HttpApiCall) -> anyhow::Result<Vec<u8>>,
+{
+    type Item = anyhow::Result<Vec<serde_json::Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let call = self.paginator.next_call()?;
+        let page = (self.fetch)(call).and_then(|body| self.paginator.advance(&body));
+        if page.is_err() {
+            self.paginator.stop();
+        }
+        Some(page)
+    }
+}
+
+fn cursor_string(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// Snowflakes compare as integers, archive timestamps as ISO8601 strings
+fn cursor_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
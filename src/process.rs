@@ -2,28 +2,76 @@ use super::*;
 use kinode_process_lib::{
     await_message, get_blob, get_state,
     http::{
-        close_ws_connection, open_ws_connection_and_await, send_ws_client_push, HttpClientAction,
-        HttpClientRequest, OutgoingHttpRequest, WsMessageType,
+        bind_http_path, close_ws_connection, open_ws_connection_and_await, send_response,
+        send_ws_client_push, HttpClientAction, HttpClientError, HttpClientRequest, HttpResponse,
+        HttpServerRequest, OutgoingHttpRequest, StatusCode, WsMessageType,
     },
     print_to_terminal,
     timer::set_timer,
+    vfs::open_file,
     Address, LazyLoadBlob, Message, Request, Response,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct State {
     gateway_url: String,
     bots: Bots,
     channels: WsChannels,
+    #[serde(default)]
+    voice_channels: VoiceWsChannels,
+    #[serde(default)]
+    voice_channels_opened: u32,
+    // Each gateway websocket gets a channel of its own, see open_gateway
+    #[serde(default)]
+    gateway_channels_opened: u32,
+    // VFS paths Record has written to, the only ones Replay reads
+    #[serde(default)]
+    recordings: HashSet<String>,
+    // Base URLs for REST calls in place of HTTP_URL, e.g. a mock server's, by bot token
+    #[serde(default)]
+    http_urls: HashMap<String, String>,
 }
 
+// Voice websockets get channel ids above the bots' gateway ones
+const VOICE_WS_CHANNEL_BASE: u32 = 1 << 16;
+
+// How long after Ready to wait for its guilds before sending AllGuildsReady anyway,
+// since a guild can stay unavailable through an outage
+const ALL_GUILDS_READY_TIMEOUT_MS: u64 = 15_000;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct Heartbeat {
     bot: BotId,
 }
 
+// Timers for DiscordApiRequest::RequestGuildMembers, keyed by nonce
+#[derive(Serialize, Deserialize, Debug)]
+enum MembersTimer {
+    // Give up and tell the parent
+    Deadline { bot: BotId, nonce: String },
+    // Send op 8 again after Discord rate limited it
+    Retry { bot: BotId, nonce: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum GuildsTimer {
+    // Stop waiting for the guilds of the Ready with this session
+    Ready { bot: BotId, session_id: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum GatewayTimer {
+    // Open the gateway websocket again, after backing off
+    Reconnect { bot: BotId, url: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum VoiceTimer {
+    Heartbeat { bot: BotId, guild_id: String },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct GatewayInfo {
     url: String,
@@ -32,14 +80,62 @@ struct GatewayInfo {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Bot {
     pub parent: Address,
-    pub gateway_connection_open: bool,
-    pub resume_gateway_url: Option<String>,
     pub token: String,
-    pub heartbeat_interval: u64,
-    pub heartbeat_sequence: u64,
     pub intents: u128,
-    pub session_id: String,
     pub ws_client_channel: u32,
+    // The heartbeat timer's interval, 0 when it isn't running
+    pub heartbeat_interval: u64,
+    pub connection: GatewayConnection,
+    // Rebuilt from the gateway after every connect, so not worth persisting
+    #[serde(skip)]
+    pub cache: Cache,
+    // Guilds listed in Ready that haven't had their GuildCreate yet, None once all have
+    #[serde(skip)]
+    pub pending_guilds: Option<HashSet<String>>,
+    // Member requests waiting for their chunks, by nonce
+    #[serde(skip)]
+    pub member_requests: HashMap<String, MemberChunks>,
+    #[serde(skip)]
+    pub member_requests_sent: u64,
+    // Voice connections by guild id, at most one per guild
+    #[serde(skip)]
+    pub voice_connections: HashMap<String, BotVoiceConnection>,
+    // VFS file the gateway traffic is appended to, see DiscordApiRequest::Record
+    #[serde(skip)]
+    pub recording: Option<String>,
+}
+
+impl Bot {
+    pub fn new(parent: Address, bot_id: &BotId, ws_client_channel: u32) -> Self {
+        Self {
+            parent,
+            token: bot_id.token.clone(),
+            intents: bot_id.intents,
+            ws_client_channel,
+            heartbeat_interval: 0,
+            connection: GatewayConnection::new(&bot_id.token, bot_id.intents, ""),
+            cache: Cache::default(),
+            pending_guilds: None,
+            member_requests: HashMap::new(),
+            member_requests_sent: 0,
+            voice_connections: HashMap::new(),
+            recording: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BotVoiceConnection {
+    pub ws_channel: u32,
+    pub ws_open: bool,
+    pub heartbeat_interval: u64,
+    pub connection: VoiceConnection,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceWsChannel {
+    pub bot: BotId,
+    pub guild_id: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Hash, Eq, Clone)]
@@ -58,13 +154,97 @@ impl BotId {
 pub type Bots = HashMap<BotId, Bot>;
 // HashMap of websocket_channel_id -> BodId
 pub type WsChannels = HashMap<u32, BotId>;
+// HashMap of voice websocket_channel_id -> BotId and guild
+pub type VoiceWsChannels = HashMap<u32, VoiceWsChannel>;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum DiscordApiRequest {
     Connect(BotId),
     Disconnect(BotId),
-    Gateway { bot: BotId, event: GatewaySendEvent },
-    Http { bot: BotId, call: HttpApiCall },
+    // Sends the bot's REST calls, and its gateway lookup on Connect, to another base URL such
+    // as MockRestServer::base_url(). None goes back to HTTP_URL. Only taken from processes on
+    // this node, and only for a bot whose token the sender has.
+    SetHttpUrl {
+        bot: BotId,
+        url: Option<String>,
+    },
+    // Appends the bot's gateway frames, tokens redacted, to a VFS file discord_api can write
+    // to, as GatewayFrame lines. zlib-stream frames are recorded inflated. None stops recording.
+    // Only taken from processes on this node.
+    Record {
+        bot: BotId,
+        path: Option<String>,
+    },
+    // Plays a recording made by Record back through the gateway event handling, without a
+    // websocket. The requester gets the events a parent would have, then a ReplayResponse.
+    // Only taken from processes on this node.
+    Replay {
+        bot: BotId,
+        path: String,
+    },
+    Gateway {
+        bot: BotId,
+        event: GatewaySendEvent,
+    },
+    Http {
+        bot: BotId,
+        call: HttpApiCall,
+    },
+    // Like Http, with other auth than the bot's, e.g. a user's OAuth2Token::auth() for
+    // RoleConnectionsCall::UpdateApplicationUser. Sent to the bot's base URL.
+    HttpAs {
+        bot: BotId,
+        auth: HttpAuth,
+        call: HttpApiCall,
+    },
+    // Fetches up to MAX_PAGINATE_PAGES pages and responds with a PaginateResponse
+    Paginate {
+        bot: BotId,
+        paginator: Paginator,
+    },
+    // Chooses what the bot's gateway cache keeps, everything by default
+    ConfigureCache {
+        bot: BotId,
+        config: CacheConfig,
+    },
+    // Reads from the bot's gateway cache, responds with JSON (null when not cached)
+    Cache {
+        bot: BotId,
+        query: CacheQuery,
+    },
+    // Sends op 8 and responds with its nonce. Once every chunk has arrived the parent gets a
+    // GatewayReceiveEvent::GuildMembers, or GuildMembersFailed after the timeout.
+    RequestGuildMembers {
+        bot: BotId,
+        request: GuildMembersRequest,
+    },
+    // Joins (or moves to) a voice channel and runs its voice gateway, the parent gets
+    // GatewayReceiveEvent::Voice events. Audio itself is up to the parent.
+    JoinVoice {
+        bot: BotId,
+        guild_id: String,
+        channel_id: String,
+        self_mute: bool,
+        self_deaf: bool,
+    },
+    LeaveVoice {
+        bot: BotId,
+        guild_id: String,
+    },
+    // After IP discovery, with the address and port Discord saw. Without a mode, one of
+    // VOICE_ENCRYPTION_MODES is picked.
+    VoiceSelectProtocol {
+        bot: BotId,
+        guild_id: String,
+        address: String,
+        port: u16,
+        mode: Option<String>,
+    },
+    VoiceSpeaking {
+        bot: BotId,
+        guild_id: String,
+        speaking: bool,
+    },
 }
 
 pub fn handle_message(our: &Address, state: &mut State) -> anyhow::Result<()> {
@@ -90,7 +270,29 @@ pub fn handle_message(our: &Address, state: &mut State) -> anyhow::Result<()> {
         Message::Response { context, .. } => {
             // Handle timer responses with a context of type Heartbeat
             // Used to maintain the Discord Gateway API connection
-            maintain_heartbeat(our, context, state)?;
+            // or of type MembersTimer for member requests, GuildsTimer for AllGuildsReady,
+            // GatewayTimer for reconnects, or VoiceTimer for voice connections
+            let bytes = context.as_deref().unwrap_or_default();
+            if let Ok(timer) = serde_json::from_slice::<MembersTimer>(bytes) {
+                handle_members_timer(our, timer, state)?;
+            } else if let Ok(GuildsTimer::Ready { bot, session_id }) =
+                serde_json::from_slice::<GuildsTimer>(bytes)
+            {
+                if let Some(bot) = state.bots.get_mut(&bot) {
+                    // A timer from before a reconnect has nothing left to wait for
+                    if bot.connection.session_id.as_deref() == Some(session_id.as_str()) {
+                        guilds_timed_out(bot)?;
+                    }
+                }
+            } else if let Ok(GatewayTimer::Reconnect { bot, url }) =
+                serde_json::from_slice::<GatewayTimer>(bytes)
+            {
+                reopen_gateway(our, &bot, url, state)?;
+            } else if let Ok(timer) = serde_json::from_slice::<VoiceTimer>(bytes) {
+                handle_voice_timer(our, timer, state)?;
+            } else {
+                maintain_heartbeat(our, context, state)?;
+            }
         }
     }
     Ok(())
@@ -106,23 +308,12 @@ fn handle_api_request(
         // Connect a bot to the Discord Gateway API
         // Comes from the parent process
         DiscordApiRequest::Connect(bot_id) => {
-            let ws_client_channel = state.bots.len() as u32;
-            let bot = Bot {
-                parent: source.clone(),
-                token: bot_id.token.clone(),
-                resume_gateway_url: None,
-                intents: bot_id.intents.clone(),
-                gateway_connection_open: false,
-                heartbeat_interval: 0,
-                heartbeat_sequence: 0,
-                session_id: "".to_string(),
-                ws_client_channel,
-            };
+            // open_gateway gives it a channel
+            let bot = Bot::new(source.clone(), &bot_id, u32::MAX);
 
             state
                 .bots
                 .insert(BotId::new(bot.token.clone(), bot.intents), bot);
-            state.channels.insert(ws_client_channel, bot_id);
 
             let _ = Request::new()
                 .target(("our", "http_client", "distro", "sys"))
@@ -130,7 +321,7 @@ fn handle_api_request(
                     OutgoingHttpRequest {
                         method: "GET".to_string(),
                         version: None,
-                        url: format!("{}/gateway", HTTP_URL.to_string()),
+                        url: format!("{}/gateway", http_url(state, &bot_id)),
                         headers: HashMap::new(),
                     },
                 ))?)
@@ -142,21 +333,58 @@ fn handle_api_request(
 
             let gateway_info = serde_json::from_slice::<GatewayInfo>(&blob.bytes)?;
             state.gateway_url = gateway_info.url.clone();
+            let Some(bot) = state.bots.get_mut(&bot_id) else {
+                return Ok(());
+            };
+            bot.connection.gateway_url = gateway_info.url;
 
             // set_state(&serde_json::to_vec(state)?);
-            connect_gateway(our, &ws_client_channel, gateway_info.url)?;
+            let url = bot.connection.connect();
+            connect_gateway(our, &bot_id, url, state)?;
+        }
+        DiscordApiRequest::SetHttpUrl { bot, url } => {
+            if source.node != our.node {
+                return Err(anyhow::anyhow!(
+                    "discord_api: SetHttpUrl is only taken from this node"
+                ));
+            }
+            match url {
+                Some(url) => state.http_urls.insert(bot.token, url),
+                None => state.http_urls.remove(&bot.token),
+            };
+        }
+        DiscordApiRequest::Record { bot, path } => {
+            if source.node != our.node {
+                return Err(anyhow::anyhow!(
+                    "discord_api: Record is only taken from this node"
+                ));
+            }
+            let Some(bot) = state.bots.get_mut(&bot) else {
+                return Err(anyhow::anyhow!("discord_api: bot not connected"));
+            };
+            if let Some(path) = &path {
+                state.recordings.insert(path.clone());
+            }
+            bot.recording = path;
+        }
+        DiscordApiRequest::Replay { bot, path } => {
+            let mut frames = 0;
+            let error = replay_recording(our, source, &bot, &path, &mut frames, state)
+                .err()
+                .map(|e| e.to_string());
+            Response::new()
+                .body(serde_json::to_vec(&ReplayResponse { frames, error })?)
+                .send()?;
         }
         // Disconnect a bot from the Discord Gateway API
         DiscordApiRequest::Disconnect(bot_id) => {
             if let Some(bot) = state.bots.get_mut(&bot_id) {
-                bot.gateway_connection_open = false;
                 bot.heartbeat_interval = 0;
-                bot.heartbeat_sequence = 0;
-                bot.session_id = "".to_string();
 
                 // Send a close message to http_client
                 close_ws_connection(our.node.clone(), bot.ws_client_channel)?;
 
+                state.channels.remove(&bot.ws_client_channel);
                 state.bots.remove(&bot_id);
                 // set_state(&serde_json::to_vec(state)?);
             }
@@ -168,21 +396,8 @@ fn handle_api_request(
         // Send an http request to the Discord HTTP API
         DiscordApiRequest::Http { bot, call } => {
             // Send an http request to http_client
-            let (url, method, http_body) = call.to_request();
-            let mut headers = HashMap::new();
-            headers.insert("Authorization".to_string(), format!("Bot {}", bot.token));
-            headers.insert("Content-Type".to_string(), "application/json".to_string());
-            headers.insert(
-                "User-Agent".to_string(),
-                format!("DiscordBot ({}, {})", "https://kinode.network", "1.0"),
-            );
-
-            let http_req = OutgoingHttpRequest {
-                method: method.to_string(),
-                version: None,
-                url: url.to_string(),
-                headers,
-            };
+            let (http_req, http_body) =
+                outgoing_http_request(&bot_auth(&bot), &call, http_url(state, &bot));
 
             let _ = Request::new()
                 .target(("our", "http_client", "distro", "sys"))
@@ -191,11 +406,362 @@ fn handle_api_request(
                 .blob_bytes(http_body)
                 .send()?;
         }
+        DiscordApiRequest::HttpAs { bot, auth, call } => {
+            let (http_req, http_body) = outgoing_http_request(&auth, &call, http_url(state, &bot));
+
+            let _ = Request::new()
+                .target(("our", "http_client", "distro", "sys"))
+                .inherit(true)
+                .body(serde_json::to_vec(&HttpClientAction::Http(http_req))?)
+                .blob_bytes(http_body)
+                .send()?;
+        }
+        // Walk a paginated endpoint, one http request per page
+        DiscordApiRequest::Paginate { bot, mut paginator } => {
+            let mut items = Vec::new();
+            let mut error = None;
+            for _ in 0..MAX_PAGINATE_PAGES {
+                let Some(call) = paginator.next_call() else {
+                    break;
+                };
+                match fetch_page(&bot, call, http_url(state, &bot))
+                    .and_then(|body| paginator.advance(&body))
+                {
+                    Ok(page) => items.extend(page),
+                    Err(e) => {
+                        error = Some(e.to_string());
+                        break;
+                    }
+                }
+            }
+
+            let response = PaginateResponse {
+                items,
+                next: (!paginator.is_done()).then_some(paginator),
+                error,
+            };
+            Response::new()
+                .body(serde_json::to_vec(&response)?)
+                .send()?;
+        }
+        DiscordApiRequest::ConfigureCache { bot, config } => {
+            if let Some(bot) = state.bots.get_mut(&bot) {
+                bot.cache.set_config(config);
+            }
+        }
+        DiscordApiRequest::Cache { bot, query } => {
+            let result = match state.bots.get(&bot) {
+                Some(bot) => bot.cache.query(&query),
+                None => serde_json::Value::Null,
+            };
+
+            Response::new().body(serde_json::to_vec(&result)?).send()?;
+        }
+        DiscordApiRequest::RequestGuildMembers {
+            bot: bot_id,
+            request,
+        } => {
+            let Some(bot) = state.bots.get_mut(&bot_id) else {
+                return Err(anyhow::anyhow!("discord_api: bot not connected"));
+            };
+            if !bot.connection.connected() {
+                return Err(anyhow::anyhow!("discord_api: gateway not connected"));
+            }
+            request.validate()?;
+
+            bot.member_requests_sent += 1;
+            let nonce = format!("members-{}", bot.member_requests_sent);
+            send_member_request(our, bot, &request, &nonce)?;
+            set_timer(
+                request.timeout_seconds() * 1000,
+                Some(serde_json::to_vec(&MembersTimer::Deadline {
+                    bot: bot_id,
+                    nonce: nonce.clone(),
+                })?),
+            );
+            bot.member_requests
+                .insert(nonce.clone(), MemberChunks::new(request, &nonce));
+
+            Response::new().body(serde_json::to_vec(&nonce)?).send()?;
+        }
+        DiscordApiRequest::JoinVoice {
+            bot: bot_id,
+            guild_id,
+            channel_id,
+            self_mute,
+            self_deaf,
+        } => {
+            let Some(bot) = state.bots.get(&bot_id) else {
+                return Err(anyhow::anyhow!("discord_api: bot not connected"));
+            };
+            let Some(user_id) = bot.cache.current_user().map(|user| user.id.clone()) else {
+                return Err(anyhow::anyhow!("discord_api: gateway not ready"));
+            };
+
+            // Moving to another channel keeps the connection, Discord sends new voice events
+            if !bot.voice_connections.contains_key(&guild_id) {
+                let ws_channel = VOICE_WS_CHANNEL_BASE + state.voice_channels_opened;
+                state.voice_channels_opened += 1;
+                state.voice_channels.insert(
+                    ws_channel,
+                    VoiceWsChannel {
+                        bot: bot_id.clone(),
+                        guild_id: guild_id.clone(),
+                    },
+                );
+                if let Some(bot) = state.bots.get_mut(&bot_id) {
+                    bot.voice_connections.insert(
+                        guild_id.clone(),
+                        BotVoiceConnection {
+                            ws_channel,
+                            ws_open: false,
+                            heartbeat_interval: 0,
+                            connection: VoiceConnection::new(&guild_id, &user_id),
+                        },
+                    );
+                }
+            }
+
+            let Some(bot) = state.bots.get(&bot_id) else {
+                return Ok(());
+            };
+            send_gateway_event(
+                our,
+                bot,
+                GatewaySendEvent::UpdateVoiceState {
+                    guild_id,
+                    channel_id: Some(channel_id),
+                    self_mute,
+                    self_deaf,
+                },
+            )?;
+        }
+        DiscordApiRequest::LeaveVoice {
+            bot: bot_id,
+            guild_id,
+        } => {
+            let Some(bot) = state.bots.get_mut(&bot_id) else {
+                return Ok(());
+            };
+            if let Some(voice) = bot.voice_connections.remove(&guild_id) {
+                state.voice_channels.remove(&voice.ws_channel);
+                if voice.ws_open {
+                    close_ws_connection(our.node.clone(), voice.ws_channel)?;
+                }
+            }
+
+            send_gateway_event(
+                our,
+                bot,
+                GatewaySendEvent::UpdateVoiceState {
+                    guild_id,
+                    channel_id: None,
+                    self_mute: false,
+                    self_deaf: false,
+                },
+            )?;
+        }
+        DiscordApiRequest::VoiceSelectProtocol {
+            bot,
+            guild_id,
+            address,
+            port,
+            mode,
+        } => {
+            let Some(voice) = state
+                .bots
+                .get(&bot)
+                .and_then(|bot| bot.voice_connections.get(&guild_id))
+            else {
+                return Err(anyhow::anyhow!("discord_api: no voice connection"));
+            };
+            let event = voice.connection.select_protocol(&address, port, mode)?;
+            send_voice_event(our, voice, &event)?;
+        }
+        DiscordApiRequest::VoiceSpeaking {
+            bot,
+            guild_id,
+            speaking,
+        } => {
+            let Some(voice) = state
+                .bots
+                .get(&bot)
+                .and_then(|bot| bot.voice_connections.get(&guild_id))
+            else {
+                return Err(anyhow::anyhow!("discord_api: no voice connection"));
+            };
+            let event = voice.connection.speaking(speaking)?;
+            send_voice_event(our, voice, &event)?;
+        }
     }
 
     Ok(())
 }
 
+fn send_gateway_event(our: &Address, bot: &Bot, event: GatewaySendEvent) -> anyhow::Result<()> {
+    let bytes = event.to_json_bytes();
+    record_frame(bot, GatewayFrameDirection::Sent, &bytes);
+    send_ws_client_push(
+        our.node.clone(),
+        bot.ws_client_channel,
+        WsMessageType::Text,
+        LazyLoadBlob { mime: None, bytes },
+    )?;
+    Ok(())
+}
+
+// Plays a recording for DiscordApiRequest::Replay, counting the frames played
+fn replay_recording(
+    our: &Address,
+    source: &Address,
+    bot_id: &BotId,
+    path: &str,
+    frames: &mut usize,
+    state: &State,
+) -> anyhow::Result<()> {
+    if source.node != our.node {
+        return Err(anyhow::anyhow!(
+            "discord_api: Replay is only taken from this node"
+        ));
+    }
+    if !state.recordings.contains(path) {
+        return Err(anyhow::anyhow!(
+            "discord_api: {} isn't a recording made by Record",
+            path
+        ));
+    }
+    let bytes = open_file(path, false)?.read()?;
+    let mut replay = GatewayReplay::from_bytes(&bytes)?;
+    // A bot of its own, so the connected one and its cache are left alone
+    let mut bot = Bot::new(source.clone(), bot_id, u32::MAX);
+    while let Some((_, actions)) = replay.next_frame() {
+        *frames += 1;
+        let Ok(actions) = actions else {
+            continue;
+        };
+        for action in actions {
+            if let GatewayAction::Dispatch(event) = action {
+                handle_gateway_event(our, event, &mut bot)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Appends to the bot's recording, if it has one. Failing to record doesn't stop the bot.
+fn record_frame(bot: &Bot, direction: GatewayFrameDirection, bytes: &[u8]) {
+    let Some(path) = bot.recording.as_ref() else {
+        return;
+    };
+    let line = GatewayFrame::new(direction, bytes, &bot.token).to_line();
+    let result = open_file(path, true).and_then(|mut file| file.append(&line));
+    if let Err(e) = result {
+        print_to_terminal(
+            1,
+            &format!("discord_api: can't record to {}: {:?}", path, e),
+        );
+    }
+}
+
+fn send_voice_event(
+    our: &Address,
+    voice: &BotVoiceConnection,
+    event: &VoiceSendEvent,
+) -> anyhow::Result<()> {
+    send_ws_client_push(
+        our.node.clone(),
+        voice.ws_channel,
+        WsMessageType::Text,
+        LazyLoadBlob {
+            mime: None,
+            bytes: event.to_json_bytes(),
+        },
+    )?;
+    Ok(())
+}
+
+fn send_member_request(
+    our: &Address,
+    bot: &Bot,
+    request: &GuildMembersRequest,
+    nonce: &str,
+) -> anyhow::Result<()> {
+    send_gateway_event(our, bot, request.to_send_event(nonce))
+}
+
+fn http_url<'a>(state: &'a State, bot: &BotId) -> &'a str {
+    state
+        .http_urls
+        .get(&bot.token)
+        .map(String::as_str)
+        .unwrap_or(HTTP_URL)
+}
+
+// Seconds to wait for each page of a Paginate
+const PAGE_TIMEOUT: u64 = 5;
+
+// Sends one page's call and returns the body, if Discord answered with a success
+fn fetch_page(bot: &BotId, call: &HttpApiCall, http_url: &str) -> anyhow::Result<Vec<u8>> {
+    let (http_req, http_body) = outgoing_http_request(&bot_auth(bot), call, http_url);
+    let message = Request::new()
+        .target(("our", "http_client", "distro", "sys"))
+        .body(serde_json::to_vec(&HttpClientAction::Http(http_req))?)
+        .blob_bytes(http_body)
+        .send_and_await_response(PAGE_TIMEOUT)?
+        .map_err(|e| anyhow::anyhow!("discord_api: page request failed: {:?}", e))?;
+
+    match serde_json::from_slice::<Result<HttpResponse, HttpClientError>>(message.body())? {
+        Ok(response) if (200..300).contains(&response.status) => {}
+        Ok(response) => {
+            let body = get_blob().map(|blob| blob.bytes).unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "discord_api: page returned {}: {}",
+                response.status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        Err(e) => return Err(anyhow::anyhow!("discord_api: page request failed: {:?}", e)),
+    }
+
+    // A successful list response always has a body, so the blob is this response's
+    match get_blob() {
+        Some(blob) if !blob.bytes.is_empty() => Ok(blob.bytes),
+        _ => Err(anyhow::anyhow!("discord_api: no data for page")),
+    }
+}
+
+fn bot_auth(bot: &BotId) -> HttpAuth {
+    HttpAuth::Bot(bot.token.clone())
+}
+
+fn outgoing_http_request(
+    auth: &HttpAuth,
+    call: &HttpApiCall,
+    http_url: &str,
+) -> (OutgoingHttpRequest, Vec<u8>) {
+    let (url, method, http_body) = call.to_request_at(http_url);
+    let mut headers = HashMap::new();
+    // The OAuth2 token endpoints take the application's credentials in the body instead
+    let sends_credentials = matches!(call, HttpApiCall::OAuth(call) if call.sends_credentials());
+    if let Some(header) = auth.header().filter(|_| !sends_credentials) {
+        headers.insert("Authorization".to_string(), header);
+    }
+    headers.insert("Content-Type".to_string(), call.content_type());
+    headers.insert(
+        "User-Agent".to_string(),
+        format!("DiscordBot ({}, {})", "https://kinode.network", "1.0"),
+    );
+
+    let http_req = OutgoingHttpRequest {
+        method: method.to_string(),
+        version: None,
+        url: url.to_string(),
+        headers,
+    };
+
+    (http_req, http_body)
+}
+
 fn handle_websocket_client_message(
     our: &Address,
     ws_message: HttpClientRequest,
@@ -209,7 +775,11 @@ fn handle_websocket_client_message(
                 return Ok(());
             };
 
-            let Some(bot_id) = state.channels.get(&channel_id) else {
+            if let Some(voice_channel) = state.voice_channels.get(&channel_id).cloned() {
+                return handle_voice_message(our, voice_channel, &blob.bytes, state);
+            }
+
+            let Some(bot_id) = state.channels.get(&channel_id).cloned() else {
                 // print_to_terminal(0, "discord_api: ws push: no bot_id");
                 return Ok(());
             };
@@ -219,14 +789,14 @@ fn handle_websocket_client_message(
                 return Ok(());
             };
 
-            // Handle Gateway events
-            match parse_gateway_blob(&blob.bytes) {
-                Ok((event, seq)) => {
-                    if let Some(seq) = seq {
-                        bot.heartbeat_sequence = seq;
-                    }
-
-                    handle_gateway_event(our, event, bot)?;
+            // Handle Gateway events. Recordings keep the inflated JSON, not zlib-stream bytes.
+            let Ok(Some(json)) = bot.connection.inflate(&blob.bytes) else {
+                return Ok(()); // Part of a zlib-stream message, or not zlib at all
+            };
+            record_frame(bot, GatewayFrameDirection::Received, &json);
+            match bot.connection.receive_json(&json) {
+                Ok(actions) => {
+                    handle_gateway_actions(our, actions, &bot_id, state)?;
                     // set_state(&serde_json::to_vec(state)?);
                 }
                 Err(_e) => {
@@ -239,7 +809,12 @@ fn handle_websocket_client_message(
         }
         HttpClientRequest::WebSocketClose { channel_id } => {
             print_to_terminal(0, "discord_api: ws close");
-            let Some(bot_id) = state.channels.get(&channel_id) else {
+            if let Some(voice_channel) = state.voice_channels.get(&channel_id).cloned() {
+                return handle_voice_close(our, voice_channel, state);
+            }
+
+            // Only the bot's current websocket, replaced ones have no bot
+            let Some(bot_id) = state.channels.get(&channel_id).cloned() else {
                 // print_to_terminal(0, "discord_api: ws push: no bot_id");
                 return Ok(());
             };
@@ -249,13 +824,12 @@ fn handle_websocket_client_message(
                 return Ok(());
             };
 
-            // Reopen connection if closed, also clear current timers and set_state again
-            bot.gateway_connection_open = false;
-            bot.heartbeat_interval = 0;
-            bot.heartbeat_sequence = 0;
-            bot.session_id = "".to_string();
-
-            connect_gateway(our, &bot.ws_client_channel, state.gateway_url.clone())?;
+            // Reopen the connection, resuming the session if it can be.
+            // http_client doesn't pass on Discord's close code, so a bad token or intents only
+            // fail after MAX_GATEWAY_RECONNECTS closes without a session.
+            record_frame(bot, GatewayFrameDirection::Closed, &[]);
+            let actions = bot.connection.on_close(None);
+            handle_gateway_actions(our, actions, &bot_id, state)?;
             // set_state(&serde_json::to_vec(state)?);
         }
     }
@@ -267,105 +841,188 @@ fn handle_websocket_client_message(
 // Sent when a bot is connected with a DiscordApiRequest::Connect
 fn connect_gateway(
     our: &Address,
-    ws_client_channel: &u32,
-    gateway_url: String,
+    bot_id: &BotId,
+    url: String,
+    state: &mut State,
 ) -> anyhow::Result<()> {
-    open_ws_connection_and_await(
-        our.node.clone(),
-        format!("{}{}", gateway_url, GATEWAY_PARAMS),
-        None,
-        *ws_client_channel,
-    )?;
+    open_gateway(our, bot_id, url, state)?;
 
     Response::new().body(vec![]).send()?;
 
     Ok(())
 }
 
+// Opens the bot's gateway websocket on a new channel and closes the one it replaces, which
+// stops being the bot's. Whatever comes on a channel that isn't a bot's, like the replaced
+// websocket closing late, is ignored.
+fn open_gateway(
+    our: &Address,
+    bot_id: &BotId,
+    url: String,
+    state: &mut State,
+) -> anyhow::Result<()> {
+    let channel = state.gateway_channels_opened % VOICE_WS_CHANNEL_BASE;
+    state.gateway_channels_opened = state.gateway_channels_opened.wrapping_add(1);
+    open_ws_connection_and_await(our.node.clone(), url, None, channel)?;
+
+    let Some(bot) = state.bots.get_mut(bot_id) else {
+        close_ws_connection(our.node.clone(), channel)?;
+        return Ok(()); // No bot to give it to
+    };
+    let replaced = std::mem::replace(&mut bot.ws_client_channel, channel);
+    state.channels.insert(channel, bot_id.clone());
+    if state.channels.remove(&replaced).is_some() {
+        let _ = close_ws_connection(our.node.clone(), replaced);
+    }
+    Ok(())
+}
+
+// Failing to reopen the gateway is taken as the websocket closing, so it's retried after
+// backing off and counts toward MAX_GATEWAY_RECONNECTS
+fn reopen_gateway(
+    our: &Address,
+    bot_id: &BotId,
+    url: String,
+    state: &mut State,
+) -> anyhow::Result<()> {
+    if !state.bots.contains_key(bot_id) {
+        return Ok(()); // Disconnected
+    }
+    if let Err(e) = open_gateway(our, bot_id, url, state) {
+        print_to_terminal(1, &format!("discord_api: can't reconnect: {:?}", e));
+        let Some(bot) = state.bots.get_mut(bot_id) else {
+            return Ok(());
+        };
+        record_frame(bot, GatewayFrameDirection::Closed, &[]);
+        let actions = bot.connection.on_close(None);
+        handle_gateway_actions(our, actions, bot_id, state)?;
+    }
+    Ok(())
+}
+
+fn handle_gateway_actions(
+    our: &Address,
+    actions: Vec<GatewayAction>,
+    bot_id: &BotId,
+    state: &mut State,
+) -> anyhow::Result<()> {
+    for action in actions {
+        let Some(bot) = state.bots.get_mut(bot_id) else {
+            return Ok(()); // Disconnected
+        };
+        match action {
+            GatewayAction::Send(event) => send_gateway_event(our, bot, event)?,
+            GatewayAction::StartHeartbeat(interval) => {
+                // Already ticking after a reconnect
+                let ticking = bot.heartbeat_interval != 0;
+                bot.heartbeat_interval = interval;
+                if !ticking {
+                    discord_heartbeat_tick(interval, BotId::new(bot.token.clone(), bot.intents));
+                }
+            }
+            GatewayAction::Reconnect { url, delay_ms: 0 } => {
+                print_to_terminal(1, "discord_api: reconnecting");
+                reopen_gateway(our, bot_id, url, state)?;
+            }
+            GatewayAction::Reconnect { url, delay_ms } => {
+                print_to_terminal(1, &format!("discord_api: reconnecting in {}ms", delay_ms));
+                set_timer(
+                    delay_ms,
+                    Some(serde_json::to_vec(&GatewayTimer::Reconnect {
+                        bot: bot_id.clone(),
+                        url,
+                    })?),
+                );
+            }
+            GatewayAction::Dispatch(event) => handle_gateway_event(our, event, bot)?,
+            GatewayAction::Fail(closed) => {
+                bot.heartbeat_interval = 0;
+                Request::new()
+                    .target(bot.parent.clone())
+                    .body(
+                        serde_json::json!(GatewayReceiveEvent::GatewayClosed(closed))
+                            .to_string()
+                            .into_bytes(),
+                    )
+                    .send()?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_gateway_event(
     our: &Address,
     event: GatewayReceiveEvent,
     bot: &mut Bot,
 ) -> anyhow::Result<()> {
-    // Handle all events that have to do with the gateway connection
+    // The bot's GatewayConnection has taken the events about the connection itself
     // Forward all other events to the parent process
     match event {
-        GatewayReceiveEvent::Hello(hello) => {
-            send_identify(our, bot, hello.heartbeat_interval)?;
-        }
         GatewayReceiveEvent::Ready(ready) => {
             print_to_terminal(1, &format!("discord_api: READY {:?}", ready));
-            bot.session_id = ready.session_id.clone();
-            bot.gateway_connection_open = true;
-            bot.resume_gateway_url = Some(ready.resume_gateway_url.clone());
 
             Request::new()
                 .target(bot.parent.clone())
                 .body(serde_json::json!(ready).to_string().into_bytes())
                 .send()?;
+            bot.pending_guilds = Some(ready.guilds.iter().map(|g| g.id().to_string()).collect());
+            guild_arrived(bot, None)?;
+            if bot.pending_guilds.is_some() {
+                set_timer(
+                    ALL_GUILDS_READY_TIMEOUT_MS,
+                    Some(serde_json::to_vec(&GuildsTimer::Ready {
+                        bot: BotId::new(bot.token.clone(), bot.intents),
+                        session_id: ready.session_id.clone(),
+                    })?),
+                );
+            }
+            bot.cache.update(GatewayReceiveEvent::Ready(ready));
 
             // set_state(&serde_json::to_vec(&load_state())?);
         }
-        GatewayReceiveEvent::Reconnect => {
-            print_to_terminal(1, &format!("discord_api: RECONNECT"));
-            // If we get a reconnect event, we need to open a WS connection to the resume_gateway_url
-            open_ws_connection_and_await(
-                our.node.clone(),
-                format!(
-                    "{}{}",
-                    bot.resume_gateway_url
-                        .clone()
-                        .unwrap_or(DISCORD_GATEWAY.to_string()),
-                    GATEWAY_PARAMS
-                ),
-                None,
-                bot.ws_client_channel,
-            )?;
-
-            // Immediately send a resume event
-            let send_event = GatewaySendEvent::Resume {
-                token: bot.token.clone(),
-                session_id: bot.session_id.clone(),
-                seq: bot.heartbeat_sequence,
+        // Chunks of our own member requests go to the parent all at once
+        GatewayReceiveEvent::GuildMembersChunk(chunk)
+            if chunk
+                .nonce
+                .as_ref()
+                .and_then(|nonce| bot.member_requests.get(nonce))
+                .is_some_and(|request| request.matches(&chunk)) =>
+        {
+            bot.cache
+                .update(GatewayReceiveEvent::GuildMembersChunk(chunk.clone()));
+            let nonce = chunk.nonce.clone().unwrap_or_default();
+            let complete = match bot.member_requests.get_mut(&nonce) {
+                Some(request) => request.add(chunk),
+                None => false,
             };
-
-            send_ws_client_push(
-                our.node.clone(),
-                bot.ws_client_channel,
-                WsMessageType::Text,
-                LazyLoadBlob {
-                    mime: None,
-                    bytes: send_event.to_json_bytes(),
-                },
-            )?;
-        }
-        GatewayReceiveEvent::InvalidSession(resumable) => {
-            // print_to_terminal(
-            //     0,
-            //     &format!("discord_api: INVALID SESSION, resumable: {:?}", resumable),
-            // );
-
-            if resumable {
-                // If we get a reconnect event, we need to open a WS connection to the resume_gateway_url
-                let send_event = GatewaySendEvent::Resume {
-                    token: bot.token.clone(),
-                    session_id: bot.session_id.clone(),
-                    seq: bot.heartbeat_sequence,
-                };
-
-                send_ws_client_push(
-                    our.node.clone(),
-                    bot.ws_client_channel,
-                    WsMessageType::Text,
-                    LazyLoadBlob {
-                        mime: None,
-                        bytes: send_event.to_json_bytes(),
-                    },
-                )?;
-            } else {
-                send_identify(our, bot, bot.heartbeat_interval)?;
+            if complete {
+                if let Some(request) = bot.member_requests.remove(&nonce) {
+                    let event = GatewayReceiveEvent::GuildMembers(request.finish());
+                    Request::new()
+                        .target(bot.parent.clone())
+                        .body(serde_json::json!(event).to_string().into_bytes())
+                        .send()?;
+                }
             }
         }
+        // Discord dropped one of our member requests, send it again when allowed
+        GatewayReceiveEvent::RateLimited(limited)
+            if limited
+                .meta
+                .nonce
+                .as_ref()
+                .is_some_and(|nonce| bot.member_requests.contains_key(nonce)) =>
+        {
+            set_timer(
+                (limited.retry_after * 1000.0).ceil() as u64,
+                Some(serde_json::to_vec(&MembersTimer::Retry {
+                    bot: BotId::new(bot.token.clone(), bot.intents),
+                    nonce: limited.meta.nonce.unwrap_or_default(),
+                })?),
+            );
+        }
         _ => {
             // print_to_terminal(0, &format!("discord_api: OTHER EVENT: {:?}", event));
             // Pass all the others to the parent process
@@ -373,60 +1030,207 @@ fn handle_gateway_event(
                 .target(bot.parent.clone())
                 .body(serde_json::json!(event).to_string().into_bytes())
                 .send()?;
+            match &event {
+                GatewayReceiveEvent::VoiceStateUpdate(voice_state) => {
+                    if let Some(voice) = voice_state
+                        .guild_id
+                        .as_ref()
+                        .and_then(|guild_id| bot.voice_connections.get_mut(guild_id))
+                    {
+                        if voice.connection.on_voice_state(voice_state) {
+                            open_voice_gateway(our, voice)?;
+                        }
+                    }
+                }
+                GatewayReceiveEvent::VoiceServerUpdate(server) => {
+                    if let Some(voice) = bot.voice_connections.get_mut(&server.guild_id) {
+                        if voice.connection.on_voice_server(server) {
+                            open_voice_gateway(our, voice)?;
+                        }
+                    }
+                }
+                GatewayReceiveEvent::GuildCreate(guild) => guild_arrived(bot, Some(guild.id()))?,
+                // Left, or in an outage, so it won't arrive
+                GatewayReceiveEvent::GuildDelete(guild) => guild_arrived(bot, Some(&guild.id))?,
+                _ => {}
+            }
+            bot.cache.update(event);
         }
     }
 
     Ok(())
 }
 
-fn send_identify(our: &Address, bot: &mut Bot, interval: u64) -> anyhow::Result<()> {
-    let send_event = GatewaySendEvent::Identify {
-        token: bot.token.clone(),
-        intents: bot.intents.clone(),
-        properties: GatewayIdentifyProperties {
-            os: "kinode".to_string(),
-            browser: "kinode".to_string(),
-            device: "kinode".to_string(),
-        },
-        compress: None,
-        large_threshold: None,
-        shard: None,
-        presence: None,
-        guild_subscriptions: None,
+fn open_voice_gateway(our: &Address, voice: &mut BotVoiceConnection) -> anyhow::Result<()> {
+    let Some(url) = voice.connection.gateway_url() else {
+        return Ok(());
     };
+    // The voice server moved, drop the old websocket
+    if voice.ws_open {
+        voice.connection.replace_websocket();
+        close_ws_connection(our.node.clone(), voice.ws_channel)?;
+    }
+    open_ws_connection_and_await(our.node.clone(), url, None, voice.ws_channel)?;
+    voice.ws_open = true;
+    Ok(())
+}
 
-    bot.heartbeat_interval = interval;
-    discord_heartbeat_tick(
-        bot.heartbeat_interval,
-        BotId::new(bot.token.clone(), bot.intents),
-    );
+// Reopens a dropped voice websocket to resume the session, unless the connection is over.
+// http_client doesn't pass on Discord's close code, so only the resume cap ends it here.
+fn handle_voice_close(
+    our: &Address,
+    voice_channel: VoiceWsChannel,
+    state: &mut State,
+) -> anyhow::Result<()> {
+    let Some(bot) = state.bots.get_mut(&voice_channel.bot) else {
+        return Ok(());
+    };
+    let Some(voice) = bot.voice_connections.get_mut(&voice_channel.guild_id) else {
+        return Ok(());
+    };
+    let reopened = match voice.connection.on_close(None) {
+        VoiceClose::Ignore => {
+            voice.ws_open = false;
+            return Ok(());
+        }
+        VoiceClose::Reopen(url) => {
+            open_ws_connection_and_await(our.node.clone(), url, None, voice.ws_channel).is_ok()
+        }
+        VoiceClose::Disconnect => false,
+    };
+    voice.ws_open = reopened;
+    if reopened {
+        return Ok(());
+    }
 
-    send_ws_client_push(
-        our.node.clone(),
-        bot.ws_client_channel,
-        WsMessageType::Text,
-        LazyLoadBlob {
-            mime: None,
-            bytes: GatewaySendEvent::Heartbeat {
-                seq: Some(bot.heartbeat_sequence),
+    state.voice_channels.remove(&voice.ws_channel);
+    bot.voice_connections.remove(&voice_channel.guild_id);
+    let event = GatewayReceiveEvent::Voice(VoiceEvent::Disconnected {
+        guild_id: voice_channel.guild_id,
+    });
+    Request::new()
+        .target(bot.parent.clone())
+        .body(serde_json::json!(event).to_string().into_bytes())
+        .send()?;
+    Ok(())
+}
+
+fn handle_voice_message(
+    our: &Address,
+    voice_channel: VoiceWsChannel,
+    bytes: &[u8],
+    state: &mut State,
+) -> anyhow::Result<()> {
+    let Some(bot) = state.bots.get_mut(&voice_channel.bot) else {
+        return Ok(());
+    };
+    let Some(voice) = bot.voice_connections.get_mut(&voice_channel.guild_id) else {
+        return Ok(());
+    };
+    let Ok((event, seq)) = parse_voice_blob(bytes) else {
+        return Ok(());
+    };
+
+    for action in voice.connection.handle(event, seq) {
+        match action {
+            VoiceAction::Send(event) => send_voice_event(our, voice, &event)?,
+            VoiceAction::Notify(event) => {
+                Request::new()
+                    .target(bot.parent.clone())
+                    .body(
+                        serde_json::json!(GatewayReceiveEvent::Voice(event))
+                            .to_string()
+                            .into_bytes(),
+                    )
+                    .send()?;
             }
-            .to_json_bytes(),
-        },
-    )?;
+            VoiceAction::StartHeartbeat(interval) => {
+                // Already ticking after a resume
+                let ticking = voice.heartbeat_interval != 0;
+                voice.heartbeat_interval = interval;
+                if !ticking {
+                    voice_heartbeat_tick(
+                        interval,
+                        voice_channel.bot.clone(),
+                        voice_channel.guild_id.clone(),
+                    );
+                }
+            }
+        }
+    }
 
-    send_ws_client_push(
-        our.node.clone(),
-        bot.ws_client_channel,
-        WsMessageType::Text,
-        LazyLoadBlob {
-            mime: None,
-            bytes: send_event.to_json_bytes(),
-        },
-    )?;
+    Ok(())
+}
+
+fn handle_voice_timer(our: &Address, timer: VoiceTimer, state: &mut State) -> anyhow::Result<()> {
+    match timer {
+        VoiceTimer::Heartbeat { bot, guild_id } => {
+            let Some(voice) = state
+                .bots
+                .get_mut(&bot)
+                .and_then(|bot| bot.voice_connections.get_mut(&guild_id))
+            else {
+                return Ok(()); // Left the voice channel
+            };
+            if !voice.ws_open {
+                voice.heartbeat_interval = 0;
+                return Ok(());
+            }
+
+            let heartbeat = voice.connection.heartbeat();
+            send_voice_event(our, voice, &heartbeat)?;
+            voice_heartbeat_tick(voice.heartbeat_interval, bot, guild_id);
+        }
+    }
 
     Ok(())
 }
 
+fn voice_heartbeat_tick(interval: u64, bot: BotId, guild_id: String) {
+    set_timer(
+        interval,
+        Some(serde_json::to_vec(&VoiceTimer::Heartbeat { bot, guild_id }).unwrap()),
+    );
+}
+
+// Tells the parent once the last guild from Ready has arrived
+fn guild_arrived(bot: &mut Bot, guild_id: Option<&str>) -> anyhow::Result<()> {
+    let Some(pending) = bot.pending_guilds.as_mut() else {
+        return Ok(());
+    };
+    if let Some(guild_id) = guild_id {
+        pending.remove(guild_id);
+    }
+    if pending.is_empty() {
+        bot.pending_guilds = None;
+        Request::new()
+            .target(bot.parent.clone())
+            .body(
+                serde_json::json!(GatewayReceiveEvent::AllGuildsReady)
+                    .to_string()
+                    .into_bytes(),
+            )
+            .send()?;
+    }
+    Ok(())
+}
+
+// Sends AllGuildsReady without the guilds that never arrived
+fn guilds_timed_out(bot: &mut Bot) -> anyhow::Result<()> {
+    let Some(pending) = bot.pending_guilds.take() else {
+        return Ok(()); // All arrived
+    };
+    print_to_terminal(
+        1,
+        &format!(
+            "discord_api: guilds still unavailable after Ready: {:?}",
+            pending
+        ),
+    );
+    bot.pending_guilds = Some(HashSet::new());
+    guild_arrived(bot, None)
+}
+
 fn maintain_heartbeat(
     our: &Address,
     context: Option<Vec<u8>>,
@@ -438,25 +1242,56 @@ fn maintain_heartbeat(
 
     let heartbeat = serde_json::from_slice::<Heartbeat>(&context)?;
 
-    let Some(bot) = state.bots.get(&heartbeat.bot) else {
+    let Some(bot) = state.bots.get_mut(&heartbeat.bot) else {
         return Ok(()); // Bot does not exist
     };
+    if bot.heartbeat_interval == 0 {
+        return Ok(()); // Stopped
+    }
 
-    if bot.gateway_connection_open {
-        send_ws_client_push(
-            our.node.clone(),
-            bot.ws_client_channel,
-            WsMessageType::Text,
-            LazyLoadBlob {
-                mime: None,
-                bytes: GatewaySendEvent::Heartbeat {
-                    seq: Some(bot.heartbeat_sequence),
-                }
-                .to_json_bytes(),
-            },
-        )?;
+    let actions = bot.connection.heartbeat();
+    discord_heartbeat_tick(bot.heartbeat_interval, heartbeat.bot.clone());
+    handle_gateway_actions(our, actions, &heartbeat.bot, state)?;
+
+    Ok(())
+}
+
+fn handle_members_timer(
+    our: &Address,
+    timer: MembersTimer,
+    state: &mut State,
+) -> anyhow::Result<()> {
+    match timer {
+        MembersTimer::Deadline { bot, nonce } => {
+            let Some(bot) = state.bots.get_mut(&bot) else {
+                return Ok(()); // Bot does not exist
+            };
+            let Some(request) = bot.member_requests.remove(&nonce) else {
+                return Ok(()); // Already complete
+            };
 
-        discord_heartbeat_tick(bot.heartbeat_interval, heartbeat.bot);
+            let error = format!(
+                "timed out after {} seconds",
+                request.request.timeout_seconds()
+            );
+            let event = GatewayReceiveEvent::GuildMembersFailed(request.fail(&error));
+            Request::new()
+                .target(bot.parent.clone())
+                .body(serde_json::json!(event).to_string().into_bytes())
+                .send()?;
+        }
+        MembersTimer::Retry { bot, nonce } => {
+            let Some(bot) = state.bots.get(&bot) else {
+                return Ok(()); // Bot does not exist
+            };
+            let Some(request) = bot.member_requests.get(&nonce) else {
+                return Ok(()); // Already complete or timed out
+            };
+
+            if bot.connection.connected() && !request.started() {
+                send_member_request(our, bot, &request.request, &nonce)?;
+            }
+        }
     }
 
     Ok(())
@@ -469,6 +1304,39 @@ fn discord_heartbeat_tick(interval: u64, bot: BotId) {
     );
 }
 
+// Serves an Interactions Endpoint URL from the calling process.
+// Bind the path once, public and not local-only since Discord calls it from outside.
+pub fn bind_interactions_endpoint(path: &str) -> anyhow::Result<()> {
+    bind_http_path(path, false, false)
+}
+
+// Handles a request from http_server on the bound path: verifies it, answers PING,
+// passes other interactions to `handler` and sends the HTTP response.
+pub fn handle_interactions_http_request<F>(
+    verifier: &InteractionVerifier,
+    body: &[u8],
+    handler: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(Interaction) -> anyhow::Result<InteractionResponse>,
+{
+    let HttpServerRequest::Http(request) = serde_json::from_slice::<HttpServerRequest>(body)?
+    else {
+        return Ok(()); // Not an http request, e.g. a websocket message
+    };
+    let request_body = get_blob().map(|blob| blob.bytes).unwrap_or_default();
+
+    let reply = verifier.handle(&request.headers, &request_body, handler);
+    send_response(
+        StatusCode::from_u16(reply.status)?,
+        Some(HashMap::from([(
+            "Content-Type".to_string(),
+            reply.content_type,
+        )])),
+        reply.body,
+    )
+}
+
 pub fn load_state() -> State {
     match get_state() {
         Some(state) => match serde_json::from_slice::<State>(&state) {
@@ -477,12 +1345,22 @@ pub fn load_state() -> State {
                 gateway_url: DISCORD_GATEWAY.to_string(),
                 bots: HashMap::new(),
                 channels: HashMap::new(),
+                voice_channels: HashMap::new(),
+                voice_channels_opened: 0,
+                gateway_channels_opened: 0,
+                recordings: HashSet::new(),
+                http_urls: HashMap::new(),
             },
         },
         None => State {
             gateway_url: DISCORD_GATEWAY.to_string(),
             bots: HashMap::new(),
             channels: HashMap::new(),
+            voice_channels: HashMap::new(),
+            voice_channels_opened: 0,
+            gateway_channels_opened: 0,
+            recordings: HashSet::new(),
+            http_urls: HashMap::new(),
         },
     }
 }
@@ -5,6 +5,75 @@ use serde::{Deserialize, Serialize};
 pub const DISCORD_GATEWAY: &str = "wss://gateway.discord.gg/?v=9&encoding=json";
 pub const GATEWAY_PARAMS: &str = "?v=9&encoding=json";
 pub const HTTP_URL: &str = "https://discord.com/api/v9";
+pub const CDN_URL: &str = "https://cdn.discordapp.com";
+
+// Channel flags
+pub const CHANNEL_FLAG_PINNED: u64 = 1 << 1; // Thread pinned to the top of its forum or media channel
+pub const CHANNEL_FLAG_REQUIRE_TAG: u64 = 1 << 4; // Forum or media channel requires a tag on new posts
+pub const CHANNEL_FLAG_HIDE_MEDIA_DOWNLOAD_OPTIONS: u64 = 1 << 15; // Media channel only
+
+// Message flags
+pub const MESSAGE_FLAG_SUPPRESS_EMBEDS: u32 = 1 << 2;
+pub const MESSAGE_FLAG_EPHEMERAL: u32 = 1 << 6; // Only visible to the user who invoked the interaction
+pub const MESSAGE_FLAG_SUPPRESS_NOTIFICATIONS: u32 = 1 << 12;
+
+// Permission bits, the ones the cache needs to work out channel visibility
+pub const PERMISSION_ADMINISTRATOR: u64 = 1 << 3;
+pub const PERMISSION_VIEW_CHANNEL: u64 = 1 << 10;
+
+// Moderation limits
+pub const MAX_TIMEOUT_SECONDS: u64 = 28 * 24 * 60 * 60; // communication_disabled_until
+pub const MAX_DELETE_MESSAGE_SECONDS: u32 = 7 * 24 * 60 * 60; // Ban and bulk-ban
+pub const MAX_BULK_BAN_USERS: usize = 200;
+
+// Defines an enum for an integer discriminant sent by Discord.
+// It (de)serializes as the bare integer, and values this crate doesn't know yet
+// become Unknown(value) instead of failing the whole payload.
+macro_rules! discord_enum {
+    ($name:ident { $($variant:ident = $value:literal,)* }) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(u32),
+        }
+
+        impl $name {
+            pub fn as_u32(&self) -> u32 {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => *value,
+                }
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(value: u32) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    value => $name::Unknown(value),
+                }
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(value: $name) -> Self {
+                value.as_u32()
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_u32(self.as_u32())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                u32::deserialize(deserializer).map($name::from)
+            }
+        }
+    };
+}
 
 // Gateway types
 
@@ -293,7 +362,10 @@ pub enum GatewayEventType {
     MessageReactionRemove,
     MessageReactionRemoveAll,
     MessageReactionRemoveEmoji,
+    MessagePollVoteAdd,
+    MessagePollVoteRemove,
     PresenceUpdate,
+    RateLimited,
     StageInstanceCreate,
     StageInstanceUpdate,
     StageInstanceDelete,
@@ -308,13 +380,14 @@ impl GatewayEventType {
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_uppercase().as_str() {
             "READY" => Some(Self::Ready),
+            "RESUMED" => Some(Self::Resumed),
             "APPLICATION_COMMAND_PERMISSIONS_UPDATE" => {
                 Some(Self::ApplicationCommandPermissionsUpdate)
             }
-            "AUTOMODERATION_RULE_CREATE" => Some(Self::AutoModerationRuleCreate),
-            "AUTOMODERATION_RULE_UPDATE" => Some(Self::AutoModerationRuleUpdate),
-            "AUTOMODERATION_RULE_DELETE" => Some(Self::AutoModerationRuleDelete),
-            "AUTOMODERATION_ACTION_EXECUTION" => Some(Self::AutoModerationActionExecution),
+            "AUTO_MODERATION_RULE_CREATE" => Some(Self::AutoModerationRuleCreate),
+            "AUTO_MODERATION_RULE_UPDATE" => Some(Self::AutoModerationRuleUpdate),
+            "AUTO_MODERATION_RULE_DELETE" => Some(Self::AutoModerationRuleDelete),
+            "AUTO_MODERATION_ACTION_EXECUTION" => Some(Self::AutoModerationActionExecution),
             "CHANNEL_CREATE" => Some(Self::ChannelCreate),
             "CHANNEL_UPDATE" => Some(Self::ChannelUpdate),
             "CHANNEL_DELETE" => Some(Self::ChannelDelete),
@@ -363,7 +436,10 @@ impl GatewayEventType {
             "MESSAGE_REACTION_REMOVE" => Some(Self::MessageReactionRemove),
             "MESSAGE_REACTION_REMOVE_ALL" => Some(Self::MessageReactionRemoveAll),
             "MESSAGE_REACTION_REMOVE_EMOJI" => Some(Self::MessageReactionRemoveEmoji),
+            "MESSAGE_POLL_VOTE_ADD" => Some(Self::MessagePollVoteAdd),
+            "MESSAGE_POLL_VOTE_REMOVE" => Some(Self::MessagePollVoteRemove),
             "PRESENCE_UPDATE" => Some(Self::PresenceUpdate),
+            "RATE_LIMITED" => Some(Self::RateLimited),
             "STAGE_INSTANCE_CREATE" => Some(Self::StageInstanceCreate),
             "STAGE_INSTANCE_UPDATE" => Some(Self::StageInstanceUpdate),
             "STAGE_INSTANCE_DELETE" => Some(Self::StageInstanceDelete),
@@ -377,7 +453,7 @@ impl GatewayEventType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub id: String,
     pub username: String,
@@ -393,6 +469,26 @@ pub struct User {
     pub premium_type: Option<u32>,
     pub public_flags: Option<u64>,
     pub global_name: Option<String>,
+    pub banner: Option<String>,
+    pub accent_color: Option<u32>,
+    pub avatar_decoration_data: Option<AvatarDecorationData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AvatarDecorationData {
+    pub asset: String,
+    pub sku_id: String,
+}
+
+// The user in a presence, only id is guaranteed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartialUser {
+    pub id: String,
+    pub username: Option<String>,
+    pub discriminator: Option<String>,
+    pub global_name: Option<String>,
+    pub avatar: Option<String>,
+    pub bot: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -421,6 +517,31 @@ pub struct Application {
     pub slug: Option<String>,
     pub cover_image: Option<String>,
     pub flags: Option<u64>,
+    pub bot: Option<User>,
+    pub approximate_guild_count: Option<u64>,
+    pub approximate_user_install_count: Option<u64>,
+    pub redirect_uris: Option<Vec<String>>,
+    pub interactions_endpoint_url: Option<String>,
+    pub role_connections_verification_url: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub install_params: Option<InstallParams>,
+    pub custom_install_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InstallParams {
+    pub scopes: Vec<String>,
+    pub permissions: String,
+}
+
+// The application of a bot integration
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IntegrationApplication {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub description: String,
+    pub bot: Option<User>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -457,21 +578,38 @@ pub struct Team {
     pub owner_user_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// The boolean tags are sent as `"premium_subscriber": null` when true and left out when false
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RoleTags {
     pub bot_id: Option<String>,
     pub integration_id: Option<String>,
     pub subscription_listing_id: Option<String>,
-    pub premium_subscriber: Option<bool>, // will need to figure out how to handle
-    pub available_for_purchase: Option<bool>, // will need to figure out how to handle
-    pub guild_connections: Option<bool>,  // will need to figure out how to handle
+    #[serde(default, with = "null_flag", skip_serializing_if = "is_false")]
+    pub premium_subscriber: bool, // The guild's Booster role
+    #[serde(default, with = "null_flag", skip_serializing_if = "is_false")]
+    pub available_for_purchase: bool,
+    #[serde(default, with = "null_flag", skip_serializing_if = "is_false")]
+    pub guild_connections: bool, // The guild's linked role
+}
+
+// A field whose presence is the value, as in RoleTags
+mod null_flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(_: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_none()
+    }
 
-                                          // premium_subscriber?    null    whether this is the guild's Booster role
-                                          // available_for_purchase?    null    whether this role is available for purchase
-                                          // guild_connections?    null    whether this role is a guild's linked role
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        Option::<serde::de::IgnoredAny>::deserialize(deserializer).map(|_| true)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Role {
     pub id: String,
     pub name: String,
@@ -491,15 +629,21 @@ pub struct Role {
 pub struct Emoji {
     pub id: Option<String>,
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub require_colons: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub managed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub animated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub available: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GuildMember {
     pub user: Option<User>,
     pub nick: Option<String>,
@@ -516,11 +660,11 @@ pub struct GuildMember {
     pub communication_disabled_until: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Channel {
     pub id: String,
     #[serde(rename = "type")]
-    pub channel_type: u32,
+    pub channel_type: ChannelType,
     pub guild_id: Option<String>,
     pub position: Option<u64>,
     pub permission_overwrites: Option<Vec<PermissionOverwrite>>,
@@ -545,38 +689,103 @@ pub struct Channel {
     pub member: Option<ThreadMember>,
     pub default_auto_archive_duration: Option<u64>,
     pub permissions: Option<String>,
+    pub flags: Option<u64>,
+    pub total_message_sent: Option<u64>,
+    pub newly_created: Option<bool>, // Only on THREAD_CREATE
+
+    // Forum and media channels
+    pub available_tags: Option<Vec<ForumTag>>,
+    pub applied_tags: Option<Vec<String>>, // On threads in a forum or media channel
+    pub default_reaction_emoji: Option<DefaultReaction>,
+    pub default_thread_rate_limit_per_user: Option<u64>,
+    pub default_sort_order: Option<SortOrderType>,
+    pub default_forum_layout: Option<ForumLayoutType>,
+}
+
+// How posts in a forum or media channel are sorted
+discord_enum!(SortOrderType {
+    LatestActivity = 0,
+    CreationDate = 1,
+});
+
+discord_enum!(ForumLayoutType {
+    NotSet = 0,
+    ListView = 1,
+    GalleryView = 2,
+});
+
+discord_enum!(ChannelType {
+    GuildText = 0,
+    Dm = 1,
+    GuildVoice = 2,
+    GroupDm = 3,
+    GuildCategory = 4,
+    GuildAnnouncement = 5,
+    AnnouncementThread = 10,
+    PublicThread = 11,
+    PrivateThread = 12,
+    GuildStageVoice = 13,
+    GuildDirectory = 14,
+    GuildForum = 15,
+    GuildMedia = 16,
+});
+
+discord_enum!(OverwriteType {
+    Role = 0,
+    Member = 1,
+});
+
+// A tag that can be applied to threads in a forum or media channel.
+// id is None when creating a new tag through a channel update.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForumTag {
+    pub id: Option<String>,
+    pub name: String,
+    pub moderated: bool,
+    pub emoji_id: Option<String>,
+    pub emoji_name: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DefaultReaction {
+    pub emoji_id: Option<String>,
+    pub emoji_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PermissionOverwrite {
     pub id: String,
     #[serde(rename = "type")]
-    pub overwrite_type: u32,
-    pub allow: Option<u32>,
-    pub deny: Option<u32>,
+    pub overwrite_type: OverwriteType,
+    pub allow: Option<String>, // Permission bit set as a string
+    pub deny: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ThreadMetadata {
     pub archived: bool,
     pub auto_archive_duration: u64,
     pub archive_timestamp: String,
     pub locked: Option<bool>,
+    pub invitable: Option<bool>,          // Private threads only
+    pub create_timestamp: Option<String>, // Only for threads created after 2022-01-09
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ThreadMember {
-    pub id: String,
-    pub user_id: String,
+    // id and user_id are left out for the members in GUILD_CREATE
+    pub id: Option<String>,
+    pub user_id: Option<String>,
     pub join_timestamp: String,
     pub flags: u32,
     pub guild_id: Option<String>,
+    pub member: Option<GuildMember>, // With with_member=true, and in THREAD_MEMBERS_UPDATE
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PresenceUpdate {
-    pub user: User,
-    pub guild_id: String,
+    pub user: PartialUser,
+    pub guild_id: Option<String>, // Left out in GUILD_CREATE and member chunks
     pub status: String,
     pub activities: Option<Vec<Activity>>,
     pub client_status: ClientStatus,
@@ -586,7 +795,7 @@ pub struct PresenceUpdate {
     pub guild_member: Option<GuildMember>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Activity {
     pub name: String,
     #[serde(rename = "type")]
@@ -605,26 +814,26 @@ pub struct Activity {
     pub flags: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActivityTimestamps {
     pub start: Option<u64>,
     pub end: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActivityEmoji {
     pub name: String,
-    pub id: Option<u64>,
+    pub id: Option<String>,
     pub animated: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActivityParty {
     pub id: Option<String>,
     pub size: Option<[u64; 2]>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActivityAssets {
     pub large_image: Option<String>,
     pub large_text: Option<String>,
@@ -632,14 +841,15 @@ pub struct ActivityAssets {
     pub small_text: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActivitySecrets {
     pub join: Option<String>,
     pub spectate: Option<String>,
+    #[serde(rename = "match")]
     pub match_: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ClientStatus {
     pub desktop: Option<String>,
     pub mobile: Option<String>,
@@ -661,6 +871,44 @@ pub struct WelcomeScreenChannel {
     pub emoji_name: Option<String>,
 }
 
+discord_enum!(VerificationLevel {
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    VeryHigh = 4,
+});
+
+discord_enum!(DefaultMessageNotificationLevel {
+    AllMessages = 0,
+    OnlyMentions = 1,
+});
+
+discord_enum!(ExplicitContentFilterLevel {
+    Disabled = 0,
+    MembersWithoutRoles = 1,
+    AllMembers = 2,
+});
+
+discord_enum!(MfaLevel {
+    None = 0,
+    Elevated = 1,
+});
+
+discord_enum!(PremiumTier {
+    None = 0,
+    Tier1 = 1,
+    Tier2 = 2,
+    Tier3 = 3,
+});
+
+discord_enum!(NsfwLevel {
+    Default = 0,
+    Explicit = 1,
+    Safe = 2,
+    AgeRestricted = 3,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Guild {
     pub id: String,
@@ -671,19 +919,20 @@ pub struct Guild {
     pub discovery_splash: Option<String>,
     pub owner: Option<bool>,
     pub owner_id: String,
-    pub permissions: Option<u64>,
-    pub region: String,
+    pub permissions: Option<String>,
+    pub region: Option<String>, // Deprecated, null or left out
     pub afk_channel_id: Option<String>,
     pub afk_timeout: u64,
     pub widget_enabled: Option<bool>,
     pub widget_channel_id: Option<String>,
-    pub verification_level: u32,
-    pub default_message_notifications: u32,
-    pub explicit_content_filter: u32,
+    pub verification_level: VerificationLevel,
+    pub default_message_notifications: DefaultMessageNotificationLevel,
+    pub explicit_content_filter: ExplicitContentFilterLevel,
     pub roles: Vec<Role>,
     pub emojis: Vec<Emoji>,
+    pub stickers: Option<Vec<Sticker>>,
     pub features: Vec<String>,
-    pub mfa_level: u32,
+    pub mfa_level: MfaLevel,
     pub application_id: Option<String>,
     pub system_channel_id: Option<String>,
     pub system_channel_flags: u32,
@@ -693,15 +942,18 @@ pub struct Guild {
     pub vanity_url_code: Option<String>,
     pub description: Option<String>,
     pub banner: Option<String>,
-    pub premium_tier: u32,
+    pub premium_tier: PremiumTier,
     pub premium_subscription_count: Option<u64>,
     pub preferred_locale: String,
     pub public_updates_channel_id: Option<String>,
     pub max_video_channel_users: Option<u64>,
+    pub max_stage_video_channel_users: Option<u64>,
+    pub safety_alerts_channel_id: Option<String>,
+    pub premium_progress_bar_enabled: Option<bool>,
     pub approximate_member_count: Option<u64>,
     pub approximate_presence_count: Option<u64>,
     pub welcome_screen: Option<WelcomeScreen>,
-    pub nsfw_level: u32,
+    pub nsfw_level: NsfwLevel,
 
     // May not be there on GuildCreate
     pub joined_at: Option<String>,
@@ -720,9 +972,57 @@ pub struct Guild {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UnavailableGuild {
     pub id: String,
+    // Left out of GUILD_DELETE when the bot was removed from the guild, rather than it going
+    // down in an outage
+    #[serde(default, skip_serializing_if = "is_false")]
     pub unavailable: bool,
 }
 
+impl UnavailableGuild {
+    // In a GUILD_DELETE, whether the bot left or was removed, so the guild won't come back
+    pub fn removed(&self) -> bool {
+        !self.unavailable
+    }
+}
+
+// A guild in READY or GUILD_CREATE, which is unavailable until it has loaded or during an outage
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum GuildCreatePayload {
+    Available(Box<Guild>),
+    Unavailable(UnavailableGuild),
+}
+
+impl GuildCreatePayload {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Available(guild) => &guild.id,
+            Self::Unavailable(guild) => &guild.id,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        matches!(self, Self::Available(_))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Ban {
+    pub reason: Option<String>,
+    pub user: User,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkBanResponse {
+    pub banned_users: Vec<String>,
+    pub failed_users: Vec<String>, // Includes users that were already banned
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PruneCount {
+    pub pruned: Option<u64>, // null when compute_prune_count is false
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApplicationCommandPermissions {
     pub id: String,
@@ -731,22 +1031,86 @@ pub struct ApplicationCommandPermissions {
     pub permission: bool,
 }
 
+discord_enum!(AutoModerationEventType {
+    MessageSend = 1,
+    MemberUpdate = 2,
+});
+
+discord_enum!(AutoModerationTriggerType {
+    Keyword = 1,
+    Spam = 3,
+    KeywordPreset = 4,
+    MentionSpam = 5,
+    MemberProfile = 6,
+});
+
+discord_enum!(AutoModerationKeywordPresetType {
+    Profanity = 1,
+    SexualContent = 2,
+    Slurs = 3,
+});
+
+discord_enum!(AutoModerationActionType {
+    BlockMessage = 1,
+    SendAlertMessage = 2,
+    Timeout = 3,
+    BlockMemberInteraction = 4,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AutoModerationRule {
     pub id: String,
+    pub guild_id: String,
     pub name: String,
-    pub enabled: bool,
+    pub creator_id: String,
+    pub event_type: AutoModerationEventType,
+    pub trigger_type: AutoModerationTriggerType,
+    pub trigger_metadata: AutoModerationTriggerMetadata,
     pub actions: Vec<AutoModerationAction>,
-    pub conditions: Vec<AutoModerationCondition>,
+    pub enabled: bool,
+    pub exempt_roles: Vec<String>,
+    pub exempt_channels: Vec<String>,
+    // Not sent by Discord, kept for older payloads
+    pub conditions: Option<Vec<AutoModerationCondition>>,
+}
+
+// Which fields apply depends on the rule's trigger_type
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AutoModerationTriggerMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword_filter: Option<Vec<String>>, // KEYWORD, MEMBER_PROFILE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex_patterns: Option<Vec<String>>, // KEYWORD, MEMBER_PROFILE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presets: Option<Vec<AutoModerationKeywordPresetType>>, // KEYWORD_PRESET
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_list: Option<Vec<String>>, // KEYWORD, KEYWORD_PRESET, MEMBER_PROFILE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mention_total_limit: Option<u32>, // MENTION_SPAM, max 50
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mention_raid_protection_enabled: Option<bool>, // MENTION_SPAM
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AutoModerationAction {
     #[serde(rename = "type")]
-    pub action_type: u32,
+    pub action_type: AutoModerationActionType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AutoModerationActionMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AutoModerationActionMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>, // SEND_ALERT_MESSAGE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u64>, // TIMEOUT, max 2419200 (4 weeks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_message: Option<String>, // BLOCK_MESSAGE, max 150 characters
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AutoModerationCondition {
     #[serde(rename = "type")]
@@ -765,25 +1129,15 @@ pub struct Hello {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Ready {
-    // _trace: Vec<serde_json::Value>,
     pub application: PartialApplication,
     pub v: u32,
     pub user: User,
-    pub guilds: Vec<serde_json::Value>, // Can be either a Guild or UnavailableGuild
+    pub guilds: Vec<GuildCreatePayload>, // Unavailable, each followed by a GUILD_CREATE
     pub resume_gateway_url: String,
     pub session_id: String,
     pub shard: Option<[u64; 2]>,
-
-    // Fields that shouldn't be here but are
-    pub auth: Option<serde_json::Value>,
-    pub current_location: Option<Vec<String>>,
-    pub geo_ordered_rtc_regions: Option<Vec<String>>,
-    pub guild_join_requests: Option<Vec<String>>,
-    pub presences: Option<Vec<PresenceUpdate>>,
-    pub relationships: Option<Vec<String>>,
-    pub private_channels: Option<Vec<String>>,
-    pub session_type: Option<String>,
-    pub user_settings: Option<serde_json::Value>,
+    #[serde(rename = "_trace")]
+    pub trace: Option<Vec<String>>, // Debug info about the gateway servers
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApplicationCommandPermissionsUpdate {
@@ -793,23 +1147,10 @@ pub struct ApplicationCommandPermissionsUpdate {
     pub permissions: Vec<ApplicationCommandPermissions>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct AutoModerationRuleCreate {
-    pub guild_id: String,
-    pub rule: AutoModerationRule,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct AutoModerationRuleUpdate {
-    pub guild_id: String,
-    pub rule: AutoModerationRule,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct AutoModerationRuleDelete {
-    pub guild_id: String,
-    pub rule_id: String,
-}
+// The rule events carry the whole rule
+pub type AutoModerationRuleCreate = AutoModerationRule;
+pub type AutoModerationRuleUpdate = AutoModerationRule;
+pub type AutoModerationRuleDelete = AutoModerationRule;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AutoModerationActionExecution {
@@ -818,7 +1159,7 @@ pub struct AutoModerationActionExecution {
     pub action: AutoModerationAction,
     pub user_id: String,
     pub reason: Option<String>,
-    pub rule_trigger_type: u32,
+    pub rule_trigger_type: AutoModerationTriggerType,
     pub channel_id: Option<String>,
     pub message_id: Option<String>,
     pub alert_system_message_id: Option<String>,
@@ -840,7 +1181,7 @@ pub struct ThreadDelete {
     pub guild_id: Option<String>,
     pub parent_id: Option<String>,
     #[serde(rename = "type")]
-    pub channel_type: u32,
+    pub channel_type: ChannelType,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -865,19 +1206,84 @@ pub struct Entitlement {
     pub id: String,
     pub sku_id: String,
     pub application_id: String,
+    pub user_id: Option<String>,
     #[serde(rename = "type")]
-    pub entitlement_type: u32,
+    pub entitlement_type: EntitlementType,
     pub deleted: bool,
-    pub starts_at: Option<String>,
+    pub starts_at: Option<String>, // Not present on test entitlements
     pub ends_at: Option<String>,
-    pub guild_id: String,
+    pub guild_id: Option<String>,
+    pub consumed: Option<bool>, // Only for consumable SKUs
+}
+
+discord_enum!(EntitlementType {
+    Purchase = 1,
+    PremiumSubscription = 2,
+    DeveloperGift = 3,
+    TestModePurchase = 4,
+    FreePurchase = 5,
+    UserGift = 6,
+    PremiumPurchase = 7,
+    ApplicationSubscription = 8,
+});
+
+// Who a test entitlement is for
+discord_enum!(EntitlementOwnerType {
+    Guild = 1,
+    User = 2,
+});
+
+impl Entitlement {
+    // Whether this entitlement currently grants access to the given SKU.
+    // Does not check ends_at, Discord sends ENTITLEMENT_UPDATE/DELETE when it lapses.
+    pub fn grants(&self, sku_id: &str) -> bool {
+        self.sku_id == sku_id && !self.deleted && !self.consumed.unwrap_or(false)
+    }
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Sku {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub sku_type: SkuType,
+    pub application_id: String,
+    pub name: String,
+    pub slug: String,
+    pub flags: u64,
+}
+
+discord_enum!(SkuType {
+    Durable = 2,
+    Consumable = 3,
+    Subscription = 5,
+    SubscriptionGroup = 6, // Generated by Discord for each subscription SKU
+});
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Subscription {
+    pub id: String,
+    pub user_id: String,
+    pub sku_ids: Vec<String>,
+    pub entitlement_ids: Vec<String>,
+    pub renewal_sku_ids: Option<Vec<String>>,
+    pub current_period_start: String,
+    pub current_period_end: String,
+    pub status: SubscriptionStatus,
+    pub canceled_at: Option<String>,
+    pub country: Option<String>,
+}
+
+discord_enum!(SubscriptionStatus {
+    Active = 0,
+    Ending = 1, // Won't renew
+    Inactive = 2,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuditLogChange {
     pub key: String,
-    pub new_value: Option<String>,
-    pub old_value: Option<String>,
+    pub new_value: Option<serde_json::Value>, // Any JSON type, depending on key
+    pub old_value: Option<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -894,17 +1300,146 @@ pub struct OptionalAuditEntryInfo {
     pub role_name: Option<String>,
 }
 
+discord_enum!(AuditLogEvent {
+    GuildUpdate = 1,
+    ChannelCreate = 10,
+    ChannelUpdate = 11,
+    ChannelDelete = 12,
+    ChannelOverwriteCreate = 13,
+    ChannelOverwriteUpdate = 14,
+    ChannelOverwriteDelete = 15,
+    MemberKick = 20,
+    MemberPrune = 21,
+    MemberBanAdd = 22,
+    MemberBanRemove = 23,
+    MemberUpdate = 24,
+    MemberRoleUpdate = 25,
+    MemberMove = 26,
+    MemberDisconnect = 27,
+    BotAdd = 28,
+    RoleCreate = 30,
+    RoleUpdate = 31,
+    RoleDelete = 32,
+    InviteCreate = 40,
+    InviteUpdate = 41,
+    InviteDelete = 42,
+    WebhookCreate = 50,
+    WebhookUpdate = 51,
+    WebhookDelete = 52,
+    EmojiCreate = 60,
+    EmojiUpdate = 61,
+    EmojiDelete = 62,
+    MessageDelete = 72,
+    MessageBulkDelete = 73,
+    MessagePin = 74,
+    MessageUnpin = 75,
+    IntegrationCreate = 80,
+    IntegrationUpdate = 81,
+    IntegrationDelete = 82,
+    StageInstanceCreate = 83,
+    StageInstanceUpdate = 84,
+    StageInstanceDelete = 85,
+    StickerCreate = 90,
+    StickerUpdate = 91,
+    StickerDelete = 92,
+    GuildScheduledEventCreate = 100,
+    GuildScheduledEventUpdate = 101,
+    GuildScheduledEventDelete = 102,
+    ThreadCreate = 110,
+    ThreadUpdate = 111,
+    ThreadDelete = 112,
+    ApplicationCommandPermissionUpdate = 121,
+    SoundboardSoundCreate = 130,
+    SoundboardSoundUpdate = 131,
+    SoundboardSoundDelete = 132,
+    AutoModerationRuleCreate = 140,
+    AutoModerationRuleUpdate = 141,
+    AutoModerationRuleDelete = 142,
+    AutoModerationBlockMessage = 143,
+    AutoModerationFlagToChannel = 144,
+    AutoModerationUserCommunicationDisabled = 145,
+    CreatorMonetizationRequestCreated = 150,
+    CreatorMonetizationTermsAccepted = 151,
+    OnboardingPromptCreate = 163,
+    OnboardingPromptUpdate = 164,
+    OnboardingPromptDelete = 165,
+    OnboardingCreate = 166,
+    OnboardingUpdate = 167,
+    HomeSettingsCreate = 190,
+    HomeSettingsUpdate = 191,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuditLogEntry {
     pub id: String,
+    pub guild_id: Option<String>, // Only in GUILD_AUDIT_LOG_ENTRY_CREATE
     pub target_id: Option<String>,
     pub changes: Option<Vec<AuditLogChange>>,
     pub user_id: Option<String>,
-    pub action_type: u32,
+    pub action_type: AuditLogEvent,
     pub options: Option<OptionalAuditEntryInfo>,
     pub reason: Option<String>,
 }
 
+// The response to HttpApiCall::AuditLog, with what the entries refer to
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuditLog {
+    pub application_commands: Vec<ApplicationCommand>,
+    pub audit_log_entries: Vec<AuditLogEntry>,
+    pub auto_moderation_rules: Vec<AutoModerationRule>,
+    pub guild_scheduled_events: Vec<GuildScheduledEvent>,
+    pub integrations: Vec<PartialIntegration>,
+    pub threads: Vec<Channel>,
+    pub users: Vec<User>,
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PartialIntegration {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub integration_type: String,
+    pub account: Option<Account>,
+    pub application_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Webhook {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub webhook_type: WebhookType,
+    pub guild_id: Option<String>,
+    pub channel_id: Option<String>,
+    pub user: Option<User>,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    pub token: Option<String>,
+    pub application_id: Option<String>,
+    pub source_guild: Option<WebhookSourceGuild>,
+    pub source_channel: Option<WebhookSourceChannel>,
+    pub url: Option<String>,
+}
+
+discord_enum!(WebhookType {
+    Incoming = 1,
+    ChannelFollower = 2, // Posts an announcement channel's messages into another channel
+    Application = 3,     // Used with interactions
+});
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookSourceGuild {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookSourceChannel {
+    pub id: String,
+    pub name: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GuildBanAdd {
     pub guild_id: String,
@@ -928,13 +1463,31 @@ pub struct Sticker {
     pub id: String,
     pub pack_id: Option<String>,
     pub name: String,
-    pub description: String,
+    pub description: Option<String>,
     pub tags: Option<String>,
-    pub asset: String,
+    pub asset: Option<String>, // Deprecated, no longer sent
     pub preview_asset: Option<String>,
-    pub format_type: u32,
+    #[serde(rename = "type")]
+    pub sticker_type: Option<StickerType>,
+    pub format_type: StickerFormatType,
+    pub available: Option<bool>,
+    pub guild_id: Option<String>,
+    pub user: Option<User>,
+    pub sort_value: Option<u64>,
 }
 
+discord_enum!(StickerType {
+    Standard = 1, // Official, in a pack
+    Guild = 2,
+});
+
+discord_enum!(StickerFormatType {
+    Png = 1,
+    Apng = 2,
+    Lottie = 3,
+    Gif = 4,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GuildStickersUpdate {
     pub guild_id: String,
@@ -965,9 +1518,10 @@ pub struct GuildMemberUpdate {
     pub mute: Option<bool>,
     pub pending: Option<bool>,
     pub communication_disabled_until: Option<String>,
+    pub flags: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GuildMembersChunk {
     pub guild_id: String,
     pub members: Vec<GuildMember>,
@@ -1011,9 +1565,9 @@ pub struct GuildScheduledEvent {
     pub description: Option<String>,
     pub scheduled_start_time: String,
     pub scheduled_end_time: Option<String>,
-    pub privacy_level: u32,
-    pub status: u32,
-    pub entity_type: u32,
+    pub privacy_level: PrivacyLevel,
+    pub status: GuildScheduledEventStatus,
+    pub entity_type: GuildScheduledEventEntityType,
     pub entity_id: Option<String>,
     pub entity_metadata: Option<EntityMetadata>,
     pub creator: Option<User>,
@@ -1021,6 +1575,25 @@ pub struct GuildScheduledEvent {
     pub image: Option<String>,
 }
 
+// Of scheduled events and stage instances
+discord_enum!(PrivacyLevel {
+    Public = 1, // Stage instances only, deprecated
+    GuildOnly = 2,
+});
+
+discord_enum!(GuildScheduledEventStatus {
+    Scheduled = 1,
+    Active = 2,
+    Completed = 3,
+    Canceled = 4,
+});
+
+discord_enum!(GuildScheduledEventEntityType {
+    StageInstance = 1,
+    Voice = 2,
+    External = 3,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GuildScheduledEventUser {
     pub guild_scheduled_event_id: String,
@@ -1051,7 +1624,7 @@ pub struct Integration {
     pub synced_at: Option<String>,
     pub subscriber_count: Option<u64>,
     pub revoked: Option<bool>,
-    pub application: Option<Application>,
+    pub application: Option<IntegrationApplication>,
     pub scopes: Option<Vec<String>>,
     pub guild_id: Option<String>,
 }
@@ -1063,7 +1636,7 @@ pub struct IntegrationDelete {
     pub application_id: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Attachment {
     pub id: String,
     pub filename: String,
@@ -1089,25 +1662,39 @@ pub struct MessageActivity {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MessageReference {
+    #[serde(rename = "type")]
+    pub reference_type: Option<MessageReferenceType>,
     pub message_id: Option<String>,
-    pub channel_id: String,
+    pub channel_id: Option<String>,
     pub guild_id: Option<String>,
     pub fail_if_not_exists: Option<bool>,
 }
 
+discord_enum!(MessageReferenceType {
+    Default = 0, // A reply
+    Forward = 1,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Reaction {
     pub count: u64,
-    pub count_details: u64,
+    pub count_details: ReactionCountDetails,
     pub me: bool,
     pub me_burst: bool,
     pub emoji: Emoji,
     pub burst_colors: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReactionCountDetails {
+    pub burst: u64, // Super reactions
+    pub normal: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Embed {
     pub title: Option<String>,
+    #[serde(rename = "type")]
     pub embed_type: Option<String>,
     pub description: Option<String>,
     pub url: Option<String>,
@@ -1178,7 +1765,7 @@ pub struct EmbedField {
 pub struct StickerItem {
     pub id: String,
     pub name: String,
-    pub format_type: u32,
+    pub format_type: StickerFormatType,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1189,6 +1776,46 @@ pub struct RoleSubscriptionData {
     pub is_renewal: bool,
 }
 
+discord_enum!(MessageType {
+    Default = 0,
+    RecipientAdd = 1,
+    RecipientRemove = 2,
+    Call = 3,
+    ChannelNameChange = 4,
+    ChannelIconChange = 5,
+    ChannelPinnedMessage = 6,
+    UserJoin = 7,
+    GuildBoost = 8,
+    GuildBoostTier1 = 9,
+    GuildBoostTier2 = 10,
+    GuildBoostTier3 = 11,
+    ChannelFollowAdd = 12,
+    GuildDiscoveryDisqualified = 14,
+    GuildDiscoveryRequalified = 15,
+    GuildDiscoveryGracePeriodInitialWarning = 16,
+    GuildDiscoveryGracePeriodFinalWarning = 17,
+    ThreadCreated = 18,
+    Reply = 19,
+    ChatInputCommand = 20,
+    ThreadStarterMessage = 21,
+    GuildInviteReminder = 22,
+    ContextMenuCommand = 23,
+    AutoModerationAction = 24,
+    RoleSubscriptionPurchase = 25,
+    InteractionPremiumUpsell = 26,
+    StageStart = 27,
+    StageEnd = 28,
+    StageSpeaker = 29,
+    StageTopic = 31,
+    GuildApplicationPremiumSubscription = 32,
+    GuildIncidentAlertModeEnabled = 36,
+    GuildIncidentAlertModeDisabled = 37,
+    GuildIncidentReportRaid = 38,
+    GuildIncidentReportFalseAlarm = 39,
+    PurchaseNotification = 44,
+    PollResult = 46,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Message {
     // in a MessageUpdate all fields are optional except id and channel_id
@@ -1201,23 +1828,27 @@ pub struct Message {
     pub tts: Option<bool>,
     pub mentions: Option<Vec<User>>,
     pub mention_everyone: Option<bool>,
-    pub mention_roles: Vec<Role>,
+    #[serde(default)]
+    pub mention_roles: Vec<String>,
     pub mention_channels: Option<Vec<Channel>>,
+    #[serde(default)]
     pub attachments: Vec<Attachment>,
+    #[serde(default)]
     pub embeds: Vec<Embed>,
     pub reactions: Option<Vec<Reaction>>,
     pub nonce: Option<String>,
     pub pinned: Option<bool>,
     pub webhook_id: Option<String>,
     #[serde(rename = "type")]
-    pub message_type: Option<u32>,
+    pub message_type: Option<MessageType>,
     pub activity: Option<MessageActivity>,
     pub application: Option<Application>,
     pub application_id: Option<String>,
     pub message_reference: Option<MessageReference>,
     pub flags: Option<u64>,
     pub referenced_message: Option<Box<Message>>,
-    pub interaction: Option<Box<Interaction>>,
+    pub interaction: Option<MessageInteraction>, // Deprecated in favour of interaction_metadata
+    pub interaction_metadata: Option<MessageInteractionMetadata>,
     pub thread: Option<Channel>,
     pub components: Option<Vec<serde_json::Value>>, // Need to figure this one out
     pub sticker_items: Option<Vec<StickerItem>>,
@@ -1225,10 +1856,83 @@ pub struct Message {
     pub position: Option<u64>,
     pub role_subscription_data: Option<RoleSubscriptionData>,
     pub resolved: Option<ResolvedData>,
+    pub poll: Option<Poll>,
     pub guild_id: Option<String>,
     pub member: Option<GuildMember>,
 }
 
+// The interaction a message is a response to
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageInteraction {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub interaction_type: InteractionKind,
+    pub name: String,
+    pub user: User,
+    pub member: Option<GuildMember>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageInteractionMetadata {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub interaction_type: InteractionKind,
+    pub user: User,
+    pub authorizing_integration_owners: HashMap<String, String>, // Installation context -> guild or user id
+    pub original_response_message_id: Option<String>,
+    pub target_user: Option<User>,             // User commands
+    pub target_message_id: Option<String>,     // Message commands
+    pub interacted_message_id: Option<String>, // Message components
+    pub triggering_interaction_metadata: Option<Box<MessageInteractionMetadata>>, // Modal submits
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollMedia {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<Emoji>, // Partial emoji, either { id } for custom or { name } for unicode
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollAnswer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<u32>, // Only sent by Discord, not used when creating a poll
+    pub poll_media: PollMedia,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollAnswerCount {
+    pub id: u32,
+    pub count: u64,
+    pub me_voted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollResults {
+    pub is_finalized: bool,
+    pub answer_counts: Vec<PollAnswerCount>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Poll {
+    pub question: PollMedia,
+    pub answers: Vec<PollAnswer>,
+    pub expiry: Option<String>,
+    pub allow_multiselect: bool,
+    pub layout_type: PollLayoutType,
+    pub results: Option<PollResults>, // Can be missing while the votes are being counted
+}
+
+discord_enum!(PollLayoutType {
+    Default = 1,
+});
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollAnswerVoters {
+    pub users: Vec<User>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResolvedData {
     pub users: Option<HashMap<String, User>>,
@@ -1243,8 +1947,9 @@ pub struct ResolvedData {
 pub struct ApplicationCommandInteractionDataOption {
     pub name: String,
     #[serde(rename = "type")]
-    pub option_type: u32,
-    pub value: serde_json::Value,
+    pub option_type: ApplicationCommandOptionType,
+    #[serde(default)]
+    pub value: serde_json::Value, // Null for subcommands and subcommand groups
     pub options: Option<Vec<ApplicationCommandInteractionDataOption>>,
     pub focused: Option<bool>,
 }
@@ -1254,19 +1959,28 @@ pub struct InteractionData {
     pub id: String,
     pub name: String,
     #[serde(rename = "type")]
-    pub interaction_type: u32,
+    pub interaction_type: ApplicationCommandType,
     pub resolved: Option<ResolvedData>,
     pub options: Option<Vec<ApplicationCommandInteractionDataOption>>,
     pub guild_id: Option<String>,
     pub target_id: Option<String>,
 }
 
+// The kind of interaction received, not to be confused with the InteractionType callback sent back
+discord_enum!(InteractionKind {
+    Ping = 1,
+    ApplicationCommand = 2,
+    MessageComponent = 3,
+    ApplicationCommandAutocomplete = 4,
+    ModalSubmit = 5,
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Interaction {
     pub id: String,
     pub application_id: String,
     #[serde(rename = "type")]
-    pub interaction_type: u32,
+    pub interaction_type: InteractionKind,
     pub data: Option<InteractionData>,
     pub guild_id: Option<String>,
     pub channel_id: Option<String>,
@@ -1279,6 +1993,28 @@ pub struct Interaction {
     pub locale: Option<String>,
     pub guild_locale: Option<String>,
     pub entitlements: Option<Vec<Entitlement>>,
+    pub guild: Option<InteractionGuild>,
+    pub channel: Option<Channel>, // Partial
+    pub authorizing_integration_owners: Option<HashMap<String, String>>,
+    pub context: Option<InteractionContextType>,
+}
+
+// The partial guild sent with an interaction
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InteractionGuild {
+    pub id: String,
+    pub locale: String,
+    pub features: Vec<String>,
+}
+
+impl Interaction {
+    // Whether any of the entitlements sent with this interaction grants the given SKU
+    pub fn has_entitlement(&self, sku_id: &str) -> bool {
+        self.entitlements
+            .as_ref()
+            .map(|entitlements| entitlements.iter().any(|e| e.grants(sku_id)))
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1327,8 +2063,17 @@ pub struct MessageReactionAdd {
     pub member: Option<GuildMember>,
     pub emoji: Emoji,
     pub message_author_id: Option<String>,
+    pub burst: Option<bool>, // A super reaction
+    pub burst_colors: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    pub reaction_type: Option<ReactionType>,
 }
 
+discord_enum!(ReactionType {
+    Normal = 0,
+    Burst = 1, // A super reaction
+});
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MessageReactionRemove {
     pub user_id: String,
@@ -1336,6 +2081,9 @@ pub struct MessageReactionRemove {
     pub message_id: String,
     pub guild_id: Option<String>,
     pub emoji: Emoji,
+    pub burst: Option<bool>,
+    #[serde(rename = "type")]
+    pub reaction_type: Option<ReactionType>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1353,6 +2101,15 @@ pub struct MessageReactionRemoveEmoji {
     pub emoji: Emoji,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessagePollVote {
+    pub user_id: String,
+    pub channel_id: String,
+    pub message_id: String,
+    pub guild_id: Option<String>,
+    pub answer_id: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TypingStart {
     pub channel_id: String,
@@ -1392,13 +2149,27 @@ pub struct WebhooksUpdate {
     pub channel_id: String,
 }
 
+// A gateway request that was dropped, only op 8 (RequestGuildMembers) for now
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RateLimited {
+    pub opcode: u8,
+    pub retry_after: f64, // Seconds
+    pub meta: RateLimitedMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RateLimitedMeta {
+    pub guild_id: String,
+    pub nonce: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct StageInstance {
     pub id: String,
     pub guild_id: String,
     pub channel_id: String,
     pub topic: String,
-    pub privacy_level: u32,
+    pub privacy_level: PrivacyLevel,
     pub discoverable_disabled: bool,
     pub guild_scheduled_event_id: Option<String>,
 }
@@ -1420,7 +2191,7 @@ pub struct GuildChannelUpdate {
 pub struct GuildChannel {
     pub name: String,
     #[serde(rename = "type")]
-    pub channel_type: Option<String>,
+    pub channel_type: Option<ChannelType>,
     pub position: Option<i32>,
     pub topic: Option<String>,
     pub bitrate: Option<i32>,
@@ -1432,10 +2203,10 @@ pub struct GuildChannel {
     pub rtc_region: Option<String>,
     pub video_quality_mode: Option<String>,
     pub default_auto_archive_duration: Option<String>,
-    pub default_reaction_emoji: Option<String>,
-    pub default_sort_order: Option<String>,
-    pub default_forum_layout: Option<String>,
-    pub available_tags: Option<Vec<String>>,
+    pub default_reaction_emoji: Option<DefaultReaction>,
+    pub default_sort_order: Option<SortOrderType>,
+    pub default_forum_layout: Option<ForumLayoutType>,
+    pub available_tags: Option<Vec<ForumTag>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1454,8 +2225,7 @@ pub struct ApplicationCommandPermission {
     pub permission: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum ApplicationCommandOptionType {
+discord_enum!(ApplicationCommandOptionType {
     SubCommand = 1,
     SubCommandGroup = 2,
     String = 3,
@@ -1467,7 +2237,7 @@ pub enum ApplicationCommandOptionType {
     Mentionable = 9,
     Number = 10,
     Attachment = 11,
-}
+});
 
 impl ApplicationCommandOptionType {
     pub fn as_u8(&self) -> u8 {
@@ -1483,35 +2253,82 @@ impl ApplicationCommandOptionType {
             ApplicationCommandOptionType::Mentionable => 9,
             ApplicationCommandOptionType::Number => 10,
             ApplicationCommandOptionType::Attachment => 11,
+            ApplicationCommandOptionType::Unknown(value) => *value as u8,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApplicationCommandOption {
     #[serde(rename = "type")]
-    pub option_type: u8,
+    pub option_type: ApplicationCommandOptionType,
     pub name: String,
     pub description: String,
     pub name_localizations: Option<HashMap<String, String>>,
     pub description_localizations: Option<HashMap<String, String>>,
     pub required: Option<bool>,
-    // pub choices: TODO https://discord.com/developers/docs/interactions/application-commands#application-command-object-application-command-option-type
-    // pub options: TODO
-    // pub channel_types: TODO
-    // pub min_value: Option<f32>,
-    // pub max_value: Option<f32>,
-    // pub min_length: Option<u32>,
-    // pub max_length: Option<u32>,
-    // pub autocomplete: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<ApplicationCommandOptionChoice>>,
+    // Subcommands of a group, or the options of a subcommand
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<ApplicationCommandOption>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_types: Option<Vec<ChannelType>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autocomplete: Option<bool>,
+}
+
+impl ApplicationCommandOption {
+    pub fn new(
+        option_type: ApplicationCommandOptionType,
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        Self {
+            option_type,
+            name: name.into(),
+            description: description.into(),
+            name_localizations: None,
+            description_localizations: None,
+            required: None,
+            choices: None,
+            options: None,
+            channel_types: None,
+            min_value: None,
+            max_value: None,
+            min_length: None,
+            max_length: None,
+            autocomplete: None,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum ApplicationCommandType {
+discord_enum!(ApplicationCommandType {
     ChatInput = 1,
     User = 2,
     Message = 3,
-}
+});
+
+// Where an app is installed, to a guild or to a user's account
+discord_enum!(ApplicationIntegrationType {
+    GuildInstall = 0,
+    UserInstall = 1,
+});
+
+// Where an interaction was triggered
+discord_enum!(InteractionContextType {
+    Guild = 0,
+    BotDm = 1,
+    PrivateChannel = 2, // DMs and group DMs other than with the bot
+});
 
 impl ApplicationCommandType {
     pub fn as_u8(&self) -> u8 {
@@ -1519,33 +2336,72 @@ impl ApplicationCommandType {
             ApplicationCommandType::ChatInput => 1,
             ApplicationCommandType::User => 2,
             ApplicationCommandType::Message => 3,
+            ApplicationCommandType::Unknown(value) => *value as u8,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApplicationCommand {
+    pub id: String,
     pub name: String,
     pub description: Option<String>,
     #[serde(rename = "type")]
     pub command_type: Option<ApplicationCommandType>,
     pub application_id: String,
-    pub guild_id: String,
-    pub command_id: String,
+    pub guild_id: Option<String>, // None for global commands
     pub name_localizations: Option<HashMap<String, String>>,
     pub description_localizations: Option<HashMap<String, String>>,
-    pub default_member_permissions: Option<u32>,
+    pub default_member_permissions: Option<String>, // Permission bit set as a string
     pub dm_permission: Option<bool>,
-    pub options: Vec<ApplicationCommandOption>,
+    pub nsfw: Option<bool>,
+    pub options: Option<Vec<ApplicationCommandOption>>,
+    pub version: String,
+    pub integration_types: Option<Vec<ApplicationIntegrationType>>,
+    pub contexts: Option<Vec<InteractionContextType>>, // Where the command can be used
+}
+
+impl ApplicationCommand {
+    // The definition of this command, as it would be sent to create it
+    pub fn to_new(&self) -> NewApplicationCommand {
+        NewApplicationCommand {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            command_type: self.command_type,
+            options: self.options.clone(),
+            name_localizations: self.name_localizations.clone(),
+            description_localizations: self.description_localizations.clone(),
+            default_member_permissions: self.default_member_permissions.clone(),
+            dm_permission: self.dm_permission,
+            nsfw: self.nsfw,
+            integration_types: self.integration_types.clone(),
+            contexts: self.contexts.clone(),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// The body for creating, updating or bulk setting a command
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct NewApplicationCommand {
     pub name: String,
     pub description: Option<String>,
     #[serde(rename = "type")]
-    pub command_type: Option<u8>,
+    pub command_type: Option<ApplicationCommandType>,
     pub options: Option<Vec<ApplicationCommandOption>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_localizations: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_localizations: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_member_permissions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dm_permission: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nsfw: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integration_types: Option<Vec<ApplicationIntegrationType>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<Vec<InteractionContextType>>, // Where the command can be used
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1565,9 +2421,9 @@ pub struct NewGuild {
     pub description: Option<String>,
     pub region: Option<String>,
     pub icon: Option<String>,
-    pub verification_level: Option<u32>,
-    pub default_message_notifications: Option<u32>,
-    pub explicit_content_filter: Option<u32>,
+    pub verification_level: Option<VerificationLevel>,
+    pub default_message_notifications: Option<DefaultMessageNotificationLevel>,
+    pub explicit_content_filter: Option<ExplicitContentFilterLevel>,
     pub preferred_locale: Option<String>,
     pub afk_timeout: Option<u32>,
     pub roles: Vec<GuildRole>,
@@ -1583,9 +2439,9 @@ pub struct UpdateGuild {
     pub description: Option<String>,
     pub region: Option<String>,
     pub icon: Option<String>,
-    pub verification_level: Option<String>,
-    pub default_message_notifications: Option<String>,
-    pub explicit_content_filter: Option<String>,
+    pub verification_level: Option<VerificationLevel>,
+    pub default_message_notifications: Option<DefaultMessageNotificationLevel>,
+    pub explicit_content_filter: Option<ExplicitContentFilterLevel>,
     pub preferred_locale: Option<String>,
     pub afk_timeout: Option<String>,
     pub afk_channel_id: Option<String>,
@@ -1611,7 +2467,7 @@ pub struct AllowedMention {
     pub replied_user: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct InteractionCallbackData {
     pub tts: Option<bool>,
     pub content: Option<String>,
@@ -1622,15 +2478,92 @@ pub struct InteractionCallbackData {
     pub attachments: Option<Vec<Attachment>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl InteractionCallbackData {
+    pub fn content(content: impl Into<String>) -> Self {
+        Self {
+            content: Some(content.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn embeds(embeds: Vec<Embed>) -> Self {
+        Self {
+            embeds: Some(embeds),
+            ..Default::default()
+        }
+    }
+
+    // Only the invoking user will see the message
+    pub fn ephemeral(mut self) -> Self {
+        self.flags = Some(self.flags.unwrap_or(0) | MESSAGE_FLAG_EPHEMERAL);
+        self
+    }
+}
+
+// A choice returned for an autocomplete interaction, or offered by a command option
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApplicationCommandOptionChoice {
+    pub name: String,
+    pub name_localizations: Option<HashMap<String, String>>,
+    pub value: serde_json::Value, // String, integer or number matching the option type
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct WebhookMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub embeds: Option<Vec<Embed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub allowed_mentions: Option<AllowedMention>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub attachments: Option<Vec<Attachment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub components: Option<Vec<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub payload_json: Option<String>,
     // files[n] **	file contents	the contents of the file being sent/edited // REQUIRES CHANGING TO FORM DATA
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u32>, // Follow-ups only, e.g. MESSAGE_FLAG_EPHEMERAL
+}
+
+// A file uploaded with a multipart/form-data request
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileUpload {
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub description: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+// The first message of a forum or media channel post
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ForumThreadMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeds: Option<Vec<Embed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_mentions: Option<AllowedMention>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticker_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u64>,
+}
+
+// Question text max 300 characters, up to 10 answers of max 55 characters each
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollCreateRequest {
+    pub question: PollMedia,
+    pub answers: Vec<PollAnswer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>, // Hours, defaults to 24, max 768 (32 days)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_multiselect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout_type: Option<PollLayoutType>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1650,11 +2583,46 @@ pub struct ApplicationRoleConnectionsMetadata {
     pub description_localizations: HashMap<String, String>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NewAutoModerationRule {
+    pub name: String,
+    pub event_type: AutoModerationEventType,
+    pub trigger_type: AutoModerationTriggerType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_metadata: Option<AutoModerationTriggerMetadata>,
+    pub actions: Vec<AutoModerationAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exempt_roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exempt_channels: Option<Vec<String>>,
+}
+
+// trigger_type cannot be changed once a rule is created
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct UpdateAutoModerationRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<AutoModerationEventType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_metadata: Option<AutoModerationTriggerMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<AutoModerationAction>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exempt_roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exempt_channels: Option<Vec<String>>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HttpStageInstance {
     pub topic: String,
     pub channel_id: String,
-    pub privacy_level: Option<u32>,
+    pub privacy_level: Option<PrivacyLevel>,
     pub guild_scheduled_event_id: Option<String>,
     pub send_start_notification: Option<bool>,
 }
@@ -0,0 +1,507 @@
+// Voice gateway signaling, without any audio.
+//
+// Joining a voice channel takes an UpdateVoiceState on the main gateway, after which Discord
+// sends our VOICE_STATE_UPDATE (the session id) and a VOICE_SERVER_UPDATE (token and endpoint).
+// A VoiceConnection correlates the two, then runs the voice websocket at gateway_url():
+// Hello -> Identify, Ready (SSRC, UDP address, modes) -> SelectProtocol once the audio side has
+// done IP discovery, SessionDescription (mode and secret key). handle() returns what to send and
+// what to tell the parent, so the websocket and timers can live anywhere, e.g. the discord_api
+// process, while a separate process handles the UDP transport.
+
+use serde::{Deserialize, Serialize};
+
+use super::types::*;
+
+pub const VOICE_GATEWAY_PARAMS: &str = "?v=8";
+
+// Close codes where reconnecting won't help, e.g. 4006 when the session is no longer valid
+// or 4014 when the bot was kicked from the channel or the channel was deleted
+pub fn voice_close_code_is_fatal(code: u16) -> bool {
+    matches!(code, 4004 | 4006 | 4011 | 4012 | 4014 | 4016)
+}
+
+// Reopened websockets in a row that didn't get back to Ready or Resumed before giving up
+pub const MAX_VOICE_RESUMES: u32 = 5;
+
+// Encryption modes we can pick from Ready.modes, most preferred first
+pub const VOICE_ENCRYPTION_MODES: &[&str] =
+    &["aead_aes256_gcm_rtpsize", "aead_xchacha20_poly1305_rtpsize"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum VoiceSendEvent {
+    Identify {
+        server_id: String,
+        user_id: String,
+        session_id: String,
+        token: String,
+    },
+    SelectProtocol {
+        address: String,
+        port: u16,
+        mode: String,
+    },
+    Heartbeat {
+        nonce: u64,
+        seq_ack: Option<u64>,
+    },
+    Speaking {
+        speaking: u32, // Bit set: 1 = MICROPHONE, 2 = SOUNDSHARE, 4 = PRIORITY
+        ssrc: u32,
+    },
+    Resume {
+        server_id: String,
+        session_id: String,
+        token: String,
+        seq_ack: Option<u64>,
+    },
+}
+
+impl VoiceSendEvent {
+    pub fn to_json_bytes(&self) -> Vec<u8> {
+        match self {
+            VoiceSendEvent::Identify {
+                server_id,
+                user_id,
+                session_id,
+                token,
+            } => serde_json::json!({
+                "op": 0,
+                "d": {
+                    "server_id": server_id,
+                    "user_id": user_id,
+                    "session_id": session_id,
+                    "token": token,
+                },
+            }),
+            VoiceSendEvent::SelectProtocol {
+                address,
+                port,
+                mode,
+            } => serde_json::json!({
+                "op": 1,
+                "d": {
+                    "protocol": "udp",
+                    "data": {
+                        "address": address,
+                        "port": port,
+                        "mode": mode,
+                    },
+                },
+            }),
+            VoiceSendEvent::Heartbeat { nonce, seq_ack } => serde_json::json!({
+                "op": 3,
+                "d": {
+                    "t": nonce,
+                    "seq_ack": seq_ack,
+                },
+            }),
+            VoiceSendEvent::Speaking { speaking, ssrc } => serde_json::json!({
+                "op": 5,
+                "d": {
+                    "speaking": speaking,
+                    "delay": 0,
+                    "ssrc": ssrc,
+                },
+            }),
+            VoiceSendEvent::Resume {
+                server_id,
+                session_id,
+                token,
+                seq_ack,
+            } => serde_json::json!({
+                "op": 7,
+                "d": {
+                    "server_id": server_id,
+                    "session_id": session_id,
+                    "token": token,
+                    "seq_ack": seq_ack,
+                },
+            }),
+        }
+        .to_string()
+        .as_bytes()
+        .to_vec()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct VoicePayload {
+    op: u8,
+    d: Option<serde_json::Value>,
+    seq: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceReady {
+    pub ssrc: u32,
+    pub ip: String,
+    pub port: u16,
+    pub modes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceSessionDescription {
+    pub mode: String,
+    pub secret_key: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceSpeaking {
+    pub user_id: Option<String>,
+    pub ssrc: u32,
+    pub speaking: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceHello {
+    pub heartbeat_interval: f64, // Milliseconds
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoiceClientDisconnect {
+    pub user_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum VoiceReceiveEvent {
+    Ready(VoiceReady),
+    SessionDescription(VoiceSessionDescription),
+    Speaking(VoiceSpeaking),
+    HeartbeatAck,
+    Hello(VoiceHello),
+    Resumed,
+    ClientDisconnect(VoiceClientDisconnect),
+    // Opcodes we don't act on, e.g. the client connect and DAVE ones
+    Other(u8),
+}
+
+pub fn parse_voice_blob(payload_bytes: &[u8]) -> anyhow::Result<(VoiceReceiveEvent, Option<u64>)> {
+    let payload = serde_json::from_slice::<VoicePayload>(payload_bytes).map_err(|_| {
+        anyhow::anyhow!(
+            "discord_api: not a valid voice payload {}",
+            String::from_utf8_lossy(payload_bytes)
+        )
+    })?;
+    let d = payload.d.unwrap_or_default();
+    let parse_error =
+        |name: &str| anyhow::anyhow!("Failed to parse voice {} with data: {}", name, d);
+
+    let event = match payload.op {
+        2 => VoiceReceiveEvent::Ready(
+            serde_json::from_value(d.clone()).map_err(|_| parse_error("Ready"))?,
+        ),
+        4 => VoiceReceiveEvent::SessionDescription(
+            serde_json::from_value(d.clone()).map_err(|_| parse_error("SessionDescription"))?,
+        ),
+        5 => VoiceReceiveEvent::Speaking(
+            serde_json::from_value(d.clone()).map_err(|_| parse_error("Speaking"))?,
+        ),
+        6 => VoiceReceiveEvent::HeartbeatAck,
+        8 => VoiceReceiveEvent::Hello(
+            serde_json::from_value(d.clone()).map_err(|_| parse_error("Hello"))?,
+        ),
+        9 => VoiceReceiveEvent::Resumed,
+        13 => VoiceReceiveEvent::ClientDisconnect(
+            serde_json::from_value(d.clone()).map_err(|_| parse_error("ClientDisconnect"))?,
+        ),
+        op => VoiceReceiveEvent::Other(op),
+    };
+
+    Ok((event, payload.seq))
+}
+
+// What the parent is told about a voice connection, wrapped in GatewayReceiveEvent::Voice
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum VoiceEvent {
+    // The voice server is ready, do IP discovery against ip:port with ssrc, then select a protocol
+    Ready {
+        guild_id: String,
+        ready: VoiceReady,
+    },
+    // Everything needed to send and receive audio
+    SessionDescription {
+        guild_id: String,
+        ssrc: u32,
+        ip: String,
+        port: u16,
+        description: VoiceSessionDescription,
+    },
+    Speaking {
+        guild_id: String,
+        speaking: VoiceSpeaking,
+    },
+    ClientDisconnect {
+        guild_id: String,
+        user_id: String,
+    },
+    // The voice connection ended for good, join again to reconnect
+    Disconnected {
+        guild_id: String,
+    },
+}
+
+// What to do about a closed voice websocket, see VoiceConnection::on_close
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoiceClose {
+    // Nothing, e.g. it was replaced or a VOICE_SERVER_UPDATE with the new server follows
+    Ignore,
+    // Open the websocket at this url again, Hello then resumes the session
+    Reopen(String),
+    // The connection is over, drop it and tell the parent
+    Disconnect,
+}
+
+// What handle() wants done, in order
+#[derive(Debug, Clone)]
+pub enum VoiceAction {
+    Send(VoiceSendEvent),
+    Notify(VoiceEvent),
+    // Send heartbeat() every this many milliseconds
+    StartHeartbeat(u64),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VoiceConnectionState {
+    // Waiting for the voice state and voice server events
+    Joining,
+    // The voice websocket can be opened at gateway_url()
+    Connecting,
+    Identifying,
+    // Ready came, waiting for select_protocol()
+    SelectingProtocol,
+    Connected,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VoiceConnection {
+    pub guild_id: String,
+    pub user_id: String,
+    pub channel_id: Option<String>,
+    pub state: VoiceConnectionState,
+    session_id: Option<String>,
+    token: Option<String>,
+    endpoint: Option<String>,
+    identified: bool,
+    ready: Option<VoiceReady>,
+    seq_ack: Option<u64>,
+    heartbeat_nonce: u64,
+    replacing: bool,
+    resumes: u32,
+}
+
+impl VoiceConnection {
+    pub fn new(guild_id: &str, user_id: &str) -> Self {
+        Self {
+            guild_id: guild_id.to_string(),
+            user_id: user_id.to_string(),
+            channel_id: None,
+            state: VoiceConnectionState::Joining,
+            session_id: None,
+            token: None,
+            endpoint: None,
+            identified: false,
+            ready: None,
+            seq_ack: None,
+            heartbeat_nonce: 0,
+            replacing: false,
+            resumes: 0,
+        }
+    }
+
+    // Takes our own VOICE_STATE_UPDATE in this guild, others are ignored.
+    // Returns true when the voice websocket should be (re)opened.
+    pub fn on_voice_state(&mut self, voice_state: &VoiceState) -> bool {
+        if voice_state.user_id != self.user_id
+            || voice_state.guild_id.as_deref() != Some(self.guild_id.as_str())
+        {
+            return false;
+        }
+        self.channel_id = voice_state.channel_id.clone();
+        self.session_id = Some(voice_state.session_id.clone());
+        self.try_connect()
+    }
+
+    // Takes a VOICE_SERVER_UPDATE in this guild, sent on join and when the voice server moves.
+    // Returns true when the voice websocket should be (re)opened.
+    pub fn on_voice_server(&mut self, server: &VoiceServerUpdate) -> bool {
+        if server.guild_id != self.guild_id {
+            return false;
+        }
+        // A null endpoint means the old server went away, another update follows
+        self.token = Some(server.token.clone());
+        self.endpoint = server.endpoint.clone();
+        self.identified = false;
+        self.state = VoiceConnectionState::Joining;
+        self.try_connect()
+    }
+
+    fn try_connect(&mut self) -> bool {
+        if self.state != VoiceConnectionState::Joining
+            || self.session_id.is_none()
+            || self.token.is_none()
+            || self.endpoint.is_none()
+        {
+            return false;
+        }
+        self.state = VoiceConnectionState::Connecting;
+        true
+    }
+
+    // Call before closing the websocket to open another, so its close is ignored
+    pub fn replace_websocket(&mut self) {
+        self.replacing = true;
+    }
+
+    // Call when the voice websocket closed, with Discord's close code if there is one
+    pub fn on_close(&mut self, code: Option<u16>) -> VoiceClose {
+        if self.replacing && code.is_none() {
+            self.replacing = false;
+            return VoiceClose::Ignore;
+        }
+        self.replacing = false;
+
+        if code.is_some_and(voice_close_code_is_fatal)
+            || self.channel_id.is_none()
+            || self.resumes >= MAX_VOICE_RESUMES
+        {
+            return VoiceClose::Disconnect;
+        }
+        // The voice server went away, the next VOICE_SERVER_UPDATE reconnects
+        let Some(url) = self.gateway_url() else {
+            self.state = VoiceConnectionState::Joining;
+            return VoiceClose::Ignore;
+        };
+        self.resumes += 1;
+        VoiceClose::Reopen(url)
+    }
+
+    pub fn gateway_url(&self) -> Option<String> {
+        let endpoint = self.endpoint.as_deref()?;
+        let host = endpoint.trim_start_matches("wss://").trim_end_matches('/');
+        Some(format!("wss://{}/{}", host, VOICE_GATEWAY_PARAMS))
+    }
+
+    // Acts on a voice gateway event and its seq
+    pub fn handle(&mut self, event: VoiceReceiveEvent, seq: Option<u64>) -> Vec<VoiceAction> {
+        if seq.is_some() {
+            self.seq_ack = seq;
+        }
+        match event {
+            VoiceReceiveEvent::Hello(hello) => {
+                let (Some(session_id), Some(token)) = (self.session_id.clone(), self.token.clone())
+                else {
+                    return vec![];
+                };
+                let identify = match self.identified {
+                    // A reopened websocket picks the session back up
+                    true => VoiceSendEvent::Resume {
+                        server_id: self.guild_id.clone(),
+                        session_id,
+                        token,
+                        seq_ack: self.seq_ack,
+                    },
+                    false => VoiceSendEvent::Identify {
+                        server_id: self.guild_id.clone(),
+                        user_id: self.user_id.clone(),
+                        session_id,
+                        token,
+                    },
+                };
+                self.identified = true;
+                if self.state == VoiceConnectionState::Connecting {
+                    self.state = VoiceConnectionState::Identifying;
+                }
+                vec![
+                    VoiceAction::StartHeartbeat(hello.heartbeat_interval as u64),
+                    VoiceAction::Send(identify),
+                ]
+            }
+            VoiceReceiveEvent::Ready(ready) => {
+                self.resumes = 0;
+                self.state = VoiceConnectionState::SelectingProtocol;
+                self.ready = Some(ready.clone());
+                vec![VoiceAction::Notify(VoiceEvent::Ready {
+                    guild_id: self.guild_id.clone(),
+                    ready,
+                })]
+            }
+            VoiceReceiveEvent::SessionDescription(description) => {
+                let Some(ready) = self.ready.as_ref() else {
+                    return vec![];
+                };
+                self.state = VoiceConnectionState::Connected;
+                vec![VoiceAction::Notify(VoiceEvent::SessionDescription {
+                    guild_id: self.guild_id.clone(),
+                    ssrc: ready.ssrc,
+                    ip: ready.ip.clone(),
+                    port: ready.port,
+                    description,
+                })]
+            }
+            VoiceReceiveEvent::Speaking(speaking) => {
+                vec![VoiceAction::Notify(VoiceEvent::Speaking {
+                    guild_id: self.guild_id.clone(),
+                    speaking,
+                })]
+            }
+            VoiceReceiveEvent::ClientDisconnect(disconnect) => {
+                vec![VoiceAction::Notify(VoiceEvent::ClientDisconnect {
+                    guild_id: self.guild_id.clone(),
+                    user_id: disconnect.user_id,
+                })]
+            }
+            VoiceReceiveEvent::Resumed => {
+                self.resumes = 0;
+                vec![]
+            }
+            VoiceReceiveEvent::HeartbeatAck | VoiceReceiveEvent::Other(_) => vec![],
+        }
+    }
+
+    pub fn heartbeat(&mut self) -> VoiceSendEvent {
+        self.heartbeat_nonce += 1;
+        VoiceSendEvent::Heartbeat {
+            nonce: self.heartbeat_nonce,
+            seq_ack: self.seq_ack,
+        }
+    }
+
+    // After IP discovery: our external address and port as seen by the voice server.
+    // Without a mode, the first of VOICE_ENCRYPTION_MODES the server offers is used.
+    pub fn select_protocol(
+        &self,
+        address: &str,
+        port: u16,
+        mode: Option<String>,
+    ) -> anyhow::Result<VoiceSendEvent> {
+        let Some(ready) = self.ready.as_ref() else {
+            return Err(anyhow::anyhow!("voice connection is not ready"));
+        };
+        let mode = match mode {
+            Some(mode) => mode,
+            None => VOICE_ENCRYPTION_MODES
+                .iter()
+                .find(|mode| ready.modes.iter().any(|offered| offered == *mode))
+                .map(|mode| mode.to_string())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("no supported encryption mode in {:?}", ready.modes)
+                })?,
+        };
+        Ok(VoiceSendEvent::SelectProtocol {
+            address: address.to_string(),
+            port,
+            mode,
+        })
+    }
+
+    // Must be sent before the first audio packet
+    pub fn speaking(&self, speaking: bool) -> anyhow::Result<VoiceSendEvent> {
+        let Some(ready) = self.ready.as_ref() else {
+            return Err(anyhow::anyhow!("voice connection is not ready"));
+        };
+        Ok(VoiceSendEvent::Speaking {
+            speaking: speaking as u32,
+            ssrc: ready.ssrc,
+        })
+    }
+}
@@ -0,0 +1,387 @@
+// Voice audio over UDP: IP discovery, RTP packets and transport encryption.
+//
+// Once the voice gateway sends its SessionDescription (see voice_gateway.rs), audio goes to the
+// voice server's ip:port as RTP packets carrying Opus frames, 20ms each, encrypted with the
+// negotiated mode and secret key. A VoiceSender packetizes and paces pre-encoded Opus frames,
+// a VoiceReceiver decrypts what arrives, per SSRC. Signal Speaking (op 5) before sending audio.
+//
+// Sockets are plain std::net::UdpSocket, so this runs wherever UDP is available, not inside a
+// Kinode process. Needs the voice_transport feature.
+
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::XChaCha20Poly1305;
+use crypto_secretbox::aead::{Aead, KeyInit, Payload};
+use crypto_secretbox::XSalsa20Poly1305;
+use serde::{Deserialize, Serialize};
+
+pub const RTP_HEADER_LEN: usize = 12;
+pub const OPUS_FRAME_DURATION: Duration = Duration::from_millis(20);
+// 20ms at 48kHz
+pub const OPUS_FRAME_SAMPLES: u32 = 960;
+// Sent five times when audio stops, so the decoder doesn't interpolate
+pub const OPUS_SILENCE_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+const IP_DISCOVERY_LEN: usize = 74;
+const RTP_VERSION: u8 = 0x80;
+const RTP_PAYLOAD_TYPE_OPUS: u8 = 0x78;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VoiceEncryptionMode {
+    XSalsa20Poly1305, // Deprecated by Discord
+    Aes256GcmRtpSize,
+    XChaCha20Poly1305RtpSize,
+}
+
+impl VoiceEncryptionMode {
+    pub fn from_mode(mode: &str) -> anyhow::Result<Self> {
+        match mode {
+            "xsalsa20_poly1305" => Ok(Self::XSalsa20Poly1305),
+            "aead_aes256_gcm_rtpsize" => Ok(Self::Aes256GcmRtpSize),
+            "aead_xchacha20_poly1305_rtpsize" => Ok(Self::XChaCha20Poly1305RtpSize),
+            _ => Err(anyhow::anyhow!(
+                "unsupported voice encryption mode {}",
+                mode
+            )),
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Self::XSalsa20Poly1305 => "xsalsa20_poly1305",
+            Self::Aes256GcmRtpSize => "aead_aes256_gcm_rtpsize",
+            Self::XChaCha20Poly1305RtpSize => "aead_xchacha20_poly1305_rtpsize",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RtpHeader {
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    pub fn to_bytes(&self) -> [u8; RTP_HEADER_LEN] {
+        let mut bytes = [0; RTP_HEADER_LEN];
+        bytes[0] = RTP_VERSION;
+        bytes[1] = RTP_PAYLOAD_TYPE_OPUS;
+        bytes[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        bytes
+    }
+
+    pub fn parse(packet: &[u8]) -> anyhow::Result<Self> {
+        if packet.len() < RTP_HEADER_LEN || packet[0] & 0xC0 != RTP_VERSION {
+            return Err(anyhow::anyhow!("not an RTP packet"));
+        }
+        Ok(Self {
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+            ssrc: u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+        })
+    }
+}
+
+// A decrypted audio packet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RtpPacket {
+    pub header: RtpHeader,
+    pub opus: Vec<u8>,
+}
+
+// Seals and opens RTP payloads with the session's mode and secret key
+#[derive(Debug, Clone)]
+pub struct VoiceCrypto {
+    mode: VoiceEncryptionMode,
+    key: [u8; 32],
+    nonce: u32, // Counter for the rtpsize modes
+}
+
+impl VoiceCrypto {
+    pub fn new(mode: &str, secret_key: &[u8]) -> anyhow::Result<Self> {
+        let key = secret_key
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("voice secret key must be 32 bytes"))?;
+        Ok(Self {
+            mode: VoiceEncryptionMode::from_mode(mode)?,
+            key,
+            nonce: 0,
+        })
+    }
+
+    pub fn mode(&self) -> VoiceEncryptionMode {
+        self.mode
+    }
+
+    pub fn seal(&mut self, header: &RtpHeader, opus: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let header = header.to_bytes();
+        let mut packet = header.to_vec();
+        match self.mode {
+            // The nonce is the RTP header padded with zeros
+            VoiceEncryptionMode::XSalsa20Poly1305 => {
+                let mut nonce = [0; 24];
+                nonce[..RTP_HEADER_LEN].copy_from_slice(&header);
+                packet.extend(encrypt::<XSalsa20Poly1305>(&self.key, &nonce, opus, &[])?);
+            }
+            // The header is authenticated, a 4 byte nonce counter goes at the end
+            VoiceEncryptionMode::Aes256GcmRtpSize
+            | VoiceEncryptionMode::XChaCha20Poly1305RtpSize => {
+                self.nonce = self.nonce.wrapping_add(1);
+                let counter = self.nonce.to_be_bytes();
+                packet.extend(self.aead(&counter, opus, &header, true)?);
+                packet.extend(counter);
+            }
+        }
+        Ok(packet)
+    }
+
+    pub fn open(&self, packet: &[u8]) -> anyhow::Result<RtpPacket> {
+        let header = RtpHeader::parse(packet)?;
+        let csrc_len = 4 * (packet[0] & 0x0F) as usize;
+        let has_extension = packet[0] & 0x10 != 0;
+        let has_padding = packet[0] & 0x20 != 0;
+
+        let mut plaintext = match self.mode {
+            // Everything after the fixed header is encrypted
+            VoiceEncryptionMode::XSalsa20Poly1305 => {
+                let mut nonce = [0; 24];
+                nonce[..RTP_HEADER_LEN].copy_from_slice(&packet[..RTP_HEADER_LEN]);
+                let mut plaintext =
+                    decrypt::<XSalsa20Poly1305>(&self.key, &nonce, &packet[RTP_HEADER_LEN..], &[])?;
+                plaintext.drain(..csrc_len.min(plaintext.len()));
+                if has_extension && plaintext.len() >= 4 {
+                    let words = u16::from_be_bytes([plaintext[2], plaintext[3]]) as usize;
+                    plaintext.drain(..(4 + 4 * words).min(plaintext.len()));
+                }
+                plaintext
+            }
+            // The extension header is authenticated in the clear, its body is encrypted
+            VoiceEncryptionMode::Aes256GcmRtpSize
+            | VoiceEncryptionMode::XChaCha20Poly1305RtpSize => {
+                let aad_len = RTP_HEADER_LEN + csrc_len + if has_extension { 4 } else { 0 };
+                if packet.len() < aad_len + 4 {
+                    return Err(anyhow::anyhow!("voice packet too short"));
+                }
+                let (body, counter) = packet.split_at(packet.len() - 4);
+                let (aad, ciphertext) = body.split_at(aad_len);
+                let mut plaintext = self.aead(counter, ciphertext, aad, false)?;
+                if has_extension {
+                    let words = u16::from_be_bytes([aad[aad_len - 2], aad[aad_len - 1]]) as usize;
+                    plaintext.drain(..(4 * words).min(plaintext.len()));
+                }
+                plaintext
+            }
+        };
+
+        if has_padding {
+            let padding = plaintext.last().copied().unwrap_or(0) as usize;
+            plaintext.truncate(plaintext.len().saturating_sub(padding));
+        }
+
+        Ok(RtpPacket {
+            header,
+            opus: plaintext,
+        })
+    }
+
+    fn aead(&self, counter: &[u8], data: &[u8], aad: &[u8], seal: bool) -> anyhow::Result<Vec<u8>> {
+        match self.mode {
+            VoiceEncryptionMode::Aes256GcmRtpSize => {
+                let mut nonce = [0; 12];
+                nonce[..4].copy_from_slice(counter);
+                match seal {
+                    true => encrypt::<Aes256Gcm>(&self.key, &nonce, data, aad),
+                    false => decrypt::<Aes256Gcm>(&self.key, &nonce, data, aad),
+                }
+            }
+            _ => {
+                let mut nonce = [0; 24];
+                nonce[..4].copy_from_slice(counter);
+                match seal {
+                    true => encrypt::<XChaCha20Poly1305>(&self.key, &nonce, data, aad),
+                    false => decrypt::<XChaCha20Poly1305>(&self.key, &nonce, data, aad),
+                }
+            }
+        }
+    }
+}
+
+fn encrypt<C: KeyInit + Aead>(
+    key: &[u8; 32],
+    nonce: &[u8],
+    msg: &[u8],
+    aad: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key).map_err(|_| anyhow::anyhow!("bad voice key"))?;
+    cipher
+        .encrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| anyhow::anyhow!("failed to encrypt voice packet"))
+}
+
+fn decrypt<C: KeyInit + Aead>(
+    key: &[u8; 32],
+    nonce: &[u8],
+    msg: &[u8],
+    aad: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key).map_err(|_| anyhow::anyhow!("bad voice key"))?;
+    cipher
+        .decrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| anyhow::anyhow!("failed to decrypt voice packet"))
+}
+
+pub fn ip_discovery_request(ssrc: u32) -> [u8; IP_DISCOVERY_LEN] {
+    let mut packet = [0; IP_DISCOVERY_LEN];
+    packet[0..2].copy_from_slice(&1u16.to_be_bytes()); // Request
+    packet[2..4].copy_from_slice(&70u16.to_be_bytes()); // Length after this field
+    packet[4..8].copy_from_slice(&ssrc.to_be_bytes());
+    packet
+}
+
+// The address and port the voice server sees us at
+pub fn parse_ip_discovery_response(packet: &[u8]) -> anyhow::Result<(String, u16)> {
+    if packet.len() < IP_DISCOVERY_LEN || packet[0..2] != 2u16.to_be_bytes() {
+        return Err(anyhow::anyhow!("not an IP discovery response"));
+    }
+    let address = &packet[8..72];
+    let end = address
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(address.len());
+    let address = String::from_utf8(address[..end].to_vec())?;
+    let port = u16::from_be_bytes([packet[72], packet[73]]);
+    Ok((address, port))
+}
+
+// Sends the discovery request on a socket connected to the voice server and waits for the answer.
+// Set a read timeout on the socket to not wait forever.
+pub fn discover_ip(socket: &UdpSocket, ssrc: u32) -> anyhow::Result<(String, u16)> {
+    socket.send(&ip_discovery_request(ssrc))?;
+    let mut buffer = [0; 1024];
+    loop {
+        let len = socket.recv(&mut buffer)?;
+        if let Ok(found) = parse_ip_discovery_response(&buffer[..len]) {
+            return Ok(found);
+        }
+    }
+}
+
+// Turns Opus frames into encrypted RTP packets for our SSRC
+#[derive(Debug, Clone)]
+pub struct VoiceSender {
+    pub ssrc: u32,
+    crypto: VoiceCrypto,
+    sequence: u16,
+    timestamp: u32,
+}
+
+impl VoiceSender {
+    pub fn new(ssrc: u32, mode: &str, secret_key: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            ssrc,
+            crypto: VoiceCrypto::new(mode, secret_key)?,
+            sequence: 0,
+            timestamp: 0,
+        })
+    }
+
+    // The packet for one 20ms Opus frame
+    pub fn packet(&mut self, opus: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let header = RtpHeader {
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            ssrc: self.ssrc,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(OPUS_FRAME_SAMPLES);
+        self.crypto.seal(&header, opus)
+    }
+
+    pub fn silence(&mut self) -> anyhow::Result<Vec<Vec<u8>>> {
+        (0..5).map(|_| self.packet(&OPUS_SILENCE_FRAME)).collect()
+    }
+
+    // Streams the frames every 20ms on a socket connected to the voice server, then the
+    // silence frames. Returns how many packets went out.
+    pub fn play<I>(&mut self, socket: &UdpSocket, frames: I) -> anyhow::Result<usize>
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let mut next = Instant::now();
+        let mut sent = 0;
+        let mut send = |packet: Vec<u8>| -> anyhow::Result<()> {
+            // Keep the cadence against the clock, not the time each send took
+            if let Some(wait) = next.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+            socket.send(&packet)?;
+            next += OPUS_FRAME_DURATION;
+            sent += 1;
+            Ok(())
+        };
+        for frame in frames {
+            send(self.packet(&frame)?)?;
+        }
+        for packet in self.silence()? {
+            send(packet)?;
+        }
+        Ok(sent)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct VoiceStream {
+    pub user_id: Option<String>,
+    pub last_sequence: Option<u16>,
+    pub packets: u64,
+}
+
+// Decrypts incoming packets and keeps track of each SSRC
+#[derive(Debug, Clone)]
+pub struct VoiceReceiver {
+    crypto: VoiceCrypto,
+    streams: HashMap<u32, VoiceStream>,
+}
+
+impl VoiceReceiver {
+    pub fn new(mode: &str, secret_key: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            crypto: VoiceCrypto::new(mode, secret_key)?,
+            streams: HashMap::new(),
+        })
+    }
+
+    // From the voice gateway's Speaking events, which map SSRCs to users
+    pub fn set_user(&mut self, ssrc: u32, user_id: &str) {
+        self.streams.entry(ssrc).or_default().user_id = Some(user_id.to_string());
+    }
+
+    pub fn stream(&self, ssrc: u32) -> Option<&VoiceStream> {
+        self.streams.get(&ssrc)
+    }
+
+    // None for RTCP and for packets older than the last one from their SSRC
+    pub fn receive(&mut self, packet: &[u8]) -> anyhow::Result<Option<RtpPacket>> {
+        if packet.len() >= 2 && (200..=204).contains(&packet[1]) {
+            return Ok(None);
+        }
+        let packet = self.crypto.open(packet)?;
+        let stream = self.streams.entry(packet.header.ssrc).or_default();
+        if let Some(last) = stream.last_sequence {
+            // Sequence numbers wrap, anything in the half behind the last one is late
+            if packet.header.sequence.wrapping_sub(last) == 0
+                || packet.header.sequence.wrapping_sub(last) > u16::MAX / 2
+            {
+                return Ok(None);
+            }
+        }
+        stream.last_sequence = Some(packet.header.sequence);
+        stream.packets += 1;
+        Ok(Some(packet))
+    }
+}
@@ -0,0 +1,84 @@
+// Reads the audit log fixture the way a moderation log would show it.
+
+use std::path::Path;
+
+use discord_api::*;
+
+fn audit_log() -> AuditLog {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rest/audit_log.json");
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn entry(log: &AuditLog, action_type: AuditLogEvent) -> &AuditLogEntry {
+    log.audit_log_entries
+        .iter()
+        .find(|entry| entry.action_type == action_type)
+        .unwrap()
+}
+
+#[test]
+fn resolves_users_and_targets() {
+    let log = audit_log();
+    let kick = entry(&log, AuditLogEvent::MemberKick);
+    assert_eq!(user_display_name(log.entry_user(kick).unwrap()), "Nelly");
+    let target = log.entry_target(kick).unwrap();
+    assert!(matches!(target, AuditLogTarget::User(_)));
+    assert_eq!(target.name(), "wumpus");
+
+    let webhook = entry(&log, AuditLogEvent::WebhookCreate);
+    let target = log.entry_target(webhook).unwrap();
+    assert!(matches!(target, AuditLogTarget::Webhook(_)));
+    assert_eq!(target.name(), "Captain Hook");
+
+    // Channels aren't sent with the log
+    let update = entry(&log, AuditLogEvent::ChannelUpdate);
+    assert!(matches!(
+        log.entry_target(update),
+        Some(AuditLogTarget::Id("1044359423545921579"))
+    ));
+}
+
+#[test]
+fn parses_change_values_by_key() {
+    let log = audit_log();
+    let roles = entry(&log, AuditLogEvent::MemberRoleUpdate);
+    match roles.change("$add").and_then(AuditLogChange::new_parsed) {
+        Some(AuditLogValue::Roles(roles)) => assert_eq!(roles[0].name, "Muted"),
+        other => panic!("{:?}", other),
+    }
+
+    let update = entry(&log, AuditLogEvent::ChannelUpdate);
+    let slowmode = update.change("rate_limit_per_user").unwrap();
+    assert!(matches!(
+        slowmode.old_parsed(),
+        Some(AuditLogValue::Integer(0))
+    ));
+    assert!(matches!(
+        slowmode.new_parsed(),
+        Some(AuditLogValue::Integer(10))
+    ));
+    assert!(matches!(
+        update.change("nsfw").and_then(AuditLogChange::new_parsed),
+        Some(AuditLogValue::Bool(true))
+    ));
+
+    let overwrite = entry(&log, AuditLogEvent::ChannelOverwriteUpdate);
+    assert!(matches!(
+        overwrite
+            .change("deny")
+            .and_then(AuditLogChange::new_parsed),
+        Some(AuditLogValue::Permissions(2048))
+    ));
+
+    let webhook = entry(&log, AuditLogEvent::WebhookCreate);
+    assert!(matches!(
+        webhook.change("channel_id").and_then(AuditLogChange::new_parsed),
+        Some(AuditLogValue::Id(id)) if id == "1044359423545921579"
+    ));
+    // A null value is still a change, just not one with a value
+    assert!(webhook
+        .change("avatar_hash")
+        .unwrap()
+        .new_parsed()
+        .is_none());
+}
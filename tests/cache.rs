@@ -0,0 +1,92 @@
+// Feeds gateway fixtures through the Cache and reads back what it keeps.
+
+use std::path::Path;
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+fn fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/gateway")
+        .join(format!("{}.json", name));
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn event(event: &str, d: Value) -> GatewayReceiveEvent {
+    let payload = json!({ "op": 0, "s": 1, "t": event, "d": d });
+    parse_gateway_blob(payload.to_string().as_bytes())
+        .unwrap()
+        .0
+}
+
+#[test]
+fn partial_message_update_keeps_the_rest() {
+    let mut cache = Cache::new(CacheConfig::default());
+    let created = fixture("MESSAGE_CREATE");
+    let (channel_id, id) = (
+        created["channel_id"].as_str().unwrap().to_string(),
+        created["id"].as_str().unwrap().to_string(),
+    );
+    cache.update(event("MESSAGE_CREATE", created));
+
+    // An embed unfurl only sends the embeds
+    cache.update(event(
+        "MESSAGE_UPDATE",
+        json!({
+            "id": id,
+            "channel_id": channel_id,
+            "guild_id": "197038439483310086",
+            "embeds": [{ "type": "link", "url": "https://discord.com" }],
+        }),
+    ));
+    let message = cache.message(&channel_id, &id).unwrap();
+    assert_eq!(message.author.as_ref().unwrap().username, "nelly");
+    assert_eq!(
+        message.content.as_deref(),
+        Some("Supa Hot <@&1044359506689605683>")
+    );
+    assert_eq!(
+        message.mention_roles,
+        vec!["1044359506689605683".to_string()]
+    );
+    assert_eq!(message.embeds.len(), 1);
+
+    // An edit sends the whole message
+    let mut edited = fixture("MESSAGE_CREATE");
+    edited["content"] = json!("Supa Cold");
+    edited["mention_roles"] = json!([]);
+    cache.update(event("MESSAGE_UPDATE", edited));
+    let message = cache.message(&channel_id, &id).unwrap();
+    assert_eq!(message.content.as_deref(), Some("Supa Cold"));
+    assert!(message.mention_roles.is_empty());
+    assert_eq!(message.embeds.len(), 2);
+}
+
+#[test]
+fn deleting_a_channel_drops_its_threads() {
+    let mut cache = Cache::new(CacheConfig::default());
+    let thread = fixture("THREAD_CREATE");
+    let thread_id = thread["id"].as_str().unwrap().to_string();
+    let parent_id = thread["parent_id"].as_str().unwrap().to_string();
+    let mut parent = fixture("CHANNEL_CREATE");
+    parent["id"] = json!(parent_id);
+    parent["parent_id"] = Value::Null;
+    cache.update(event("CHANNEL_CREATE", parent.clone()));
+    cache.update(event("THREAD_CREATE", thread.clone()));
+    let mut message = fixture("MESSAGE_CREATE");
+    message["channel_id"] = json!(thread_id);
+    cache.update(event("MESSAGE_CREATE", message));
+
+    // A thread of another channel stays
+    let mut other = thread;
+    other["id"] = json!("1101000000000000002");
+    other["parent_id"] = json!("1044359423545921577");
+    cache.update(event("THREAD_CREATE", other));
+
+    assert_eq!(cache.messages(&thread_id).len(), 1);
+    cache.update(event("CHANNEL_DELETE", parent));
+    assert!(cache.channel(&parent_id).is_none());
+    assert!(cache.channel(&thread_id).is_none());
+    assert!(cache.messages(&thread_id).is_empty());
+    assert!(cache.channel("1101000000000000002").is_some());
+}
@@ -0,0 +1,67 @@
+// CDN URLs for the images on users, guilds, emojis and stickers.
+
+use discord_api::*;
+
+#[test]
+fn hashed_images_pick_their_format() {
+    assert_eq!(
+        user_avatar_url(
+            "80351110224678912",
+            "8342729096ea3675442027381ff50dfe",
+            None,
+            None
+        )
+        .unwrap(),
+        "https://cdn.discordapp.com/avatars/80351110224678912/8342729096ea3675442027381ff50dfe.png"
+    );
+    // Animated hashes default to gif, and can't be asked for as gif when static
+    assert_eq!(
+        guild_icon_url(
+            "197038439483310086",
+            "a_1269e74af4df7417b13759eae50c83dc",
+            None,
+            Some(256)
+        )
+        .unwrap(),
+        "https://cdn.discordapp.com/icons/197038439483310086/a_1269e74af4df7417b13759eae50c83dc.gif?size=256"
+    );
+    assert!(guild_banner_url("1", "1269e74af4df", Some(ImageFormat::Gif), None).is_err());
+    assert!(role_icon_url("1", "1269e74af4df", Some(ImageFormat::Lottie), None).is_err());
+    assert_eq!(
+        guild_splash_url("1", "a_1269e74af4df", Some(ImageFormat::Webp), None).unwrap(),
+        "https://cdn.discordapp.com/splashes/1/a_1269e74af4df.webp"
+    );
+}
+
+#[test]
+fn sizes_are_powers_of_two_from_16_to_4096() {
+    for size in [16, 1024, 4096] {
+        assert!(user_avatar_url("1", "abc", None, Some(size)).is_ok());
+    }
+    for size in [0, 8, 100, 8192] {
+        assert!(user_avatar_url("1", "abc", None, Some(size)).is_err());
+    }
+}
+
+#[test]
+fn emojis_stickers_and_default_avatars() {
+    assert_eq!(
+        emoji_url("41771983429993937", true, None, None).unwrap(),
+        "https://cdn.discordapp.com/emojis/41771983429993937.gif"
+    );
+    assert!(emoji_url("41771983429993937", false, Some(ImageFormat::Gif), None).is_err());
+    assert_eq!(
+        sticker_url("749054660769218631", StickerFormatType::Lottie).unwrap(),
+        "https://cdn.discordapp.com/stickers/749054660769218631.json"
+    );
+    assert!(sticker_url("749054660769218631", StickerFormatType::Unknown(9)).is_err());
+
+    // New usernames go by the id, legacy ones by the discriminator
+    assert_eq!(default_avatar_index("80351110224678912", Some("0")), 5);
+    assert_eq!(default_avatar_index("80351110224678912", None), 5);
+    assert_eq!(default_avatar_index("80351110224678912", Some("1337")), 2);
+    assert_eq!(
+        default_avatar_url("80351110224678912", Some("1337")),
+        "https://cdn.discordapp.com/embed/avatars/2.png"
+    );
+}
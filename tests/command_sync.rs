@@ -0,0 +1,124 @@
+// Plans syncs against the commands in the application_commands fixture.
+
+use std::path::Path;
+
+use discord_api::*;
+
+fn registered() -> Vec<ApplicationCommand> {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rest/application_commands.json");
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn plan(desired: Vec<NewApplicationCommand>) -> CommandSyncPlan {
+    CommandSyncPlan::new(
+        "1100000000000000001",
+        CommandScope::Global,
+        &registered(),
+        desired,
+    )
+}
+
+// What a bot would declare for what's registered: no type for slash commands, and
+// the defaults Discord fills in left out
+fn declared() -> Vec<NewApplicationCommand> {
+    registered()
+        .iter()
+        .map(|command| {
+            let mut command = command.to_new();
+            if command.command_type == Some(ApplicationCommandType::ChatInput) {
+                command.command_type = None;
+            }
+            command.dm_permission = None;
+            command.nsfw = None;
+            command
+        })
+        .collect()
+}
+
+#[test]
+fn unchanged_commands_need_nothing() {
+    let plan = plan(declared());
+    assert!(plan.is_empty(), "{}", plan);
+    assert_eq!(plan.to_string(), "global commands are up to date");
+
+    // Localizations that are empty either way don't count as a change
+    let mut desired = declared();
+    desired[0].name_localizations = Some(Default::default());
+    assert!(self::plan(desired).is_empty());
+}
+
+#[test]
+fn plans_creates_updates_and_deletes() {
+    let mut desired = declared();
+    desired[0].description = Some("Send a random adorable animal".to_string());
+    desired.remove(1);
+    desired.push(NewApplicationCommand {
+        name: "ping".to_string(),
+        description: Some("Pong".to_string()),
+        ..Default::default()
+    });
+
+    let plan = plan(desired);
+    assert_eq!(
+        plan.to_string(),
+        "global command changes:\
+         \n  delete Report (1103000000000000003)\
+         \n  update blep (1103000000000000001)\
+         \n  create ping"
+    );
+    let calls = plan.calls();
+    assert!(matches!(
+        calls[0],
+        HttpApiCall::Commands(CommandsCall::DeleteGlobalApplicationCommand { .. })
+    ));
+    assert!(matches!(
+        calls[2],
+        HttpApiCall::Commands(CommandsCall::CreateApplicationCommand { .. })
+    ));
+}
+
+#[test]
+fn same_name_with_another_type_is_another_command() {
+    let mut desired = declared();
+    desired[1].command_type = Some(ApplicationCommandType::Message);
+    let plan = plan(desired);
+    assert!(matches!(
+        &plan.actions[..],
+        [
+            CommandSyncAction::Delete { .. },
+            CommandSyncAction::Create(command)
+        ] if command.name == "Report"
+    ));
+}
+
+#[test]
+fn where_a_command_is_available_counts_when_declared() {
+    // blep is registered as a guild install command, with no contexts
+    let mut desired = declared();
+    desired[0].integration_types = None;
+    assert!(plan(desired).is_empty());
+
+    let mut desired = declared();
+    desired[0].integration_types = Some(vec![
+        ApplicationIntegrationType::GuildInstall,
+        ApplicationIntegrationType::UserInstall,
+    ]);
+    desired[1].contexts = Some(vec![InteractionContextType::Guild]);
+    let plan = plan(desired);
+    assert_eq!(
+        plan.to_string(),
+        "global command changes:\
+         \n  update blep (1103000000000000001)\
+         \n  update Report (1103000000000000003)"
+    );
+    let HttpApiCall::Commands(CommandsCall::UpdateApplicationCommand { command, .. }) =
+        &plan.calls()[0]
+    else {
+        panic!("{:?}", plan.calls());
+    };
+    assert_eq!(
+        serde_json::to_value(command).unwrap()["integration_types"],
+        serde_json::json!([0, 1])
+    );
+}
@@ -0,0 +1,109 @@
+// Builds the registered commands from a CommandRouter, and routes interactions back to them.
+
+use discord_api::*;
+use serde_json::json;
+
+slash_command! {
+    pub struct Ping("ping", "Check the bot is up") {}
+}
+
+slash_command! {
+    pub struct Ban("mod ban", "Ban a member") {
+        reason: Option<String> => "Shown in the audit log",
+    }
+}
+
+slash_command! {
+    pub struct Kick("mod user kick", "Kick a member") {}
+}
+
+slash_command! {
+    pub struct Mod("mod", "Moderation tools") {}
+}
+
+slash_command! {
+    pub struct TooDeep("mod user role add", "Give a member a role") {}
+}
+
+fn reply(content: &str) -> anyhow::Result<InteractionResponse> {
+    Ok(InteractionResponse::ChannelMessageWithSource(
+        InteractionCallbackData {
+            content: Some(content.to_string()),
+            ..Default::default()
+        },
+    ))
+}
+
+fn router() -> CommandRouter {
+    CommandRouter::new()
+        .describe("mod", "Moderation tools")
+        .command(|_, _: Ping| reply("pong"))
+        .command(|_, ban: Ban| reply(&format!("banned: {:?}", ban.reason)))
+        .command(|_, _: Kick| reply("kicked"))
+}
+
+#[test]
+fn nests_subcommands_and_groups_under_one_root() {
+    let commands = router().application_commands().unwrap();
+    let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["ping", "mod"]);
+
+    let moderation = &commands[1];
+    assert_eq!(moderation.description.as_deref(), Some("Moderation tools"));
+    let options = moderation.options.as_ref().unwrap();
+    assert_eq!(options[0].name, "ban");
+    assert_eq!(
+        options[0].option_type,
+        ApplicationCommandOptionType::SubCommand
+    );
+    assert_eq!(options[1].name, "user");
+    assert_eq!(
+        options[1].option_type,
+        ApplicationCommandOptionType::SubCommandGroup
+    );
+    assert_eq!(options[1].options.as_ref().unwrap()[0].name, "kick");
+
+    let interaction: Interaction = serde_json::from_value(json!({
+        "id": "1",
+        "application_id": "2",
+        "type": 2,
+        "token": "t",
+        "version": 1,
+        "data": {
+            "id": "3",
+            "name": "mod",
+            "type": 1,
+            "options": [{
+                "name": "ban",
+                "type": 1,
+                "options": [{ "name": "reason", "type": 3, "value": "spam" }],
+            }],
+        },
+    }))
+    .unwrap();
+    assert!(router().dispatch(&interaction).is_ok());
+}
+
+#[test]
+fn rejects_paths_discord_would() {
+    let error = |router: CommandRouter| router.application_commands().unwrap_err().to_string();
+
+    assert!(error(router().command(|_, _: TooDeep| reply("added"))).contains("three words"));
+    // Declared twice
+    assert!(error(router().command(|_, _: Ping| reply("pong"))).contains("/ping and /ping"));
+    // A command with subcommands, or a subcommand that's also a group
+    assert!(error(router().command(|_, _: Mod| reply("mod"))).contains("/mod ban and /mod"));
+    slash_command! {
+        pub struct User("mod user", "Look up a member") {}
+    }
+    assert!(error(router().command(|_, _: User| reply("user"))).contains("/mod user kick"));
+    // Required options go before optional ones
+    slash_command! {
+        pub struct Warn("mod warn", "Warn a member") {
+            reason: Option<String> => "Shown in the audit log",
+            user: String => "Who to warn",
+        }
+    }
+    assert!(error(router().command(|_, _: Warn| reply("warned")))
+        .contains("required option `user` after optional option `reason`"));
+}
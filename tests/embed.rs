@@ -0,0 +1,82 @@
+// EmbedBuilder against Discord's embed limits.
+
+use discord_api::*;
+
+fn field_error(builder: EmbedBuilder) -> String {
+    builder.build().unwrap_err().field
+}
+
+#[test]
+fn builds_a_rich_embed() {
+    let embed = EmbedBuilder::new()
+        .title("Release")
+        .description("What's new")
+        .color_hex("#5865F2")
+        .field("Fixes", "3")
+        .inline_field("Features", "1")
+        .footer("v1.2", None)
+        .build()
+        .unwrap();
+    assert_eq!(embed.embed_type.as_deref(), Some("rich"));
+    assert_eq!(embed.color, Some(0x5865F2));
+    let fields = embed.fields.unwrap();
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[1].inline, Some(true));
+}
+
+#[test]
+fn reads_hex_colors_strictly() {
+    for hex in ["#5865F2", "5865f2", "0x5865F2", "0X5865F2"] {
+        let embed = EmbedBuilder::new().color_hex(hex).build().unwrap();
+        assert_eq!(embed.color, Some(0x5865F2), "{}", hex);
+    }
+    for hex in [
+        "+5865F", "#+5865F", "##5865F2", "5865F", "5865F2A", "GG65F2", "",
+    ] {
+        assert_eq!(
+            field_error(EmbedBuilder::new().color_hex(hex)),
+            "color",
+            "{}",
+            hex
+        );
+    }
+    // A later valid color replaces the invalid one
+    let embed = EmbedBuilder::new()
+        .color_hex("nope")
+        .color_hex("#000000")
+        .build()
+        .unwrap();
+    assert_eq!(embed.color, Some(0));
+}
+
+#[test]
+fn enforces_limits() {
+    let over = |max: usize| "x".repeat(max + 1);
+    assert_eq!(
+        field_error(EmbedBuilder::new().title(over(EMBED_TITLE_MAX))),
+        "title"
+    );
+    assert_eq!(
+        field_error(EmbedBuilder::new().field("a", over(EMBED_FIELD_VALUE_MAX))),
+        "fields[0].value"
+    );
+    assert_eq!(
+        field_error(EmbedBuilder::new().field("a", "b").field("", "c")),
+        "fields[1].name"
+    );
+    let crowded = (0..=EMBED_FIELDS_MAX).fold(EmbedBuilder::new(), |builder, i| {
+        builder.field(i.to_string(), "value")
+    });
+    assert_eq!(field_error(crowded), "fields");
+
+    // Each part within its own limit, but not all together. Limits count characters.
+    let description = "é".repeat(EMBED_DESCRIPTION_MAX);
+    assert!(EmbedBuilder::new()
+        .description(description.clone())
+        .build()
+        .is_ok());
+    let combined = EmbedBuilder::new()
+        .description(description)
+        .footer("f".repeat(EMBED_FOOTER_TEXT_MAX), None);
+    assert_eq!(field_error(combined), "total");
+}
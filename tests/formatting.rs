@@ -0,0 +1,103 @@
+// Mentions, tokenizing and splitting long messages.
+
+use discord_api::*;
+
+#[test]
+fn builds_and_parses_mentions() {
+    assert_eq!(user_mention("80351110224678912"), "<@80351110224678912>");
+    assert_eq!(role_mention("165511591545143296"), "<@&165511591545143296>");
+    assert_eq!(
+        timestamp_mention(1618953630, Some(TimestampStyle::Relative)),
+        "<t:1618953630:R>"
+    );
+    assert_eq!(
+        custom_emoji("mmLol", "216154654256398347", false),
+        "<:mmLol:216154654256398347>"
+    );
+
+    let content = "hi <@80351110224678912>, see <#41771983423143937> <@&nope> @everyone";
+    let mentions = parse_mentions(content);
+    assert_eq!(mentions.len(), 3, "{:?}", mentions);
+    assert_eq!(escape_mentions("@everyone"), "@\u{200b}everyone");
+    assert_eq!(escape_markdown("*bold* _it_"), "\\*bold\\* \\_it\\_");
+}
+
+#[test]
+fn splits_on_line_breaks() {
+    let text = "first line\nsecond line\nthird line";
+    assert_eq!(split_message(text, 2000), vec![text.to_string()]);
+    assert_eq!(
+        split_message(text, 23),
+        vec!["first line\nsecond line", "third line"]
+    );
+
+    // A line longer than a message is cut, counting characters rather than bytes
+    let long = "é".repeat(25);
+    let chunks = split_message(&long, 10);
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 10));
+    assert_eq!(chunks.concat(), long);
+}
+
+#[test]
+fn code_blocks_are_closed_and_reopened() {
+    let text = "```rust\nlet a = 1;\nlet b = 2;\nlet c = 3;\n```\nafter";
+    let chunks = split_message(text, 34);
+    assert_eq!(
+        chunks,
+        vec![
+            "```rust\nlet a = 1;\nlet b = 2;\n```",
+            "```rust\nlet c = 3;\n```\nafter",
+        ]
+    );
+    assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 34));
+}
+
+#[test]
+fn never_ends_a_message_on_an_opening_fence() {
+    // The opening line fits at the end of the first message, its block doesn't
+    let text = "some text before\n```rust\nlet a = 1;\nlet b = 2;\n```";
+    let chunks = split_message(text, 34);
+    assert_eq!(
+        chunks,
+        vec!["some text before", "```rust\nlet a = 1;\nlet b = 2;\n```"]
+    );
+
+    // Same when the first line of the block has to be cut
+    let text = format!("intro\n```\n{}\n```", "x".repeat(40));
+    let chunks = split_message(&text, 30);
+    assert_eq!(chunks[0], "intro");
+    assert!(chunks[1..].iter().all(|chunk| chunk.starts_with("```\nx")));
+    assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 30));
+}
+
+#[test]
+fn never_sends_empty_messages() {
+    let long = "a".repeat(2500);
+    let chunks = split_message(&format!("\n{}", long), 2000);
+    assert_eq!(chunks, vec!["a".repeat(2000), "a".repeat(500)]);
+    assert!(split_message("\n\n \n", 2000).is_empty());
+}
+
+#[test]
+fn cuts_around_fences_on_long_lines() {
+    // Cutting at 20 would split the fence and its language
+    let text = format!("{}```rust\nlet a = 1;\n```", "a".repeat(18));
+    assert_eq!(
+        split_message(&text, 24),
+        vec!["a".repeat(18), "```rust\nlet a = 1;\n```".to_string()]
+    );
+
+    // A line opening a code block, too long for one message
+    let text = format!("```rust {}\nfn main() {{}}\n```", "x".repeat(30));
+    let chunks = split_message(&text, 24);
+    assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 24));
+    for chunk in &chunks {
+        assert_eq!(chunk.matches("```").count(), 2, "{:?}", chunk);
+        assert!(chunk.starts_with("```rust"), "{:?}", chunk);
+    }
+    assert!(chunks[1..]
+        .iter()
+        .all(|chunk| chunk.starts_with("```rust\n")));
+    assert_eq!(chunks.concat().matches('x').count(), 30);
+}
@@ -0,0 +1,173 @@
+// Records gateway traffic the way the process does and plays it back.
+
+use std::path::Path;
+
+use discord_api::*;
+use flate2::{Compress, Compression, FlushCompress};
+use serde_json::{json, Value};
+
+const TOKEN: &str = "MTk4NjIyNDgzNDcxOTI1MjQ4.Cl2FMQ.ZnCjm1XVW7vRze4b7Cq4se7kKWs";
+
+fn fixture(event: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/gateway")
+        .join(format!("{}.json", event));
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn received(payload: Value) -> GatewayFrame {
+    GatewayFrame::new(
+        GatewayFrameDirection::Received,
+        payload.to_string().as_bytes(),
+        TOKEN,
+    )
+}
+
+fn dispatch(t: &str, s: u64, d: Value) -> GatewayFrame {
+    received(json!({ "op": 0, "t": t, "s": s, "d": d }))
+}
+
+fn op(op: u8, d: Value) -> GatewayFrame {
+    received(json!({ "op": op, "t": null, "s": null, "d": d }))
+}
+
+fn sent(event: GatewaySendEvent) -> GatewayFrame {
+    GatewayFrame::new(GatewayFrameDirection::Sent, &event.to_json_bytes(), TOKEN)
+}
+
+#[test]
+fn redacts_tokens() {
+    let identify = sent(
+        GatewayConnection::new(TOKEN, 513, "")
+            .handle(
+                GatewayReceiveEvent::Hello(Hello {
+                    heartbeat_interval: 41250,
+                }),
+                None,
+            )
+            .into_iter()
+            .find_map(|action| match action {
+                GatewayAction::Send(event) => Some(event),
+                _ => None,
+            })
+            .unwrap(),
+    );
+    assert!(!identify.payload.contains(TOKEN));
+    let payload: Value = serde_json::from_str(&identify.payload).unwrap();
+    assert_eq!(payload["d"]["token"], REDACTED);
+    assert_eq!(payload["d"]["intents"], 513);
+
+    // Voice and interaction tokens too, and the result still parses
+    for event in ["VOICE_SERVER_UPDATE", "INTERACTION_CREATE"] {
+        let frame = dispatch(event, 1, fixture(event));
+        let payload: Value = serde_json::from_str(&frame.payload).unwrap();
+        assert_eq!(payload["d"]["token"], REDACTED, "{}", event);
+        assert!(
+            parse_gateway_blob(frame.payload.as_bytes()).is_ok(),
+            "{}",
+            event
+        );
+    }
+
+    // Anything that isn't JSON still loses the bot's token
+    let frame = GatewayFrame::new(
+        GatewayFrameDirection::Received,
+        format!("oops {}", TOKEN).as_bytes(),
+        TOKEN,
+    );
+    assert_eq!(frame.payload, format!("oops {}", REDACTED));
+}
+
+#[test]
+fn replays_like_the_live_connection() {
+    let guild_delete = |id: &str| json!({ "id": id, "unavailable": true });
+    let frames = vec![
+        op(10, json!({ "heartbeat_interval": 41250 })),
+        sent(GatewaySendEvent::Heartbeat { seq: None }),
+        dispatch("READY", 1, fixture("READY")),
+        dispatch("GUILD_DELETE", 2, guild_delete("1")),
+        op(11, Value::Null),
+        // Discord asked for a reconnect. The old websocket closing isn't recorded.
+        op(7, Value::Null),
+        op(10, json!({ "heartbeat_interval": 41250 })),
+        dispatch("GUILD_DELETE", 2, guild_delete("1")),
+        dispatch("GUILD_DELETE", 3, guild_delete("2")),
+        dispatch("RESUMED", 4, Value::Null),
+    ];
+    let recording: Vec<u8> = frames.iter().flat_map(GatewayFrame::to_line).collect();
+    assert_eq!(read_recording(&recording).unwrap(), frames);
+
+    let mut replay = GatewayReplay::from_bytes(&recording).unwrap();
+    let mut sent_by_replay = Vec::new();
+    let mut events = Vec::new();
+    while let Some((frame, actions)) = replay.next_frame() {
+        assert_ne!(frame.direction, GatewayFrameDirection::Sent);
+        for action in actions.unwrap() {
+            match action {
+                GatewayAction::Send(event) => sent_by_replay.push(event),
+                GatewayAction::Dispatch(event) => events.push(event),
+                _ => {}
+            }
+        }
+    }
+
+    // The replayed seq 2 is dropped, as it was live
+    assert!(matches!(events[0], GatewayReceiveEvent::Ready(_)));
+    assert!(matches!(&events[1], GatewayReceiveEvent::GuildDelete(guild) if guild.id == "1"));
+    assert!(matches!(&events[2], GatewayReceiveEvent::GuildDelete(guild) if guild.id == "2"));
+    assert!(matches!(events[3], GatewayReceiveEvent::Resumed));
+    assert_eq!(events.len(), 4);
+    assert!(replay.connection.connected());
+    assert!(matches!(
+        sent_by_replay[1],
+        GatewaySendEvent::Resume { seq: 2, .. }
+    ));
+}
+
+#[test]
+fn records_zlib_stream_as_json() {
+    let mut zlib = Compress::new(Compression::default(), true);
+    let mut compress = |payload: Value| {
+        let mut compressed = Vec::with_capacity(4096);
+        zlib.compress_vec(
+            payload.to_string().as_bytes(),
+            &mut compressed,
+            FlushCompress::Sync,
+        )
+        .unwrap();
+        compressed
+    };
+    let hello =
+        compress(json!({ "op": 10, "t": null, "s": null, "d": { "heartbeat_interval": 41250 } }));
+    let ready = compress(json!({ "op": 0, "t": "READY", "s": 1, "d": fixture("READY") }));
+
+    // What the process does with each websocket message
+    let mut connection = GatewayConnection::new(TOKEN, 513, "");
+    connection.compress = true;
+    connection.connect();
+    let (first, second) = ready.split_at(ready.len() / 2);
+    let mut recording = Vec::new();
+    for message in [hello.as_slice(), first, second] {
+        if let Some(json) = connection.inflate(message).unwrap() {
+            let frame = GatewayFrame::new(GatewayFrameDirection::Received, &json, TOKEN);
+            recording.extend(frame.to_line());
+            connection.receive_json(&json).unwrap();
+        }
+    }
+    assert!(connection.connected());
+
+    let events = GatewayReplay::from_bytes(&recording)
+        .unwrap()
+        .events()
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(matches!(events[0], GatewayReceiveEvent::Ready(_)));
+}
+
+#[test]
+fn bad_lines_are_reported() {
+    let mut recording = op(11, Value::Null).to_line();
+    recording.extend(b"\nnot a frame\n");
+    let error = read_recording(&recording).unwrap_err().to_string();
+    assert!(error.contains("line 3"), "{}", error);
+}
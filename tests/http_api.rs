@@ -0,0 +1,191 @@
+// Checks the bodies HttpApiCall builds, without sending them anywhere.
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+fn forum_post(files: Option<Vec<FileUpload>>) -> HttpApiCall {
+    HttpApiCall::Threads(ThreadsCall::CreateForumPost {
+        channel_id: "1044359423545921581".to_string(),
+        name: "Show your setup".to_string(),
+        auto_archive_duration: None,
+        rate_limit_per_user: None,
+        applied_tags: Some(vec!["1044359423545921590".to_string()]),
+        message: ForumThreadMessage {
+            content: Some("Mine first".to_string()),
+            embeds: None,
+            allowed_mentions: None,
+            components: None,
+            sticker_ids: None,
+            flags: None,
+        },
+        files,
+    })
+}
+
+#[test]
+fn unset_fields_are_left_out() {
+    let (_, _, body) = forum_post(None).to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({
+            "name": "Show your setup",
+            "applied_tags": ["1044359423545921590"],
+            "message": { "content": "Mine first" },
+        })
+    );
+
+    let rename = HttpApiCall::Channels(ChannelsCall::Update {
+        channel_id: "1044359423545921581".to_string(),
+        name: Some("setups".to_string()),
+        icon: None,
+        flags: None,
+        available_tags: None,
+        default_reaction_emoji: None,
+        default_sort_order: Some(SortOrderType::CreationDate),
+        default_forum_layout: None,
+        applied_tags: None,
+    });
+    let (_, _, body) = rename.to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "name": "setups", "default_sort_order": 1 })
+    );
+}
+
+#[test]
+fn multipart_boundary_is_per_request() {
+    let upload = |bytes: &[u8]| {
+        forum_post(Some(vec![FileUpload {
+            filename: "setup.png".to_string(),
+            content_type: Some("image/png".to_string()),
+            description: None,
+            bytes: bytes.to_vec(),
+        }]))
+    };
+    let boundary = |call: &HttpApiCall| {
+        call.content_type()
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap()
+            .to_string()
+    };
+
+    let first = upload(b"first image");
+    let second = upload(b"second image");
+    assert_ne!(boundary(&first), boundary(&second));
+
+    // The body is split on the boundary from content_type, which no part contains
+    let (_, _, body) = first.to_request();
+    let body = String::from_utf8(body).unwrap();
+    let delimiter = format!("--{}", boundary(&first));
+    assert_eq!(body.matches(&delimiter).count(), 3);
+    assert!(body.ends_with(&format!("{}--\r\n", delimiter)));
+
+    let sneaky = upload(format!("--{}", boundary(&first)).as_bytes());
+    assert_eq!(
+        String::from_utf8(sneaky.to_request().2)
+            .unwrap()
+            .matches(&format!("--{}", boundary(&sneaky)))
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn clearing_member_fields_survives_json() {
+    // As a parent sends it, inside DiscordApiRequest::Http
+    let json = serde_json::to_vec(&HttpApiCall::Members(MembersCall::remove_timeout(
+        "197038439483310086".to_string(),
+        "80351110224678912".to_string(),
+    )))
+    .unwrap();
+    let call: HttpApiCall = serde_json::from_slice(&json).unwrap();
+    let (_, _, body) = call.to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "communication_disabled_until": null })
+    );
+
+    // A field left out stays out
+    let call: HttpApiCall = serde_json::from_value(json!({
+        "Members": {
+            "Update": {
+                "guild_id": "197038439483310086",
+                "user_id": "80351110224678912",
+                "nick": null,
+                "roles": null,
+                "mute": null,
+                "deaf": null,
+                "flags": null,
+            }
+        }
+    }))
+    .unwrap();
+    let (_, _, body) = call.to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "nick": null })
+    );
+}
+
+#[test]
+fn polls_leave_out_unset_fields() {
+    let media = |text: &str| PollMedia {
+        text: Some(text.to_string()),
+        emoji: None,
+    };
+    let call = HttpApiCall::Messages(MessagesCall::Create {
+        channel_id: "1044359423545921581".to_string(),
+        content: "Lunch?".to_string(),
+        poll: Some(PollCreateRequest {
+            question: media("Where?"),
+            answers: vec![PollAnswer {
+                answer_id: None,
+                poll_media: media("Tacos"),
+            }],
+            duration: None,
+            allow_multiselect: Some(true),
+            layout_type: None,
+        }),
+    });
+    let (_, _, body) = call.to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap()["poll"],
+        json!({
+            "question": { "text": "Where?" },
+            "answers": [{ "poll_media": { "text": "Tacos" } }],
+            "allow_multiselect": true,
+        })
+    );
+}
+
+#[test]
+fn editing_a_reply_sends_only_set_fields() {
+    let call = HttpApiCall::Interactions(InteractionsCall::UpdateOriginalWebhookMessage {
+        webhook_id: "1044359423545921581".to_string(),
+        webhook_token: "token".to_string(),
+        thread_id: None,
+        message: WebhookMessage {
+            content: Some("Done!".to_string()),
+            ..Default::default()
+        },
+    });
+    let (_, _, body) = call.to_request();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "content": "Done!" })
+    );
+}
+
+#[test]
+fn multipart_filenames_stay_on_their_header_line() {
+    let call = forum_post(Some(vec![FileUpload {
+        filename: "a\"b\r\nContent-Type: text/html\r\n\r\nc.png".to_string(),
+        content_type: Some("image/png\r\nX-Injected: 1".to_string()),
+        description: None,
+        bytes: b"image".to_vec(),
+    }]));
+    let body = String::from_utf8(call.to_request().2).unwrap();
+    assert!(body.contains(
+        "filename=\"a_b__Content-Type: text/html____c.png\"\r\nContent-Type: image/png__X-Injected: 1\r\n\r\nimage\r\n"
+    ));
+}
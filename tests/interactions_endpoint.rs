@@ -0,0 +1,88 @@
+// Signs requests the way Discord does and checks which ones the verifier lets through.
+
+use std::collections::HashMap;
+
+use discord_api::*;
+use ed25519_dalek::{Signer, SigningKey};
+
+const TIMESTAMP: &str = "1710000000";
+const PING: &[u8] = br#"{"id":"1","application_id":"2","type":1,"token":"t","version":1}"#;
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+fn verifier() -> InteractionVerifier {
+    InteractionVerifier::new(&hex::encode(key().verifying_key().as_bytes())).unwrap()
+}
+
+fn sign(timestamp: &str, body: &[u8]) -> String {
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    hex::encode(key().sign(&message).to_bytes())
+}
+
+#[test]
+fn accepts_only_what_was_signed() {
+    let verifier = verifier();
+    let signature = sign(TIMESTAMP, PING);
+    assert!(verifier.verify(&signature, TIMESTAMP, PING).is_ok());
+
+    // Another body, another timestamp or another signature
+    let tampered = std::str::from_utf8(PING)
+        .unwrap()
+        .replace("\"type\":1", "\"type\":2");
+    assert!(verifier
+        .verify(&signature, TIMESTAMP, tampered.as_bytes())
+        .is_err());
+    assert!(verifier.verify(&signature, "1710000001", PING).is_err());
+    let mut flipped = hex::decode(&signature).unwrap();
+    flipped[0] ^= 1;
+    assert!(verifier
+        .verify(&hex::encode(flipped), TIMESTAMP, PING)
+        .is_err());
+}
+
+#[test]
+fn rejects_badly_encoded_signatures_and_keys() {
+    let verifier = verifier();
+    let signature = sign(TIMESTAMP, PING);
+    // Not hex, too short, too long
+    assert!(verifier
+        .verify(&signature.replace('a', "z"), TIMESTAMP, PING)
+        .is_err());
+    assert!(verifier.verify(&signature[..126], TIMESTAMP, PING).is_err());
+    assert!(verifier
+        .verify(&format!("{}00", signature), TIMESTAMP, PING)
+        .is_err());
+    assert!(InteractionVerifier::new("not a key").is_err());
+    assert!(InteractionVerifier::new(&"00".repeat(31)).is_err());
+
+    // The identity point is a weak key: (R = identity, s = 0) passes a plain
+    // verification for any message, strict verification refuses it
+    let weak = InteractionVerifier::new(&format!("01{}", "00".repeat(31))).unwrap();
+    let forged = format!("01{}", "00".repeat(63));
+    assert!(weak.verify(&forged, TIMESTAMP, PING).is_err());
+}
+
+#[test]
+fn answers_pings_and_refuses_unsigned_requests() {
+    let verifier = verifier();
+    let headers = |signature: &str| {
+        HashMap::from([
+            // Header names can arrive in any case
+            ("x-signature-ed25519".to_string(), signature.to_string()),
+            (TIMESTAMP_HEADER.to_string(), TIMESTAMP.to_string()),
+        ])
+    };
+    let handler = |_| panic!("a PING isn't passed on");
+
+    let response = verifier.handle(&headers(&sign(TIMESTAMP, PING)), PING, handler);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, br#"{"type":1}"#);
+
+    let response = verifier.handle(&headers(&sign("0", PING)), PING, handler);
+    assert_eq!(response.status, 401);
+    let response = verifier.handle(&HashMap::new(), PING, handler);
+    assert_eq!(response.status, 401);
+}
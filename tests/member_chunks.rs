@@ -0,0 +1,85 @@
+// Collects GUILD_MEMBERS_CHUNK replies the way the process does for RequestGuildMembers.
+
+use std::path::Path;
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+const GUILD: &str = "197038439483310086";
+
+fn fixture() -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/gateway/GUILD_MEMBERS_CHUNK.json");
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// Chunk `index` of `count` with the fixture's `member`th member
+fn chunk(index: u64, count: u64, member: usize, nonce: &str) -> GuildMembersChunk {
+    let mut chunk = fixture();
+    chunk["members"] = json!([chunk["members"][member]]);
+    chunk["chunk_index"] = json!(index);
+    chunk["chunk_count"] = json!(count);
+    chunk["nonce"] = json!(nonce);
+    serde_json::from_value(chunk).unwrap()
+}
+
+fn member_ids(members: &GuildMembers) -> Vec<&str> {
+    members
+        .members
+        .iter()
+        .map(|member| member.user.as_ref().unwrap().id.as_str())
+        .collect()
+}
+
+#[test]
+fn collects_chunks_in_any_order_once() {
+    let request = GuildMembersRequest::new(GUILD, MemberSelector::All);
+    let mut chunks = MemberChunks::new(request, "members-1");
+    assert!(!chunks.started());
+    assert!(!chunks.is_complete());
+
+    assert!(!chunks.matches(&chunk(0, 2, 0, "members-2")));
+    let last = chunk(1, 2, 1, "members-1");
+    assert!(chunks.matches(&last));
+    assert!(!chunks.add(last));
+    assert!(chunks.started());
+
+    // A repeated chunk doesn't count twice or add its members again
+    assert!(!chunks.add(chunk(1, 2, 1, "members-1")));
+    assert!(!chunks.is_complete());
+
+    assert!(chunks.add(chunk(0, 2, 0, "members-1")));
+    assert!(chunks.is_complete());
+    let fixture = fixture();
+    let members = chunks.finish();
+    assert_eq!(
+        member_ids(&members),
+        vec![
+            fixture["members"][1]["user"]["id"].as_str().unwrap(),
+            fixture["members"][0]["user"]["id"].as_str().unwrap(),
+        ]
+    );
+    assert_eq!(members.presences.len(), 2);
+}
+
+#[test]
+fn validates_selectors() {
+    let request = |members| GuildMembersRequest::new(GUILD, members);
+    let query = |query: &str, limit| MemberSelector::Query {
+        query: query.to_string(),
+        limit,
+    };
+    let user_ids = |count| MemberSelector::UserIds(vec!["80351110224678912".to_string(); count]);
+
+    assert!(request(MemberSelector::All).validate().is_ok());
+    assert!(request(query("nel", 1)).validate().is_ok());
+    assert!(request(query("nel", 100)).validate().is_ok());
+    assert!(request(query("nel", 0)).validate().is_err());
+    assert!(request(query("nel", 101)).validate().is_err());
+    // Every member, like All
+    assert!(request(query("", 0)).validate().is_ok());
+
+    assert!(request(user_ids(100)).validate().is_ok());
+    assert!(request(user_ids(101)).validate().is_err());
+    assert!(request(user_ids(0)).validate().is_err());
+}
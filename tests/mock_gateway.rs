@@ -0,0 +1,458 @@
+// Runs GatewayConnection over real websockets against scripted mock gateways, checking what
+// it sends back through the connection lifecycle. Needs --features mock_server.
+
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use discord_api::*;
+use serde_json::json;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+const TOKEN: &str = "my_token";
+const INTENTS: u128 = 513;
+
+// What a bot would do with GatewayActions, with a websocket and a heartbeat timer
+struct Client {
+    connection: GatewayConnection,
+    socket: Option<WebSocket<MaybeTlsStream<TcpStream>>>,
+    heartbeat_interval: Option<Duration>,
+    next_heartbeat: Instant,
+    events: Vec<GatewayReceiveEvent>,
+    failed: Option<GatewayClosed>,
+    // Reconnect delays asked for. The client doesn't wait them out.
+    delays: Vec<u64>,
+    // Lose close codes on the way, as http_client does
+    drop_close_codes: bool,
+}
+
+impl Client {
+    fn connect(server: &MockGatewayServer, compress: bool) -> Self {
+        let mut connection = GatewayConnection::new(TOKEN, INTENTS, &server.url());
+        connection.compress = compress;
+        let url = connection.connect();
+        let mut client = Self {
+            connection,
+            socket: None,
+            heartbeat_interval: None,
+            next_heartbeat: Instant::now(),
+            events: Vec::new(),
+            failed: None,
+            delays: Vec::new(),
+            drop_close_codes: false,
+        };
+        client.open(&url);
+        client
+    }
+
+    fn open(&mut self, url: &str) {
+        if let Some(mut socket) = self.socket.take() {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+        }
+        let (socket, _) = tungstenite::connect(url).unwrap();
+        if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream
+                .set_read_timeout(Some(Duration::from_millis(5)))
+                .unwrap();
+        }
+        self.socket = Some(socket);
+    }
+
+    // Runs until done says so, panicking after a few seconds
+    fn run_until(&mut self, done: impl Fn(&Client) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(self) {
+            assert!(Instant::now() < deadline, "timed out");
+            if self.heartbeat_interval.is_some() && Instant::now() >= self.next_heartbeat {
+                self.next_heartbeat += self.heartbeat_interval.unwrap();
+                let actions = self.connection.heartbeat();
+                self.apply(actions);
+            }
+
+            let Some(socket) = self.socket.as_mut() else {
+                std::thread::sleep(Duration::from_millis(5));
+                continue;
+            };
+            let actions = match socket.read() {
+                Ok(Message::Text(text)) => self.connection.receive(text.as_bytes()).unwrap(),
+                Ok(Message::Binary(bytes)) => self.connection.receive(&bytes).unwrap(),
+                Ok(Message::Close(frame)) => {
+                    let _ = socket.flush();
+                    self.socket = None;
+                    let code = frame.map(|frame| u16::from(frame.code));
+                    self.connection
+                        .on_close(code.filter(|_| !self.drop_close_codes))
+                }
+                Ok(_) => vec![],
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    vec![]
+                }
+                Err(_) => {
+                    self.socket = None;
+                    self.connection.on_close(None)
+                }
+            };
+            self.apply(actions);
+        }
+    }
+
+    fn apply(&mut self, actions: Vec<GatewayAction>) {
+        for action in actions {
+            match action {
+                GatewayAction::Send(event) => {
+                    let text = String::from_utf8(event.to_json_bytes()).unwrap();
+                    if let Some(socket) = self.socket.as_mut() {
+                        socket.send(Message::Text(text)).unwrap();
+                    }
+                }
+                GatewayAction::StartHeartbeat(interval) => {
+                    let interval = Duration::from_millis(interval);
+                    self.heartbeat_interval = Some(interval);
+                    self.next_heartbeat = Instant::now() + interval;
+                }
+                GatewayAction::Reconnect { url, delay_ms } => {
+                    self.delays.push(delay_ms);
+                    self.open(&url);
+                }
+                GatewayAction::Dispatch(event) => self.events.push(event),
+                GatewayAction::Fail(closed) => {
+                    self.socket = None;
+                    self.heartbeat_interval = None;
+                    self.failed = Some(closed);
+                }
+            }
+        }
+    }
+
+    // Guild ids of the GUILD_DELETEs dispatched, to spot dropped or doubled events
+    fn guild_deletes(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                GatewayReceiveEvent::GuildDelete(guild) => Some(guild.id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn resumed(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, GatewayReceiveEvent::Resumed))
+            .count()
+    }
+}
+
+fn guild_delete(id: u64) -> GatewayStep {
+    GatewayStep::Dispatch {
+        t: "GUILD_DELETE".to_string(),
+        d: json!({ "id": id.to_string(), "unavailable": true }),
+    }
+}
+
+fn ready() -> GatewayStep {
+    GatewayStep::Ready {
+        session_id: "session".to_string(),
+    }
+}
+
+#[test]
+fn identifies_then_heartbeats_on_time() {
+    let server = MockGatewayServer::start(vec![GatewayScript::new(vec![
+        GatewayStep::Hello(50),
+        GatewayStep::Expect(2),
+        ready(),
+        guild_delete(1),
+        GatewayStep::Expect(1),
+        GatewayStep::Expect(1),
+        GatewayStep::Expect(1),
+        // Asked for, it goes right away
+        GatewayStep::RequestHeartbeat,
+        GatewayStep::Expect(1),
+    ])])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.run_until(|_| server.received_op(0, 1).len() >= 4);
+
+    assert_eq!(server.connections(), vec!["/?v=9&encoding=json"]);
+    let identify = &server.received_op(0, 2)[0];
+    assert_eq!(identify.d()["token"], TOKEN);
+    assert_eq!(identify.d()["intents"], 513);
+    assert_eq!(identify.d()["properties"]["os"], "kinode");
+    assert!(client.connection.connected());
+    assert_eq!(client.guild_deletes(), vec!["1"]);
+
+    let heartbeats = server.received_op(0, 1);
+    assert_eq!(heartbeats[0].d(), &json!(2));
+    for pair in heartbeats[..3].windows(2) {
+        let gap = pair[1].at - pair[0].at;
+        assert!(gap >= Duration::from_millis(40), "{:?}", gap);
+        assert!(gap < Duration::from_millis(150), "{:?}", gap);
+    }
+    let requested = heartbeats[3].at - heartbeats[2].at;
+    assert!(requested < Duration::from_millis(40), "{:?}", requested);
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn resumes_after_op_7_without_repeating_events() {
+    let server = MockGatewayServer::start(vec![
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            guild_delete(1),
+            guild_delete(2),
+            GatewayStep::Reconnect,
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            // Seqs the client already has
+            GatewayStep::Replay(2),
+            GatewayStep::Replay(1),
+            guild_delete(3),
+            GatewayStep::Resumed,
+        ]),
+    ])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.run_until(|client| client.resumed() == 1);
+
+    let connections = server.connections();
+    assert_eq!(connections.len(), 2);
+    assert_eq!(connections[1], "/resume/?v=9&encoding=json");
+    let resume = &server.received_op(1, 6)[0];
+    assert_eq!(
+        resume.d(),
+        &json!({ "token": TOKEN, "session_id": "session", "seq": 3 })
+    );
+    assert!(server.received_op(1, 2).is_empty());
+    assert_eq!(client.guild_deletes(), vec!["1", "2", "3"]);
+    assert_eq!(client.connection.sequence, Some(5));
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn a_replacement_closing_before_resumed_reconnects() {
+    let server = MockGatewayServer::start(vec![
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            GatewayStep::Reconnect,
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Close(4000),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Resumed,
+        ]),
+    ])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.drop_close_codes = true;
+    client.run_until(|client| client.resumed() == 1);
+
+    assert_eq!(server.connections().len(), 3);
+    assert_eq!(client.delays, vec![0, 1000]);
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn invalid_sessions_resume_or_identify_again() {
+    let server = MockGatewayServer::start(vec![
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            guild_delete(1),
+            GatewayStep::InvalidSession(true),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::InvalidSession(false),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            guild_delete(1),
+        ]),
+    ])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.run_until(|client| client.guild_deletes().len() == 2);
+
+    let connections = server.connections();
+    assert_eq!(connections[1], "/resume/?v=9&encoding=json");
+    assert_eq!(server.received_op(1, 6)[0].d()["seq"], 2);
+    // A new session from the start, whose seqs start over
+    assert_eq!(connections[2], "/?v=9&encoding=json");
+    assert_eq!(server.received_op(2, 2).len(), 1);
+    assert_eq!(client.connection.sequence, Some(2));
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn late_acks_are_fine_but_missing_ones_reconnect() {
+    let server = MockGatewayServer::start(vec![
+        GatewayScript::new(vec![
+            GatewayStep::Hello(60),
+            GatewayStep::Expect(2),
+            ready(),
+            GatewayStep::Expect(1),
+            GatewayStep::Expect(1),
+            GatewayStep::Expect(1),
+            GatewayStep::Reconnect,
+        ])
+        .ack_after(Duration::from_millis(30)),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(60),
+            GatewayStep::Expect(6),
+            GatewayStep::Resumed,
+            GatewayStep::Sleep(Duration::from_millis(500)),
+        ])
+        .without_acks(),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(60),
+            GatewayStep::Expect(6),
+            GatewayStep::Resumed,
+        ]),
+    ])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.run_until(|client| client.resumed() == 2);
+
+    assert_eq!(server.connections().len(), 3);
+    assert!(server.received_op(0, 1).len() >= 3);
+    // One heartbeat went unanswered, the next tick gave up on the connection
+    assert_eq!(server.received_op(1, 1).len(), 1);
+    assert_eq!(server.received_op(2, 6)[0].d()["seq"], 2);
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn close_codes_resume_identify_or_stop() {
+    let server = MockGatewayServer::start(vec![
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            guild_delete(1),
+            GatewayStep::Close(4000),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Close(4009),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            GatewayStep::Close(4004),
+        ]),
+    ])
+    .unwrap();
+    let mut client = Client::connect(&server, false);
+    client.run_until(|client| client.failed.is_some());
+
+    let connections = server.connections();
+    assert_eq!(connections.len(), 3);
+    assert_eq!(connections[1], "/resume/?v=9&encoding=json");
+    assert_eq!(connections[2], "/?v=9&encoding=json");
+    assert_eq!(
+        client.failed,
+        Some(GatewayClosed {
+            code: 4004,
+            reason: "Authentication failed".to_string(),
+        })
+    );
+    assert!(!client.connection.resumable());
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn backs_off_then_gives_up_without_close_codes() {
+    // A bad token, as http_client sees it: the websocket just closes after Resume
+    let bad_token = || {
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Close(4004),
+        ])
+    };
+    let mut scripts = vec![
+        // A session in between starts the count over
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(2),
+            ready(),
+            GatewayStep::Close(4000),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Close(4000),
+        ]),
+        GatewayScript::new(vec![
+            GatewayStep::Hello(1000),
+            GatewayStep::Expect(6),
+            GatewayStep::Resumed,
+            GatewayStep::Close(4009),
+        ]),
+    ];
+    scripts.extend((0..MAX_GATEWAY_RECONNECTS).map(|_| bad_token()));
+    let server = MockGatewayServer::start(scripts).unwrap();
+    let mut client = Client::connect(&server, false);
+    client.drop_close_codes = true;
+    client.run_until(|client| client.failed.is_some());
+
+    assert_eq!(
+        client.delays,
+        vec![1000, 2000, 1000, 2000, 4000, 8000, 16000]
+    );
+    assert_eq!(
+        server.connections().len(),
+        3 + MAX_GATEWAY_RECONNECTS as usize
+    );
+    assert_eq!(
+        client.failed,
+        Some(GatewayClosed {
+            code: CLOSED_WITHOUT_CODE,
+            reason: "Closed, gave up after 5 reconnects".to_string(),
+        })
+    );
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
+
+#[test]
+fn reads_zlib_stream_split_over_messages() {
+    let server = MockGatewayServer::start(vec![GatewayScript::new(vec![
+        GatewayStep::Hello(1000),
+        GatewayStep::Expect(2),
+        ready(),
+        guild_delete(1),
+        guild_delete(2),
+    ])
+    .split_frames()])
+    .unwrap();
+    let mut client = Client::connect(&server, true);
+    client.run_until(|client| client.guild_deletes().len() == 2);
+
+    assert_eq!(
+        server.connections(),
+        vec!["/?v=9&encoding=json&compress=zlib-stream"]
+    );
+    assert!(client.connection.connected());
+    assert!(server.errors().is_empty(), "{:?}", server.errors());
+}
@@ -0,0 +1,458 @@
+// Sends HttpApiCalls to the mock REST server over real HTTP and checks the answers parse
+// as the crate's types. Needs --features mock_server.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+struct Reply {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+// Just enough HTTP/1.1 to talk to the mock
+fn send(server: &MockRestServer, call: &HttpApiCall) -> Reply {
+    send_with_auth(server, call, Some("Bot my_token"))
+}
+
+fn send_with_auth(server: &MockRestServer, call: &HttpApiCall, auth: Option<&str>) -> Reply {
+    let (url, method, body) = call.to_request_at(&server.base_url());
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target = format!("{}?{}", target, query);
+    }
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        method,
+        target,
+        server.address(),
+        call.content_type(),
+        body.len()
+    );
+    if let Some(auth) = auth {
+        request.push_str(&format!("Authorization: {}\r\n", auth));
+    }
+    request.push_str("\r\n");
+
+    let mut stream = TcpStream::connect(server.address()).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    stream.write_all(&body).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .unwrap()
+        .split(' ')
+        .nth(1)
+        .unwrap()
+        .parse()
+        .unwrap();
+    let headers = lines
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.to_ascii_lowercase(), value.to_string()))
+        .collect();
+    Reply {
+        status,
+        headers,
+        body: response[split + 4..].to_vec(),
+    }
+}
+
+// A guild with #general and one other member, nelly
+fn seeded() -> (MockRestServer, String, String, String) {
+    let server = MockRestServer::start().unwrap();
+    let mut discord = server.discord();
+    let guild_id = discord.add_guild("Discord Developers");
+    let channel_id = discord.add_channel(&guild_id, "general", 0);
+    let user_id = discord.add_user("nelly");
+    discord.add_member(&guild_id, &user_id);
+    drop(discord);
+    (server, guild_id, channel_id, user_id)
+}
+
+#[test]
+fn messages_round_trip() {
+    let (server, _, channel_id, user_id) = seeded();
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Messages(MessagesCall::Create {
+            channel_id: channel_id.clone(),
+            content: format!("hi {}", user_mention(&user_id)),
+            poll: None,
+        }),
+    );
+    assert_eq!(reply.status, 200);
+    let message: Message = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(message.channel_id, channel_id);
+    assert_eq!(message.mentions.unwrap()[0].id, user_id);
+    assert_eq!(
+        message.author.unwrap().id,
+        server.discord().bot_user_id().to_string()
+    );
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Messages(MessagesCall::Update {
+            channel_id: channel_id.clone(),
+            message_id: message.id.clone(),
+            content: Some("edited".to_string()),
+        }),
+    );
+    let edited: Message = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(edited.content.as_deref(), Some("edited"));
+    assert!(edited.edited_timestamp.is_some());
+
+    // Someone else's message can't be edited
+    let theirs = server.discord().add_message(&channel_id, &user_id, "mine");
+    let reply = send(
+        &server,
+        &HttpApiCall::Messages(MessagesCall::Update {
+            channel_id: channel_id.clone(),
+            message_id: theirs,
+            content: Some("not yours".to_string()),
+        }),
+    );
+    assert_eq!(reply.status, 403);
+    assert_eq!(reply.json()["code"], 50005);
+
+    // Every request was recorded as sent
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(
+        requests[0].path,
+        format!("/channels/{}/messages", channel_id)
+    );
+    assert_eq!(requests[0].header("Authorization"), Some("Bot my_token"));
+    assert_eq!(requests[1].json(), json!({ "content": "edited" }));
+}
+
+#[test]
+fn errors_look_like_discords() {
+    let (server, _, channel_id, _) = seeded();
+    let create = |channel_id: &str, content: String| {
+        HttpApiCall::Messages(MessagesCall::Create {
+            channel_id: channel_id.to_string(),
+            content,
+            poll: None,
+        })
+    };
+
+    let reply = send(&server, &create("1", "hi".to_string()));
+    assert_eq!(reply.status, 404);
+    assert_eq!(
+        reply.json(),
+        json!({ "message": "Unknown Channel", "code": 10003 })
+    );
+
+    let reply = send(&server, &create(&channel_id, String::new()));
+    assert_eq!(
+        (reply.status, reply.json()["code"].clone()),
+        (400, json!(50006))
+    );
+
+    let reply = send(&server, &create(&channel_id, "a".repeat(2001)));
+    assert_eq!(reply.json()["code"], 50035);
+    assert_eq!(
+        reply.json()["errors"]["content"]["_errors"][0]["code"],
+        "BASE_TYPE_MAX_LENGTH"
+    );
+
+    let reply = send_with_auth(&server, &create(&channel_id, "hi".to_string()), None);
+    assert_eq!(reply.status, 401);
+
+    // Routes the model doesn't cover are a 404 until stubbed
+    let call = HttpApiCall::Users(UsersCall::GetConnections);
+    assert_eq!(send(&server, &call).status, 404);
+    server.discord().respond(
+        "GET",
+        "/users/@me/connections",
+        MockResponse::json(200, &json!([])),
+    );
+    assert_eq!(send(&server, &call).json(), json!([]));
+}
+
+#[test]
+fn guild_channels_members_and_roles() {
+    let (server, guild_id, _, user_id) = seeded();
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Guilds(GuildsCall::Get {
+            guild_id: guild_id.clone(),
+            with_counts: Some(true),
+        }),
+    );
+    let guild: Guild = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(guild.approximate_member_count, Some(2));
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Channels(ChannelsCall::ListGuildChannels {
+            guild_id: guild_id.clone(),
+        }),
+    );
+    let channels: Vec<Channel> = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(channels[0].name.as_deref(), Some("general"));
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Roles(RolesCall::CreateGuildRole {
+            guild_id: guild_id.clone(),
+            role: GuildRole {
+                id: String::new(),
+                name: Some("Moderators".to_string()),
+                color: Some(3447003),
+                permissions: Some(8192),
+                hoist: Some(true),
+                mentionable: None,
+                unicode_emoji: None,
+            },
+        }),
+    );
+    let role: Role = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(role.permissions, "8192");
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Roles(RolesCall::AddGuildMemberRole {
+            guild_id: guild_id.clone(),
+            user_id: user_id.clone(),
+            role_id: role.id.clone(),
+        }),
+    );
+    assert_eq!(reply.status, 204);
+
+    let timeout = MembersCall::timeout(
+        guild_id.clone(),
+        user_id.clone(),
+        std::time::Duration::from_secs(60),
+    )
+    .unwrap();
+    send(&server, &HttpApiCall::Members(timeout));
+
+    // A nickname set then reset with null
+    for nick in [Some("Nelly".to_string()), None] {
+        send(
+            &server,
+            &HttpApiCall::Members(MembersCall::Update {
+                guild_id: guild_id.clone(),
+                user_id: user_id.clone(),
+                nick: Some(nick),
+                roles: None,
+                mute: None,
+                deaf: None,
+                channel_id: None,
+                communication_disabled_until: None,
+                flags: None,
+            }),
+        );
+    }
+    {
+        let discord = server.discord();
+        let member = discord.member(&guild_id, &user_id).unwrap();
+        assert!(member["nick"].is_null());
+        assert!(!member["communication_disabled_until"].is_null());
+    }
+
+    // Ban limits are checked before anything is sent
+    assert!(GuildsCall::ban(guild_id.clone(), user_id.clone(), Some(7 * 24 * 60 * 60)).is_ok());
+    assert!(GuildsCall::ban(
+        guild_id.clone(),
+        user_id.clone(),
+        Some(7 * 24 * 60 * 60 + 1)
+    )
+    .is_err());
+    assert!(GuildsCall::bulk_ban(guild_id.clone(), vec![], None).is_err());
+    let user_ids = vec![user_id.clone(); MAX_BULK_BAN_USERS + 1];
+    assert!(GuildsCall::bulk_ban(guild_id.clone(), user_ids, None).is_err());
+
+    let reply = send(
+        &server,
+        &HttpApiCall::Members(MembersCall::Search {
+            guild_id: guild_id.clone(),
+            limit: Some(10),
+            query: Some("NEL".to_string()),
+        }),
+    );
+    let members: Vec<GuildMember> = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].roles, vec![role.id.clone()]);
+    assert!(members[0].communication_disabled_until.is_some());
+
+    // Listing pages by user id
+    let bot_user_id = server.discord().bot_user_id().parse().unwrap();
+    let reply = send(
+        &server,
+        &HttpApiCall::Members(MembersCall::GetAll {
+            guild_id: guild_id.clone(),
+            limit: Some(1000),
+            after: Some(bot_user_id),
+        }),
+    );
+    let members: Vec<GuildMember> = serde_json::from_slice(&reply.body).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].user.as_ref().unwrap().id, user_id);
+}
+
+#[test]
+fn paginates_messages() {
+    let (server, _, channel_id, user_id) = seeded();
+    let ids: Vec<String> = (0..5)
+        .map(|i| {
+            server
+                .discord()
+                .add_message(&channel_id, &user_id, &format!("{}", i))
+        })
+        .collect();
+
+    let mut paginator = Paginator::new(HttpApiCall::Messages(MessagesCall::GetAll {
+        channel_id: channel_id.clone(),
+        around: None,
+        before: None,
+        after: None,
+        limit: Some(2),
+    }))
+    .unwrap();
+    let mut seen = Vec::new();
+    while let Some(call) = paginator.next_call() {
+        let reply = send(&server, call);
+        for message in paginator.advance_as::<Message>(&reply.body).unwrap() {
+            seen.push(message.id);
+        }
+    }
+    // Newest first, three pages
+    assert_eq!(seen, ids.into_iter().rev().collect::<Vec<_>>());
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn rate_limit_headers_and_429s() {
+    let (server, _, channel_id, _) = seeded();
+    let call = HttpApiCall::Channels(ChannelsCall::Get {
+        channel_id: channel_id.clone(),
+    });
+
+    let reply = send(&server, &call);
+    assert_eq!(reply.headers["x-ratelimit-limit"], "5");
+    assert_eq!(reply.headers["x-ratelimit-remaining"], "4");
+    let bucket = reply.headers["x-ratelimit-bucket"].clone();
+    assert_eq!(send(&server, &call).headers["x-ratelimit-remaining"], "3");
+
+    server.discord().rate_limit(MockRateLimit {
+        path: Some("/channels".to_string()),
+        retry_after: 1.5,
+        global: false,
+    });
+    let reply = send(&server, &call);
+    assert_eq!(reply.status, 429);
+    assert_eq!(reply.headers["retry-after"], "2");
+    assert_eq!(reply.headers["x-ratelimit-bucket"], bucket);
+    assert_eq!(reply.headers["x-ratelimit-scope"], "user");
+    assert_eq!(
+        reply.json(),
+        json!({ "message": "You are being rate limited.", "retry_after": 1.5, "global": false })
+    );
+
+    // Used up, the next one goes through
+    assert_eq!(send(&server, &call).status, 200);
+    assert_eq!(server.requests()[2].status, 429);
+
+    server.discord().rate_limit(MockRateLimit {
+        global: true,
+        ..MockRateLimit::new(0.25)
+    });
+    let reply = send(&server, &HttpApiCall::Gateway(GatewayCall::GetGateway));
+    assert_eq!(reply.headers["x-ratelimit-global"], "true");
+}
+
+#[test]
+fn oauth2_code_flow() {
+    let (server, _, _, user_id) = seeded();
+    let mut discord = server.discord();
+    let credentials = OAuth2Credentials::new(discord.bot_user_id(), discord.client_secret.clone());
+    let code =
+        discord.add_authorization_code(&user_id, &[OAuth2Scope::Identify, OAuth2Scope::GuildsJoin]);
+    drop(discord);
+
+    let exchange = HttpApiCall::OAuth(OAuthCall::ExchangeCode {
+        credentials: credentials.clone(),
+        code: code.clone(),
+        redirect_uri: "https://example.com/callback".to_string(),
+    });
+    let reply = send_with_auth(&server, &exchange, None);
+    assert_eq!(reply.status, 200);
+    let response: OAuth2TokenResponse = serde_json::from_slice(&reply.body).unwrap();
+    let mut token = OAuth2Token::new(&response);
+    assert!(token.has_scope(OAuth2Scope::GuildsJoin));
+    let request = server.requests().pop().unwrap();
+    assert_eq!(
+        request.header("Content-Type"),
+        Some("application/x-www-form-urlencoded")
+    );
+    assert!(String::from_utf8(request.body)
+        .unwrap()
+        .contains("grant_type=authorization_code"));
+
+    // Codes are single use
+    let reply = send_with_auth(&server, &exchange, None);
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.json()["error"], "invalid_grant");
+
+    // The token acts as the user
+    let me = HttpApiCall::OAuth(OAuthCall::GetAuthorization);
+    let bearer = token.auth().header().unwrap();
+    let reply = send_with_auth(&server, &me, Some(&bearer));
+    assert_eq!(reply.json()["user"]["username"], "nelly");
+
+    // A refresh replaces the old access token
+    let refresh = token.refresh_call(&credentials).unwrap();
+    let reply = send_with_auth(&server, &refresh, None);
+    let response: OAuth2TokenResponse = serde_json::from_slice(&reply.body).unwrap();
+    token.refreshed(&response, std::time::SystemTime::now());
+    assert_eq!(send_with_auth(&server, &me, Some(&bearer)).status, 401);
+    let bearer = token.auth().header().unwrap();
+    assert_eq!(send_with_auth(&server, &me, Some(&bearer)).status, 200);
+
+    let reply = send_with_auth(&server, &token.revoke_call(&credentials), None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(send_with_auth(&server, &me, Some(&bearer)).status, 401);
+    assert_eq!(send_with_auth(&server, &refresh, None).status, 400);
+
+    // Client credentials give a token without a refresh token
+    let reply = send_with_auth(
+        &server,
+        &HttpApiCall::OAuth(OAuthCall::ClientCredentials {
+            credentials: OAuth2Credentials::new(credentials.client_id.clone(), "wrong"),
+            scopes: vec![OAuth2Scope::Identify],
+        }),
+        None,
+    );
+    assert_eq!(reply.status, 401);
+    let reply = send_with_auth(
+        &server,
+        &HttpApiCall::OAuth(OAuthCall::ClientCredentials {
+            credentials,
+            scopes: vec![OAuth2Scope::Identify],
+        }),
+        None,
+    );
+    let response: OAuth2TokenResponse = serde_json::from_slice(&reply.body).unwrap();
+    assert!(response.refresh_token.is_none());
+    assert_eq!(response.scope, "identify");
+}
@@ -0,0 +1,86 @@
+// Authorization URLs and token expiry, which don't need a server.
+
+use std::time::{Duration, SystemTime};
+
+use discord_api::*;
+
+#[test]
+fn builds_authorization_urls() {
+    let url = AuthorizationUrl::new(
+        "157730590492196864",
+        &[OAuth2Scope::Identify, OAuth2Scope::GuildsJoin],
+    )
+    .redirect_uri("https://example.com/callback?from=discord")
+    .state("15773059ghq9183habn")
+    .prompt(OAuth2Prompt::None)
+    .build();
+    let url = url::Url::parse(&url).unwrap();
+    assert_eq!(url.as_str().split('?').next(), Some(OAUTH2_AUTHORIZE_URL));
+    let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let param = |key: &str| {
+        query
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(param("response_type"), Some("code"));
+    assert_eq!(
+        param("redirect_uri"),
+        Some("https://example.com/callback?from=discord")
+    );
+    assert_eq!(param("scope"), Some("identify guilds.join"));
+    assert_eq!(param("state"), Some("15773059ghq9183habn"));
+    assert_eq!(param("prompt"), Some("none"));
+
+    // A bot invite has no code to send back
+    let invite = AuthorizationUrl::new(
+        "157730590492196864",
+        &[OAuth2Scope::Bot, OAuth2Scope::ApplicationsCommands],
+    )
+    .permissions(2048)
+    .guild("197038439483310086", true)
+    .integration_type(ApplicationIntegrationType::GuildInstall)
+    .build();
+    assert_eq!(
+        invite,
+        "https://discord.com/oauth2/authorize?client_id=157730590492196864&scope=bot+applications.commands&permissions=2048&guild_id=197038439483310086&disable_guild_select=true&integration_type=0"
+    );
+}
+
+#[test]
+fn tracks_expiry() {
+    let response: OAuth2TokenResponse = serde_json::from_str(
+        r#"{
+          "access_token": "6qrZcUqja7812RVdnEKjpzOL4CvHBFG",
+          "token_type": "Bearer",
+          "expires_in": 604800,
+          "refresh_token": "D43f5y0ahjqew82jZ4NViEr2YafMKhue",
+          "scope": "identify"
+        }"#,
+    )
+    .unwrap();
+    let received = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut token = OAuth2Token::received_at(&response, received);
+    assert_eq!(token.expires(), received + Duration::from_secs(604800));
+    assert!(!token.needs_refresh(received + Duration::from_secs(86400)));
+    assert!(token.needs_refresh(token.expires() - Duration::from_secs(60)));
+    assert!(!token.is_expired(token.expires() - Duration::from_secs(60)));
+    assert!(token.is_expired(token.expires()));
+    assert_eq!(
+        token.auth().header().unwrap(),
+        "Bearer 6qrZcUqja7812RVdnEKjpzOL4CvHBFG"
+    );
+
+    // A refresh that doesn't rotate the refresh token keeps the old one
+    let response: OAuth2TokenResponse = serde_json::from_str(
+        r#"{ "access_token": "new", "token_type": "Bearer", "expires_in": 604800, "scope": "identify" }"#,
+    )
+    .unwrap();
+    token.refreshed(&response, token.expires());
+    assert_eq!(token.access_token, "new");
+    assert_eq!(
+        token.refresh_token.as_deref(),
+        Some("D43f5y0ahjqew82jZ4NViEr2YafMKhue")
+    );
+    assert!(!token.is_expired(received + Duration::from_secs(604800)));
+}
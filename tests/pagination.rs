@@ -0,0 +1,127 @@
+// Walks Paginators over canned pages, checking the cursor moves the way Discord expects.
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+fn messages(before: Option<&str>, after: Option<&str>, limit: i64) -> HttpApiCall {
+    HttpApiCall::Messages(MessagesCall::GetAll {
+        channel_id: "41771983423143937".to_string(),
+        around: None,
+        before: before.map(str::to_string),
+        after: after.map(str::to_string),
+        limit: Some(limit),
+    })
+}
+
+fn ids(ids: &[u64]) -> Vec<u8> {
+    let items: Vec<Value> = ids
+        .iter()
+        .map(|id| json!({ "id": id.to_string() }))
+        .collect();
+    serde_json::to_vec(&items).unwrap()
+}
+
+fn query(call: &HttpApiCall) -> String {
+    call.to_request().0.query().unwrap_or_default().to_string()
+}
+
+#[test]
+fn walks_back_until_a_short_page() {
+    let mut paginator = Paginator::new(messages(None, None, 3)).unwrap();
+    assert_eq!(paginator.direction(), PageDirection::Before);
+
+    // Items don't have to come in order, the cursor is the furthest one
+    let page = paginator.advance(&ids(&[30, 10, 20])).unwrap();
+    assert_eq!(page.len(), 3);
+    assert!(query(paginator.next_call().unwrap()).contains("before=10"));
+
+    let page = paginator.advance(&ids(&[9, 8])).unwrap();
+    assert_eq!(page.len(), 2);
+    assert!(paginator.is_done());
+    assert!(paginator.next_call().is_none());
+    assert_eq!(paginator.fetched(), 5);
+}
+
+#[test]
+fn walks_forward_from_after() {
+    let mut paginator = Paginator::new(messages(None, Some("5"), 2)).unwrap();
+    assert_eq!(paginator.direction(), PageDirection::After);
+    paginator.advance(&ids(&[7, 6])).unwrap();
+    assert!(query(paginator.next_call().unwrap()).contains("after=7"));
+    paginator.advance(&ids(&[])).unwrap();
+    assert!(paginator.is_done());
+}
+
+#[test]
+fn stops_at_max_items() {
+    let mut paginator = Paginator::new(messages(None, None, 3))
+        .unwrap()
+        .with_max_items(4);
+    assert_eq!(paginator.advance(&ids(&[30, 20, 10])).unwrap().len(), 3);
+    assert_eq!(paginator.advance(&ids(&[9, 8, 7])).unwrap().len(), 1);
+    assert!(paginator.is_done());
+    assert!(paginator.advance(&ids(&[6])).unwrap().is_empty());
+}
+
+#[test]
+fn members_and_archived_threads() {
+    // Members page by the user's id, as an integer
+    let mut members = Paginator::new(HttpApiCall::Members(MembersCall::GetAll {
+        guild_id: "197038439483310086".to_string(),
+        limit: Some(1),
+        after: None,
+    }))
+    .unwrap();
+    members
+        .advance(br#"[{ "user": { "id": "80351110224678912" } }]"#)
+        .unwrap();
+    assert!(query(members.next_call().unwrap()).contains("after=80351110224678912"));
+
+    // Archived threads page by archive time, and say when there are no more
+    let threads = |has_more: bool| {
+        serde_json::to_vec(&json!({
+            "threads": [
+                { "id": "1", "thread_metadata": { "archive_timestamp": "2024-01-02T00:00:00+00:00" } },
+                { "id": "2", "thread_metadata": { "archive_timestamp": "2024-01-01T00:00:00+00:00" } },
+            ],
+            "members": [],
+            "has_more": has_more,
+        }))
+        .unwrap()
+    };
+    let mut archived = Paginator::new(HttpApiCall::Threads(
+        ThreadsCall::ListPublicArchivedThreads {
+            channel_id: "41771983423143937".to_string(),
+            before: None,
+            limit: Some(2),
+        },
+    ))
+    .unwrap();
+    assert_eq!(archived.advance(&threads(true)).unwrap().len(), 2);
+    assert!(query(archived.next_call().unwrap()).contains("before=2024-01-01T00:00:00%2B00:00"));
+    archived.advance(&threads(false)).unwrap();
+    assert!(archived.is_done());
+}
+
+#[test]
+fn rejects_what_cannot_be_paged() {
+    assert!(Paginator::new(HttpApiCall::Users(UsersCall::GetMe)).is_err());
+    let around = HttpApiCall::Messages(MessagesCall::GetAll {
+        channel_id: "41771983423143937".to_string(),
+        around: Some("10".to_string()),
+        before: None,
+        after: None,
+        limit: None,
+    });
+    assert!(Paginator::new(around).is_err());
+
+    // A body that isn't a page is an error, and pages() stops there
+    let paginator = Paginator::new(messages(None, None, 2)).unwrap();
+    let mut bodies = vec![
+        br#"{ "message": "Missing Access", "code": 50001 }"#.to_vec(),
+        ids(&[4, 3]),
+    ];
+    let pages: Vec<_> = paginator.pages(|_| Ok(bodies.remove(0))).collect();
+    assert_eq!(pages.len(), 1);
+    assert!(pages[0].is_err());
+}
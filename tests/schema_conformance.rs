@@ -0,0 +1,305 @@
+// Checks the types against real-shaped payloads from Discord.
+//
+// tests/fixtures/gateway holds the `d` of one dispatch per gateway event, named after the
+// event, plus EVENT.variant.json for other shapes of the same event, and tests/fixtures/rest
+// the bodies of the main REST responses. Every payload must
+// deserialize and serialize back to the same JSON. Nulls are ignored since an Option can't
+// tell a null field from a missing one.
+
+use std::path::{Path, PathBuf};
+
+use discord_api::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+// Every dispatch event Discord sends that this crate handles
+const GATEWAY_EVENTS: &[&str] = &[
+    "READY",
+    "RESUMED",
+    "APPLICATION_COMMAND_PERMISSIONS_UPDATE",
+    "AUTO_MODERATION_RULE_CREATE",
+    "AUTO_MODERATION_RULE_UPDATE",
+    "AUTO_MODERATION_RULE_DELETE",
+    "AUTO_MODERATION_ACTION_EXECUTION",
+    "CHANNEL_CREATE",
+    "CHANNEL_UPDATE",
+    "CHANNEL_DELETE",
+    "CHANNEL_PINS_UPDATE",
+    "THREAD_CREATE",
+    "THREAD_UPDATE",
+    "THREAD_DELETE",
+    "THREAD_LIST_SYNC",
+    "THREAD_MEMBER_UPDATE",
+    "THREAD_MEMBERS_UPDATE",
+    "ENTITLEMENT_CREATE",
+    "ENTITLEMENT_UPDATE",
+    "ENTITLEMENT_DELETE",
+    "GUILD_CREATE",
+    "GUILD_UPDATE",
+    "GUILD_DELETE",
+    "GUILD_AUDIT_LOG_ENTRY_CREATE",
+    "GUILD_BAN_ADD",
+    "GUILD_BAN_REMOVE",
+    "GUILD_EMOJIS_UPDATE",
+    "GUILD_STICKERS_UPDATE",
+    "GUILD_INTEGRATIONS_UPDATE",
+    "GUILD_MEMBER_ADD",
+    "GUILD_MEMBER_REMOVE",
+    "GUILD_MEMBER_UPDATE",
+    "GUILD_MEMBERS_CHUNK",
+    "GUILD_ROLE_CREATE",
+    "GUILD_ROLE_UPDATE",
+    "GUILD_ROLE_DELETE",
+    "GUILD_SCHEDULED_EVENT_CREATE",
+    "GUILD_SCHEDULED_EVENT_UPDATE",
+    "GUILD_SCHEDULED_EVENT_DELETE",
+    "GUILD_SCHEDULED_EVENT_USER_ADD",
+    "GUILD_SCHEDULED_EVENT_USER_REMOVE",
+    "INTEGRATION_CREATE",
+    "INTEGRATION_UPDATE",
+    "INTEGRATION_DELETE",
+    "INTERACTION_CREATE",
+    "INVITE_CREATE",
+    "INVITE_DELETE",
+    "MESSAGE_CREATE",
+    "MESSAGE_UPDATE",
+    "MESSAGE_DELETE",
+    "MESSAGE_DELETE_BULK",
+    "MESSAGE_REACTION_ADD",
+    "MESSAGE_REACTION_REMOVE",
+    "MESSAGE_REACTION_REMOVE_ALL",
+    "MESSAGE_REACTION_REMOVE_EMOJI",
+    "MESSAGE_POLL_VOTE_ADD",
+    "MESSAGE_POLL_VOTE_REMOVE",
+    "PRESENCE_UPDATE",
+    "RATE_LIMITED",
+    "STAGE_INSTANCE_CREATE",
+    "STAGE_INSTANCE_UPDATE",
+    "STAGE_INSTANCE_DELETE",
+    "TYPING_START",
+    "USER_UPDATE",
+    "VOICE_STATE_UPDATE",
+    "VOICE_SERVER_UPDATE",
+    "WEBHOOKS_UPDATE",
+];
+
+// Other shapes an event comes in, as (event, fixture)
+const GATEWAY_VARIANTS: &[(&str, &str)] = &[
+    // The bot was removed, rather than the guild going down
+    ("GUILD_DELETE", "GUILD_DELETE.removed"),
+];
+
+#[test]
+fn every_gateway_event_has_a_fixture() {
+    let missing = GATEWAY_EVENTS
+        .iter()
+        .filter(|event| !fixture_path("gateway", event).exists())
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "no fixture for {:?}", missing);
+}
+
+#[test]
+fn gateway_events_round_trip() {
+    let mut failures = Vec::new();
+    let events = GATEWAY_EVENTS.iter().map(|event| (*event, *event));
+    for (event, fixture) in events.chain(GATEWAY_VARIANTS.iter().copied()) {
+        gateway_round_trip(event, fixture, &mut failures);
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn guild_delete_tells_removal_from_outage() {
+    let guild_delete = |fixture: &str| {
+        let d = read_fixture("gateway", fixture).unwrap();
+        let payload = serde_json::json!({ "op": 0, "s": 42, "t": "GUILD_DELETE", "d": d });
+        match parse_gateway_blob(payload.to_string().as_bytes())
+            .unwrap()
+            .0
+        {
+            GatewayReceiveEvent::GuildDelete(guild) => guild,
+            other => panic!("{:?}", other),
+        }
+    };
+    assert!(!guild_delete("GUILD_DELETE").removed());
+    assert!(guild_delete("GUILD_DELETE.removed").removed());
+}
+
+// Nulls are ignored by the round trips, so flags that are a null when set are checked here
+#[test]
+fn presence_flags_round_trip() {
+    let original = read_fixture("rest", "guild_roles").unwrap();
+    let roles: Vec<Role> = serde_json::from_value(original.clone()).unwrap();
+    let reserialized = serde_json::to_value(&roles).unwrap();
+    let tags = |role: &str| {
+        let position = roles.iter().position(|r| r.name == role).unwrap();
+        let tags = roles[position].tags.as_ref().unwrap();
+        for flag in [
+            "premium_subscriber",
+            "available_for_purchase",
+            "guild_connections",
+        ] {
+            assert_eq!(
+                original[position]["tags"].get(flag).is_some(),
+                reserialized[position]["tags"].get(flag).is_some(),
+                "{}.{}",
+                role,
+                flag
+            );
+        }
+        tags
+    };
+
+    let booster = tags("Server Booster");
+    assert!(booster.premium_subscriber);
+    assert!(!booster.available_for_purchase && !booster.guild_connections);
+    let linked = tags("Linked");
+    assert!(linked.guild_connections && !linked.premium_subscriber);
+    let supporter = tags("Supporter");
+    assert!(supporter.available_for_purchase && !supporter.premium_subscriber);
+    assert!(!tags("kinode-bot").premium_subscriber);
+}
+
+#[test]
+fn rest_responses_round_trip() {
+    let mut failures = Vec::new();
+    round_trip::<User>("user", &mut failures);
+    round_trip::<User>("current_user", &mut failures);
+    round_trip::<Guild>("guild", &mut failures);
+    round_trip::<Vec<Channel>>("guild_channels", &mut failures);
+    round_trip::<Channel>("thread", &mut failures);
+    round_trip::<Channel>("forum_channel", &mut failures);
+    round_trip::<Channel>("dm_channel", &mut failures);
+    round_trip::<Message>("message", &mut failures);
+    round_trip::<Vec<Message>>("channel_messages", &mut failures);
+    round_trip::<GuildMember>("guild_member", &mut failures);
+    round_trip::<Vec<Role>>("guild_roles", &mut failures);
+    round_trip::<Emoji>("emoji", &mut failures);
+    round_trip::<Vec<Ban>>("guild_bans", &mut failures);
+    round_trip::<BulkBanResponse>("bulk_ban", &mut failures);
+    round_trip::<PruneCount>("prune_count", &mut failures);
+    round_trip::<Sticker>("sticker", &mut failures);
+    round_trip::<Vec<Integration>>("guild_integrations", &mut failures);
+    round_trip::<Vec<ApplicationCommand>>("application_commands", &mut failures);
+    round_trip::<AutoModerationRule>("auto_moderation_rule", &mut failures);
+    round_trip::<AuditLog>("audit_log", &mut failures);
+    round_trip::<Vec<Entitlement>>("entitlements", &mut failures);
+    round_trip::<Vec<Sku>>("skus", &mut failures);
+    round_trip::<Subscription>("subscription", &mut failures);
+    round_trip::<GuildScheduledEvent>("guild_scheduled_event", &mut failures);
+    round_trip::<StageInstance>("stage_instance", &mut failures);
+    round_trip::<Vec<ThreadMember>>("thread_members", &mut failures);
+    round_trip::<PollAnswerVoters>("poll_answer_voters", &mut failures);
+    round_trip::<Application>("application", &mut failures);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+fn gateway_round_trip(event: &str, fixture: &str, failures: &mut Vec<String>) {
+    let Some(d) = read_fixture("gateway", fixture) else {
+        return;
+    };
+    let payload = serde_json::json!({ "op": 0, "s": 42, "t": event, "d": d });
+    let parsed = match parse_gateway_blob(payload.to_string().as_bytes()) {
+        Ok((parsed, _)) => parsed,
+        Err(e) => {
+            failures.push(format!("{}: {}", fixture, summary(&e.to_string())));
+            return;
+        }
+    };
+    let reserialized = event_data(serde_json::to_value(&parsed).unwrap());
+    check_lossless(fixture, &d, &reserialized, failures);
+}
+
+fn round_trip<T: DeserializeOwned + Serialize>(name: &str, failures: &mut Vec<String>) {
+    let Some(original) = read_fixture("rest", name) else {
+        failures.push(format!("{}: missing fixture", name));
+        return;
+    };
+    match serde_json::from_value::<T>(original.clone()) {
+        Ok(parsed) => {
+            let reserialized = serde_json::to_value(&parsed).unwrap();
+            check_lossless(name, &original, &reserialized, failures);
+        }
+        Err(e) => failures.push(format!("{}: {}", name, e)),
+    }
+}
+
+fn fixture_path(kind: &str, name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(kind)
+        .join(format!("{}.json", name))
+}
+
+fn read_fixture(kind: &str, name: &str) -> Option<Value> {
+    let bytes = std::fs::read(fixture_path(kind, name)).ok()?;
+    Some(serde_json::from_slice(&bytes).unwrap_or_else(|e| panic!("{}/{}: {}", kind, name, e)))
+}
+
+// GatewayReceiveEvent serializes as {"Variant": data}, the data is what Discord sent
+fn event_data(event: Value) -> Value {
+    match event {
+        Value::Object(map) if map.len() == 1 => map.into_iter().next().unwrap().1,
+        Value::String(_) => Value::Null, // Events without data, e.g. Resumed
+        other => other,
+    }
+}
+
+fn check_lossless(name: &str, original: &Value, reserialized: &Value, failures: &mut Vec<String>) {
+    let mut differences = Vec::new();
+    diff(
+        "",
+        &strip_nulls(original.clone()),
+        &strip_nulls(reserialized.clone()),
+        &mut differences,
+    );
+    for difference in differences {
+        failures.push(format!("{}: {}", name, difference));
+    }
+}
+
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, strip_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip_nulls).collect()),
+        value => value,
+    }
+}
+
+fn diff(path: &str, expected: &Value, actual: &Value, differences: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let path = format!("{}.{}", path, key);
+                match actual.get(key) {
+                    Some(actual) => diff(&path, value, actual, differences),
+                    None => differences.push(format!("{} was dropped", path)),
+                }
+            }
+            for key in actual.keys().filter(|key| !expected.contains_key(*key)) {
+                differences.push(format!("{}.{} was added", path, key));
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                diff(&format!("{}[{}]", path, i), expected, actual, differences);
+            }
+        }
+        (Value::Number(expected), Value::Number(actual))
+            if expected.as_f64() == actual.as_f64() => {}
+        (expected, actual) if expected == actual => {}
+        (expected, actual) => {
+            differences.push(format!("{} changed from {} to {}", path, expected, actual))
+        }
+    }
+}
+
+// parse_gateway_blob errors end with the whole payload, keep the message
+fn summary(error: &str) -> &str {
+    error.split(" with data").next().unwrap_or(error)
+}
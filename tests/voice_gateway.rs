@@ -0,0 +1,276 @@
+// Runs a VoiceConnection through a whole handshake against a mock voice gateway that answers
+// the way Discord's does.
+
+use discord_api::*;
+use serde_json::{json, Value};
+
+const GUILD: &str = "41771983423143937";
+const BOT: &str = "1101200000000000001";
+
+// Answers each voice gateway request with the payloads Discord would send
+struct MockVoiceGateway {
+    seq: u64,
+    sent: Vec<Value>,
+}
+
+impl MockVoiceGateway {
+    fn new() -> Self {
+        Self {
+            seq: 0,
+            sent: Vec::new(),
+        }
+    }
+
+    fn hello(&self) -> Vec<u8> {
+        json!({ "op": 8, "d": { "v": 8, "heartbeat_interval": 13750.25 } })
+            .to_string()
+            .into_bytes()
+    }
+
+    fn receive(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let payload: Value = serde_json::from_slice(bytes).unwrap();
+        self.sent.push(payload.clone());
+        let d = &payload["d"];
+        let replies = match payload["op"].as_u64().unwrap() {
+            0 => vec![json!({
+                "op": 2,
+                "d": {
+                    "ssrc": 5353,
+                    "ip": "127.0.0.1",
+                    "port": 50005,
+                    "modes": ["aead_xchacha20_poly1305_rtpsize", "aead_aes256_gcm_rtpsize", "xsalsa20_poly1305"],
+                    "heartbeat_interval": 1,
+                },
+            })],
+            1 => vec![json!({
+                "op": 4,
+                "d": { "mode": d["data"]["mode"], "secret_key": (0..32).collect::<Vec<u8>>() },
+            })],
+            3 => vec![json!({ "op": 6, "d": { "t": d["t"] } })],
+            7 => vec![json!({ "op": 9, "d": null })],
+            _ => vec![],
+        };
+        replies
+            .into_iter()
+            .map(|mut reply| {
+                self.seq += 1;
+                reply["seq"] = json!(self.seq);
+                reply.to_string().into_bytes()
+            })
+            .collect()
+    }
+}
+
+fn voice_state(session_id: &str) -> VoiceState {
+    serde_json::from_value(json!({
+        "guild_id": GUILD,
+        "channel_id": "1044359423545921580",
+        "user_id": BOT,
+        "session_id": session_id,
+        "deaf": false,
+        "mute": false,
+        "self_deaf": false,
+        "self_mute": false,
+        "self_video": false,
+        "suppress": false,
+        "request_to_speak_timestamp": null,
+    }))
+    .unwrap()
+}
+
+fn voice_server(endpoint: &str) -> VoiceServerUpdate {
+    serde_json::from_value(json!({ "token": "my_token", "guild_id": GUILD, "endpoint": endpoint }))
+        .unwrap()
+}
+
+// Feeds the gateway's replies back until nothing is left to send, returning what the parent was told
+fn run(
+    connection: &mut VoiceConnection,
+    gateway: &mut MockVoiceGateway,
+    incoming: Vec<Vec<u8>>,
+) -> (Vec<VoiceEvent>, Option<u64>) {
+    let mut queue = incoming;
+    let mut notified = Vec::new();
+    let mut heartbeat = None;
+    while !queue.is_empty() {
+        let bytes = queue.remove(0);
+        let (event, seq) = parse_voice_blob(&bytes).unwrap();
+        for action in connection.handle(event, seq) {
+            match action {
+                VoiceAction::Send(event) => queue.extend(gateway.receive(&event.to_json_bytes())),
+                VoiceAction::Notify(event) => notified.push(event),
+                VoiceAction::StartHeartbeat(interval) => heartbeat = Some(interval),
+            }
+        }
+    }
+    (notified, heartbeat)
+}
+
+#[test]
+fn connects_once_both_voice_events_arrived() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    assert!(!connection.on_voice_server(&voice_server("us-east123.discord.media:443")));
+    assert_eq!(connection.state, VoiceConnectionState::Joining);
+
+    // Someone else's voice state doesn't count
+    let mut other = voice_state("other");
+    other.user_id = "80351110224678912".to_string();
+    assert!(!connection.on_voice_state(&other));
+
+    assert!(connection.on_voice_state(&voice_state("session")));
+    assert_eq!(connection.state, VoiceConnectionState::Connecting);
+    assert_eq!(
+        connection.gateway_url().as_deref(),
+        Some("wss://us-east123.discord.media:443/?v=8")
+    );
+}
+
+#[test]
+fn handshake_against_mock_voice_gateway() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    let mut gateway = MockVoiceGateway::new();
+    connection.on_voice_state(&voice_state("session"));
+    connection.on_voice_server(&voice_server("localhost:8080"));
+
+    let hello = gateway.hello();
+    let (notified, heartbeat) = run(&mut connection, &mut gateway, vec![hello]);
+    assert_eq!(heartbeat, Some(13750));
+    assert_eq!(
+        gateway.sent[0],
+        json!({
+            "op": 0,
+            "d": { "server_id": GUILD, "user_id": BOT, "session_id": "session", "token": "my_token" },
+        })
+    );
+    let [VoiceEvent::Ready { guild_id, ready }] = notified.as_slice() else {
+        panic!("expected Ready, got {:?}", notified);
+    };
+    assert_eq!(guild_id, GUILD);
+    assert_eq!(
+        (ready.ssrc, ready.ip.as_str(), ready.port),
+        (5353, "127.0.0.1", 50005)
+    );
+    assert_eq!(connection.state, VoiceConnectionState::SelectingProtocol);
+
+    // The audio side did IP discovery, the preferred mode both sides support is picked
+    let select = connection
+        .select_protocol("203.0.113.7", 61234, None)
+        .unwrap();
+    let replies = gateway.receive(&select.to_json_bytes());
+    let (notified, _) = run(&mut connection, &mut gateway, replies);
+    assert_eq!(
+        gateway.sent[1]["d"]["data"]["mode"],
+        "aead_aes256_gcm_rtpsize"
+    );
+    let [VoiceEvent::SessionDescription {
+        ssrc,
+        port,
+        description,
+        ..
+    }] = notified.as_slice()
+    else {
+        panic!("expected SessionDescription, got {:?}", notified);
+    };
+    assert_eq!((*ssrc, *port), (5353, 50005));
+    assert_eq!(description.mode, "aead_aes256_gcm_rtpsize");
+    assert_eq!(description.secret_key.len(), 32);
+    assert_eq!(connection.state, VoiceConnectionState::Connected);
+
+    // Heartbeats acknowledge the last seq the gateway sent
+    let heartbeat = connection.heartbeat();
+    let replies = gateway.receive(&heartbeat.to_json_bytes());
+    run(&mut connection, &mut gateway, replies);
+    assert_eq!(
+        gateway.sent[2],
+        json!({ "op": 3, "d": { "t": 1, "seq_ack": 2 } })
+    );
+
+    let speaking = connection.speaking(true).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&speaking.to_json_bytes()).unwrap(),
+        json!({ "op": 5, "d": { "speaking": 1, "delay": 0, "ssrc": 5353 } })
+    );
+}
+
+#[test]
+fn reopened_websocket_resumes() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    let mut gateway = MockVoiceGateway::new();
+    connection.on_voice_state(&voice_state("session"));
+    connection.on_voice_server(&voice_server("localhost:8080"));
+    let hello = gateway.hello();
+    run(&mut connection, &mut gateway, vec![hello]);
+
+    let hello = gateway.hello();
+    run(&mut connection, &mut gateway, vec![hello]);
+    assert_eq!(gateway.sent.last().unwrap()["op"], 7);
+    assert_eq!(gateway.sent.last().unwrap()["d"]["seq_ack"], 1);
+
+    // A new voice server needs a fresh Identify
+    assert!(connection.on_voice_server(&voice_server("us-west1.discord.media")));
+    let hello = gateway.hello();
+    run(&mut connection, &mut gateway, vec![hello]);
+    assert_eq!(gateway.sent.last().unwrap()["op"], 0);
+}
+
+#[test]
+fn select_protocol_needs_a_common_mode() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    assert!(connection
+        .select_protocol("203.0.113.7", 61234, None)
+        .is_err());
+
+    let ready = json!({ "op": 2, "d": { "ssrc": 1, "ip": "127.0.0.1", "port": 1, "modes": ["xsalsa20_poly1305"] } });
+    let (event, seq) = parse_voice_blob(ready.to_string().as_bytes()).unwrap();
+    connection.handle(event, seq);
+    assert!(connection
+        .select_protocol("203.0.113.7", 61234, None)
+        .is_err());
+    assert!(connection
+        .select_protocol("203.0.113.7", 61234, Some("xsalsa20_poly1305".to_string()))
+        .is_ok());
+}
+
+#[test]
+fn dropped_websocket_reopens_until_fatal() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    let mut gateway = MockVoiceGateway::new();
+    connection.on_voice_state(&voice_state("session"));
+    connection.on_voice_server(&voice_server("localhost:8080"));
+    let hello = gateway.hello();
+    run(&mut connection, &mut gateway, vec![hello]);
+
+    // Replacing the websocket isn't a drop
+    connection.replace_websocket();
+    assert_eq!(connection.on_close(None), VoiceClose::Ignore);
+
+    let url = connection.gateway_url().unwrap();
+    assert_eq!(connection.on_close(None), VoiceClose::Reopen(url.clone()));
+    let hello = gateway.hello();
+    run(&mut connection, &mut gateway, vec![hello]);
+    assert_eq!(gateway.sent.last().unwrap()["op"], 7);
+    assert_eq!(connection.on_close(Some(4015)), VoiceClose::Reopen(url));
+
+    // Kicked from the channel
+    assert_eq!(connection.on_close(Some(4014)), VoiceClose::Disconnect);
+}
+
+#[test]
+fn gives_up_after_too_many_resumes() {
+    let mut connection = VoiceConnection::new(GUILD, BOT);
+    let mut gateway = MockVoiceGateway::new();
+    connection.on_voice_state(&voice_state("session"));
+    connection.on_voice_server(&voice_server("localhost:8080"));
+
+    for _ in 0..MAX_VOICE_RESUMES {
+        assert!(matches!(connection.on_close(None), VoiceClose::Reopen(_)));
+    }
+    assert_eq!(connection.on_close(None), VoiceClose::Disconnect);
+
+    // Getting back to Resumed starts the count over
+    let resumed = json!({ "op": 9, "d": null, "seq": 1 })
+        .to_string()
+        .into_bytes();
+    run(&mut connection, &mut gateway, vec![resumed]);
+    assert!(matches!(connection.on_close(None), VoiceClose::Reopen(_)));
+}
@@ -0,0 +1,150 @@
+// Streams Opus frames through a local UDP echo stand-in for the voice server, which also
+// answers IP discovery, and decrypts what comes back.
+
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::Aes256Gcm;
+use discord_api::*;
+
+const SSRC: u32 = 5353;
+const KEY: [u8; 32] = [7; 32];
+
+// Answers IP discovery like Discord does and echoes everything else, until it goes quiet
+fn echo_server() -> UdpSocket {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let socket = server.try_clone().unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 1500];
+        while let Ok((len, from)) = socket.recv_from(&mut buffer) {
+            let packet = &buffer[..len];
+            if len == 74 && packet[0..2] == [0, 1] {
+                let mut response = [0; 74];
+                response[0..2].copy_from_slice(&2u16.to_be_bytes());
+                response[2..4].copy_from_slice(&70u16.to_be_bytes());
+                response[4..8].copy_from_slice(&packet[4..8]);
+                let address = from.ip().to_string();
+                response[8..8 + address.len()].copy_from_slice(address.as_bytes());
+                response[72..74].copy_from_slice(&from.port().to_be_bytes());
+                socket.send_to(&response, from).unwrap();
+            } else {
+                socket.send_to(packet, from).unwrap();
+            }
+        }
+    });
+    server
+}
+
+fn client(server: &UdpSocket) -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.connect(server.local_addr().unwrap()).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    socket
+}
+
+#[test]
+fn ip_discovery_finds_our_address() {
+    let server = echo_server();
+    let socket = client(&server);
+    let (address, port) = discover_ip(&socket, SSRC).unwrap();
+    assert_eq!(address, "127.0.0.1");
+    assert_eq!(port, socket.local_addr().unwrap().port());
+}
+
+#[test]
+fn streams_frames_then_silence_in_every_mode() {
+    for mode in VOICE_ENCRYPTION_MODES
+        .iter()
+        .copied()
+        .chain(["xsalsa20_poly1305"])
+    {
+        let server = echo_server();
+        let socket = client(&server);
+        let frames = vec![vec![1, 2, 3], vec![4, 5, 6, 7], vec![8]];
+
+        let mut sender = VoiceSender::new(SSRC, mode, &KEY).unwrap();
+        let started = Instant::now();
+        assert_eq!(sender.play(&socket, frames.clone()).unwrap(), 8);
+        // The first packet goes right away, then one every 20ms
+        assert!(started.elapsed() >= OPUS_FRAME_DURATION * 7, "{}", mode);
+
+        let mut receiver = VoiceReceiver::new(mode, &KEY).unwrap();
+        let mut buffer = [0; 1500];
+        for i in 0..8 {
+            let len = socket.recv(&mut buffer).unwrap();
+            // Encrypted on the wire
+            assert_ne!(&buffer[RTP_HEADER_LEN..len], OPUS_SILENCE_FRAME.as_slice());
+            let packet = receiver.receive(&buffer[..len]).unwrap().unwrap();
+            assert_eq!(packet.header.ssrc, SSRC);
+            assert_eq!(packet.header.sequence, i);
+            assert_eq!(packet.header.timestamp, i as u32 * OPUS_FRAME_SAMPLES);
+            match frames.get(i as usize) {
+                Some(frame) => assert_eq!(&packet.opus, frame, "{}", mode),
+                None => assert_eq!(packet.opus, OPUS_SILENCE_FRAME, "{}", mode),
+            }
+        }
+        assert_eq!(receiver.stream(SSRC).unwrap().packets, 8);
+    }
+}
+
+#[test]
+fn receiver_drops_late_packets_and_rtcp() {
+    let mut sender = VoiceSender::new(SSRC, "aead_aes256_gcm_rtpsize", &KEY).unwrap();
+    let first = sender.packet(&[1]).unwrap();
+    let second = sender.packet(&[2]).unwrap();
+
+    let mut receiver = VoiceReceiver::new("aead_aes256_gcm_rtpsize", &KEY).unwrap();
+    receiver.set_user(SSRC, "80351110224678912");
+    assert!(receiver.receive(&second).unwrap().is_some());
+    assert!(receiver.receive(&first).unwrap().is_none());
+    assert!(receiver.receive(&second).unwrap().is_none());
+    assert!(receiver.receive(&[0x81, 201, 0, 7]).unwrap().is_none());
+    let stream = receiver.stream(SSRC).unwrap();
+    assert_eq!(stream.user_id.as_deref(), Some("80351110224678912"));
+    assert_eq!(stream.packets, 1);
+
+    // Another key can't read it
+    let mut other = VoiceReceiver::new("aead_aes256_gcm_rtpsize", &[8; 32]).unwrap();
+    assert!(other.receive(&second).is_err());
+}
+
+#[test]
+fn strips_the_header_extension_discord_adds() {
+    // X bit set, one word of extension whose body is encrypted with the payload
+    let mut packet = RtpHeader {
+        sequence: 9,
+        timestamp: 960,
+        ssrc: 42,
+    }
+    .to_bytes()
+    .to_vec();
+    packet[0] |= 0x10;
+    packet.extend([0xBE, 0xDE, 0x00, 0x01]);
+    let counter = 3u32.to_be_bytes();
+    let mut nonce = [0; 12];
+    nonce[..4].copy_from_slice(&counter);
+    let ciphertext = Aes256Gcm::new_from_slice(&KEY)
+        .unwrap()
+        .encrypt(
+            (&nonce).into(),
+            Payload {
+                msg: &[0x10, 0xFF, 0x00, 0x00, 0xAA, 0xBB],
+                aad: &packet,
+            },
+        )
+        .unwrap();
+    packet.extend(ciphertext);
+    packet.extend(counter);
+
+    let mut receiver = VoiceReceiver::new("aead_aes256_gcm_rtpsize", &KEY).unwrap();
+    let packet = receiver.receive(&packet).unwrap().unwrap();
+    assert_eq!(packet.header.ssrc, 42);
+    assert_eq!(packet.opus, vec![0xAA, 0xBB]);
+}